@@ -0,0 +1,34 @@
+#version 450
+
+layout(location = 0) in vec2 tex_coords;
+layout(location = 1) in vec4 tint;
+layout(location = 2) flat in uint id;
+layout(location = 3) in vec3 position;
+
+layout(location = 0) out vec4 f_color;
+// The entity ID attachment only exists when picking is on.
+#ifdef PICKING
+layout(location = 1) out uint f_id;
+#endif
+layout(set = 0, binding = 0) uniform sampler2D tex;
+
+#include "encoding.glsl"
+#include "lighting.glsl"
+
+layout(set = 0, binding = 1) uniform Data {
+    mat4 view_projection;
+    Light lights[MAX_LIGHTS];
+    uint light_count;
+    uint output_encoding;
+} uniforms;
+
+void main() {
+    // The texture is sRGB, so sampling it gives linear colors.
+    vec4 color = texture(tex, tex_coords) * tint;
+    color = shade(color, position, uniforms.lights, uniforms.light_count);
+
+    f_color = encode_output(color, uniforms.output_encoding);
+#ifdef PICKING
+    f_id = id;
+#endif
+}
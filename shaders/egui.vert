@@ -0,0 +1,30 @@
+#version 450
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec2 tex_coords;
+
+layout(location = 0) out vec2 v_tex_coords;
+layout(location = 1) out vec4 v_color;
+layout(location = 2) flat out uint v_id;
+layout(location = 3) out vec3 v_position;
+
+// The start of the block `egui.frag` declares in full.
+layout(set = 0, binding = 1) uniform Data {
+    mat4 view_projection;
+} uniforms;
+
+layout(push_constant) uniform PushConstants {
+    mat4 model;
+    vec4 color;
+    // 0 for nothing, the index of the entity plus 1 otherwise.
+    uint id;
+} entity;
+
+void main() {
+    vec4 world = entity.model * vec4(position, 1.0);
+    gl_Position = uniforms.view_projection * world;
+    v_position = world.xyz;
+    v_tex_coords = tex_coords;
+    v_color = entity.color;
+    v_id = entity.id;
+}
@@ -0,0 +1,47 @@
+// Encodes the linear output of a fragment shader for the swapchain, as `OutputEncoding` says.
+// Included by every fragment shader that writes to the swapchain, so the output looks the same
+// whichever format and color space were picked.
+
+// Must match `OutputEncoding`.
+const uint OUTPUT_HARDWARE = 0;
+const uint OUTPUT_SRGB = 1;
+const uint OUTPUT_PQ = 2;
+const uint OUTPUT_LINEAR = 3;
+
+// Where SDR white ends up in HDR10, in nits.
+const float SDR_WHITE_NITS = 203.0;
+
+vec3 srgb_encode(vec3 linear) {
+    vec3 low = linear * 12.92;
+    vec3 high = 1.055 * pow(linear, vec3(1.0 / 2.4)) - 0.055;
+    return mix(low, high, step(0.0031308, linear));
+}
+
+vec3 pq_encode(vec3 linear) {
+    // Rec. 709 primaries to Rec. 2020, column by column.
+    const mat3 to_rec2020 = mat3(
+        0.6274, 0.0691, 0.0164,
+        0.3293, 0.9195, 0.0880,
+        0.0433, 0.0114, 0.8956
+    );
+    vec3 y = clamp(to_rec2020 * linear * (SDR_WHITE_NITS / 10000.0), 0.0, 1.0);
+
+    const float m1 = 0.1593017578125;
+    const float m2 = 78.84375;
+    const float c1 = 0.8359375;
+    const float c2 = 18.8515625;
+    const float c3 = 18.6875;
+    vec3 p = pow(y, vec3(m1));
+    return pow((c1 + c2 * p) / (1.0 + c3 * p), vec3(m2));
+}
+
+vec4 encode_output(vec4 color, uint encoding) {
+    switch (encoding) {
+        case OUTPUT_SRGB:
+            return vec4(srgb_encode(color.rgb), color.a);
+        case OUTPUT_PQ:
+            return vec4(pq_encode(color.rgb), color.a);
+        default:
+            return color;
+    }
+}
@@ -0,0 +1,46 @@
+// Shades with the lights of the scene, see `scene::Light`. Included by the fragment shaders
+// drawing scene entities, which have no normals, so the normal is taken from the triangle.
+
+// Must match `scene::MAX_LIGHTS`.
+#define MAX_LIGHTS 8
+
+// As `scene::Light::shader_data` writes it.
+struct Light {
+    // The position of a point light with w = 1, or the direction a directional light travels
+    // in with w = 0.
+    vec4 position;
+    // The color, with the radius of a point light in w.
+    vec4 color;
+};
+
+// How much of the base color is seen where no light reaches.
+const float AMBIENT = 0.1;
+
+// The light `position` on a triangle facing `normal` gets from `light`. Sprites have no back,
+// so both sides of the triangle are lit.
+vec3 light_contribution(Light light, vec3 position, vec3 normal) {
+    if (light.position.w == 0.0) {
+        vec3 to_light = -normalize(light.position.xyz);
+        return light.color.rgb * abs(dot(normal, to_light));
+    }
+
+    vec3 to_light = light.position.xyz - position;
+    float distance = length(to_light);
+    float falloff = clamp(1.0 - distance / light.color.w, 0.0, 1.0);
+    return light.color.rgb * abs(dot(normal, to_light / max(distance, 1e-4))) * falloff * falloff;
+}
+
+// `color` lit by the first `count` of `lights`. A scene without lights is drawn unlit.
+vec4 shade(vec4 color, vec3 position, Light lights[MAX_LIGHTS], uint count) {
+    if (count == 0u) {
+        return color;
+    }
+
+    // Taken outside of the loop, derivatives are only defined in uniform control flow.
+    vec3 normal = normalize(cross(dFdx(position), dFdy(position)));
+    vec3 light = vec3(AMBIENT);
+    for (uint i = 0u; i < min(count, uint(MAX_LIGHTS)); i++) {
+        light += light_contribution(lights[i], position, normal);
+    }
+    return vec4(color.rgb * light, color.a);
+}
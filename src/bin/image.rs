@@ -0,0 +1,5 @@
+use vulkan_rust::demos::Demo;
+
+fn main() {
+    vulkan_rust::cli::run_demo(Demo::Image);
+}
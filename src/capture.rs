@@ -0,0 +1,101 @@
+//! Saving a rendered frame as a PNG, for `--output`.
+//!
+//! `--output` implies `--headless`, so the image copied is the offscreen one the frame was drawn
+//! to, which [`setup::create_offscreen_image`](crate::setup::create_offscreen_image) creates
+//! with the `transfer_src` usage. The copy is recorded into the frame's own command buffer,
+//! after its render pass, and saved once the frame's fence has been waited for.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::allocator::CommandBufferAllocator;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CopyImageToBufferInfo};
+use vulkano::format::Format;
+use vulkano::image::ImageAccess;
+use vulkano::memory::allocator::MemoryAllocator;
+
+use crate::error::{Context, Error, Result};
+
+pub struct FrameCapture {
+    buffer: Arc<CpuAccessibleBuffer<[u8]>>,
+    extent: [u32; 2],
+    format: Format,
+    path: PathBuf,
+}
+
+impl FrameCapture {
+    /// Records a copy of `image` to be saved to `path`. Only 8 bit RGBA and BGRA images can be
+    /// saved.
+    pub fn record<L, A: CommandBufferAllocator>(
+        builder: &mut AutoCommandBufferBuilder<L, A>,
+        allocator: &(impl MemoryAllocator + ?Sized),
+        image: Arc<dyn ImageAccess>,
+        path: &Path,
+    ) -> Result<Self> {
+        let format = image.format();
+        if !matches!(
+            format,
+            Format::B8G8R8A8_SRGB
+                | Format::B8G8R8A8_UNORM
+                | Format::R8G8B8A8_SRGB
+                | Format::R8G8B8A8_UNORM
+        ) {
+            return Err(Error::Unsupported(
+                "only 8 bit RGBA and BGRA frames can be saved",
+            ));
+        }
+
+        let extent = image.dimensions().width_height();
+        let buffer = CpuAccessibleBuffer::from_iter(
+            allocator,
+            BufferUsage {
+                transfer_dst: true,
+                ..BufferUsage::empty()
+            },
+            false,
+            (0..extent[0] * extent[1] * 4).map(|_| 0u8),
+        )
+        .context("can't create capture buffer")?;
+
+        builder
+            .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(image, buffer.clone()))
+            .context("can't copy frame to capture buffer")?;
+
+        Ok(Self {
+            buffer,
+            extent,
+            format,
+            path: path.to_owned(),
+        })
+    }
+
+    /// Writes the copy out. Only call this once the frame's submission has finished.
+    pub fn save(&self) -> Result<()> {
+        let mut pixels = self
+            .buffer
+            .read()
+            .context("can't read capture buffer")?
+            .to_vec();
+        if matches!(self.format, Format::B8G8R8A8_SRGB | Format::B8G8R8A8_UNORM) {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        let file = File::create(&self.path).context("can't create output file")?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.extent[0], self.extent[1]);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .context("can't write png header")?
+            .write_image_data(&pixels)
+            .context("can't write png data")?;
+
+        log::info!("saved frame to {}", self.path.display());
+        Ok(())
+    }
+}
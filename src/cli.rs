@@ -0,0 +1,490 @@
+//! The command line every demo shares, whether it's started through the `vulkan-rust` launcher
+//! or through its own binary.
+//!
+//! Flags are parsed by hand like the environment variables the other modules read. Whatever
+//! isn't a flag is left in [`Options::args`] for the demo. The options start out as the config
+//! file says, see [`crate::config`], and the flags override it.
+
+use std::path::{Path, PathBuf};
+
+use vulkano::image::SampleCount;
+use vulkano::swapchain::PresentMode;
+use winit::dpi::PhysicalSize;
+use winit::window::{Fullscreen, WindowBuilder};
+
+use crate::config::{self, RendererConfig, WindowMode};
+use crate::debug;
+use crate::demos::Demo;
+use crate::gpu::{self, GpuSelector};
+use crate::present::{self, PresentSettings};
+use crate::setup;
+
+pub const USAGE: &str = "\
+usage: vulkan-rust <demo> [options] [demo arguments]
+       vulkan-rust --list-gpus
+       <demo> [options] [demo arguments]
+
+demos:
+    triangle, rectangle, rotating-rectangle, image, projection, mvp, egui, deferred, particles,
+    image-compute
+
+options:
+    --config <path>                a .toml or .ron config file, vulkan-rust.toml by default
+    --resolution <width>x<height>  the size of the window
+    --fullscreen                   borderless fullscreen on the current monitor
+    --gpu <index|name>             the GPU to use, see --list-gpus
+    --list-gpus                    print every GPU and exit
+    --present-mode <mode>          fifo, fifo-relaxed, mailbox or immediate
+    --msaa <samples>               1, 2, 4 or 8, for the demos that support it
+    --output <path>                save the last frame as a PNG, implies --headless
+    --frames <count>               exit after drawing this many frames
+    --stats-csv <path>             where egui exports frame stats, frame-stats.csv by default
+    --headless                     draw offscreen without a window, one frame by default
+    --validation                   enable the validation layer
+    --help                         print this
+
+demo arguments:
+    mvp [scene]                    a .toml or .ron scene file, reloaded when it changes
+    egui [scene]                   a .toml or .ron scene file to edit and save back to
+    deferred [forward|deferred]    the renderer to use
+    image-compute [input] [output] [kernels]
+";
+
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// The inner size of the window, in physical pixels.
+    pub resolution: Option<[u32; 2]>,
+    pub fullscreen: bool,
+    /// The GPU asked for with `--gpu`, or [`gpu::GPU_ENV`] when the flag isn't given, or the
+    /// config file.
+    pub gpu: Option<GpuSelector>,
+    pub list_gpus: bool,
+    pub present: PresentSettings,
+    /// Samples per pixel, no multisampling when `None`.
+    pub msaa: Option<u32>,
+    pub output: Option<PathBuf>,
+    /// How many frames to draw before exiting, no limit when `None`.
+    pub frames: Option<u64>,
+    /// Where the profiler exports frame stats, [`profiler::DEFAULT_EXPORT_PATH`] when `None`.
+    ///
+    /// [`profiler::DEFAULT_EXPORT_PATH`]: crate::profiler::DEFAULT_EXPORT_PATH
+    pub stats_csv: Option<PathBuf>,
+    /// Whether to draw offscreen instead of to a window.
+    pub headless: bool,
+    pub validation: bool,
+    pub help: bool,
+    /// The config file as it was read, without the flags applied.
+    pub config: RendererConfig,
+    /// Where the config file was read from, and where changes to it are saved.
+    pub config_path: Option<PathBuf>,
+    /// What wasn't a flag, in order.
+    pub args: Vec<String>,
+}
+
+impl Options {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let args = args.into_iter().collect::<Vec<_>>();
+        let config_path = config::find(config_flag(&args));
+        let config = match &config_path {
+            Some(path) => RendererConfig::load(path).map_err(|e| e.to_string())?,
+            None => RendererConfig::default(),
+        };
+        Self::parse_with_config(args, config, config_path)
+    }
+
+    /// Parses `args` over the options of `config`, read from `config_path`.
+    fn parse_with_config(
+        args: Vec<String>,
+        config: RendererConfig,
+        config_path: Option<PathBuf>,
+    ) -> Result<Self, String> {
+        let mut options = Self::from_config(config, config_path);
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => {
+                    (flag.to_owned(), Some(value.to_owned()))
+                }
+                _ => (arg.clone(), None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| format!("{} needs a value", flag))
+            };
+            // Switches are turned on by being given, `--fullscreen=false` is a mistake.
+            let switch = || match &inline_value {
+                Some(_) => Err(format!("{} doesn't take a value", flag)),
+                None => Ok(true),
+            };
+
+            match flag.as_str() {
+                // Already read by `config_flag`.
+                "--config" => {
+                    value()?;
+                }
+                "--resolution" => options.resolution = Some(parse_resolution(&value()?)?),
+                "--fullscreen" => options.fullscreen = switch()?,
+                "--gpu" => options.gpu = Some(GpuSelector::parse(&value()?)),
+                "--list-gpus" => options.list_gpus = switch()?,
+                "--present-mode" => options.present.present_mode = parse_present_mode(&value()?)?,
+                "--msaa" => options.msaa = parse_msaa(&value()?)?,
+                "--output" => options.output = Some(PathBuf::from(value()?)),
+                "--frames" => {
+                    let value = value()?;
+                    let frames = value
+                        .parse()
+                        .ok()
+                        .filter(|&frames| frames > 0)
+                        .ok_or_else(|| format!("--frames {:?} isn't a frame count", value))?;
+                    options.frames = Some(frames);
+                }
+                "--stats-csv" => options.stats_csv = Some(PathBuf::from(value()?)),
+                "--headless" => options.headless = switch()?,
+                "--validation" => options.validation = switch()?,
+                "--help" | "-h" => options.help = switch()?,
+                _ if flag.starts_with("--") => return Err(format!("unknown option {}", flag)),
+                _ => options.args.push(arg),
+            }
+        }
+
+        // The frame saved with `--output` is drawn offscreen, a window would only flash by.
+        if options.output.is_some() {
+            options.headless = true;
+        }
+        // A headless run has no window to close.
+        if options.headless && options.frames.is_none() {
+            options.frames = Some(1);
+        }
+
+        Ok(options)
+    }
+
+    /// The options the config file gives, before any flag.
+    fn from_config(config: RendererConfig, config_path: Option<PathBuf>) -> Self {
+        Self {
+            resolution: config.window.size,
+            fullscreen: config.window.mode == WindowMode::Fullscreen,
+            gpu: GpuSelector::from_env().or_else(|| config.gpu.as_deref().map(GpuSelector::parse)),
+            present: config.present_settings(),
+            msaa: (config.msaa > 1).then_some(config.msaa),
+            validation: config.validation,
+            config,
+            config_path,
+            ..Self::default()
+        }
+    }
+
+    /// The window to create, with the title, size and mode asked for.
+    pub fn window_builder(&self) -> WindowBuilder {
+        let mut builder = WindowBuilder::new()
+            .with_title(&self.config.window.title)
+            .with_maximized(self.config.window.mode == WindowMode::Maximized);
+        if let Some([width, height]) = self.resolution {
+            builder = builder.with_inner_size(PhysicalSize::new(width, height));
+        }
+        if self.fullscreen {
+            builder = builder.with_fullscreen(Some(Fullscreen::Borderless(None)));
+        }
+        builder
+    }
+
+    pub fn sample_count(&self) -> SampleCount {
+        match self.msaa {
+            Some(2) => SampleCount::Sample2,
+            Some(4) => SampleCount::Sample4,
+            Some(8) => SampleCount::Sample8,
+            _ => SampleCount::Sample1,
+        }
+    }
+
+    /// Whether `frame`, counting from 0, is the last one to draw.
+    pub fn is_last_frame(&self, frame: u64) -> bool {
+        self.frames.map_or(false, |frames| frame + 1 >= frames)
+    }
+
+    /// Where to save `frame`, when it's the last one and `--output` was given.
+    pub fn output_for(&self, frame: u64) -> Option<&Path> {
+        self.output.as_deref().filter(|_| self.is_last_frame(frame))
+    }
+}
+
+/// The path given with `--config`, which has to be known before the other flags are parsed.
+fn config_flag(args: &[String]) -> Option<PathBuf> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+    None
+}
+
+fn parse_resolution(value: &str) -> Result<[u32; 2], String> {
+    value
+        .split_once('x')
+        .and_then(|(width, height)| Some([width.parse().ok()?, height.parse().ok()?]))
+        .filter(|&[width, height]| width > 0 && height > 0)
+        .ok_or_else(|| format!("--resolution {:?} isn't <width>x<height>", value))
+}
+
+fn parse_present_mode(value: &str) -> Result<PresentMode, String> {
+    present::parse_present_mode(value).ok_or_else(|| {
+        format!(
+            "--present-mode {:?} isn't fifo, fifo-relaxed, mailbox or immediate",
+            value
+        )
+    })
+}
+
+fn parse_msaa(value: &str) -> Result<Option<u32>, String> {
+    match value {
+        "1" => Ok(None),
+        "2" | "4" | "8" => Ok(value.parse().ok()),
+        _ => Err(format!("--msaa {:?} isn't 1, 2, 4 or 8", value)),
+    }
+}
+
+fn parse_or_exit(args: impl IntoIterator<Item = String>) -> Options {
+    match Options::parse(args) {
+        Ok(options) if options.help => {
+            print!("{}", USAGE);
+            std::process::exit(0);
+        }
+        Ok(options) => {
+            if options.validation {
+                debug::request_validation();
+            }
+            options
+        }
+        Err(e) => {
+            eprint!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    }
+}
+
+/// The `main` of a demo's own binary.
+pub fn run_demo(demo: Demo) {
+    env_logger::init();
+    demo.run(parse_or_exit(std::env::args().skip(1)));
+}
+
+/// The `main` of the launcher, where the first argument names the demo.
+pub fn launch() {
+    env_logger::init();
+
+    let mut args = std::env::args().skip(1).peekable();
+    let Some(name) = args.peek().cloned() else {
+        eprint!("{}", USAGE);
+        std::process::exit(2);
+    };
+    if name.starts_with('-') {
+        let options = parse_or_exit(args);
+        if !options.list_gpus {
+            eprint!("a demo has to be named first\n\n{}", USAGE);
+            std::process::exit(2);
+        }
+        let listed = setup::create_instance().and_then(|instance| gpu::list(&instance));
+        if let Err(e) = listed {
+            log::error!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    args.next();
+
+    let Some(demo) = Demo::from_name(&name) else {
+        eprint!("unknown demo {:?}\n\n{}", name, USAGE);
+        std::process::exit(2);
+    };
+    demo.run(parse_or_exit(args));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn present_modes_parse_by_name() {
+        assert_eq!(parse_present_mode("fifo"), Ok(PresentMode::Fifo));
+        assert_eq!(
+            parse_present_mode("fifo-relaxed"),
+            Ok(PresentMode::FifoRelaxed)
+        );
+        assert_eq!(parse_present_mode("mailbox"), Ok(PresentMode::Mailbox));
+        assert_eq!(parse_present_mode("immediate"), Ok(PresentMode::Immediate));
+    }
+
+    #[test]
+    fn present_modes_round_trip_through_their_names() {
+        for mode in present::PRESENT_MODES {
+            assert_eq!(
+                parse_present_mode(present::present_mode_name(mode)),
+                Ok(mode)
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_present_modes_are_rejected() {
+        // Names are matched exactly, not like the debug output or case insensitively.
+        for name in ["", "vsync", "Fifo", "FIFO", "fifo_relaxed", "FifoRelaxed"] {
+            let error = parse_present_mode(name).unwrap_err();
+            assert!(error.starts_with("--present-mode"), "{}", error);
+        }
+    }
+
+    /// Parses `args` over `config`, without looking for a config file.
+    fn parse(args: &[&str], config: RendererConfig) -> Result<Options, String> {
+        let args = args.iter().map(|&arg| arg.to_owned()).collect();
+        Options::parse_with_config(args, config, None)
+    }
+
+    #[test]
+    fn flags_are_parsed() {
+        let options = parse(
+            &[
+                "--resolution",
+                "800x600",
+                "--fullscreen",
+                "--gpu",
+                "1",
+                "--present-mode",
+                "mailbox",
+                "--msaa",
+                "4",
+                "--frames",
+                "5",
+                "--stats-csv",
+                "stats.csv",
+                "--validation",
+                "scene.toml",
+            ],
+            RendererConfig::default(),
+        )
+        .unwrap();
+
+        assert_eq!(options.resolution, Some([800, 600]));
+        assert!(options.fullscreen);
+        assert_eq!(options.gpu, Some(GpuSelector::Index(1)));
+        assert_eq!(options.present.present_mode, PresentMode::Mailbox);
+        assert_eq!(options.msaa, Some(4));
+        assert_eq!(options.frames, Some(5));
+        assert_eq!(options.stats_csv, Some(PathBuf::from("stats.csv")));
+        assert!(options.validation);
+        assert!(!options.headless);
+        assert_eq!(options.args, ["scene.toml"]);
+    }
+
+    #[test]
+    fn values_can_follow_an_equals_sign() {
+        let options = parse(
+            &["--resolution=800x600", "--frames=5", "--output=frame.png"],
+            RendererConfig::default(),
+        )
+        .unwrap();
+
+        assert_eq!(options.resolution, Some([800, 600]));
+        assert_eq!(options.frames, Some(5));
+        assert_eq!(options.output, Some(PathBuf::from("frame.png")));
+    }
+
+    #[test]
+    fn flags_override_the_config() {
+        let mut config = RendererConfig {
+            present_mode: PresentMode::Immediate,
+            msaa: 8,
+            ..RendererConfig::default()
+        };
+        config.window.size = Some([1024, 768]);
+
+        let options = parse(&["--msaa", "2"], config.clone()).unwrap();
+        assert_eq!(options.msaa, Some(2));
+        // What no flag was given for keeps the config's value.
+        assert_eq!(options.resolution, Some([1024, 768]));
+        assert_eq!(options.present.present_mode, PresentMode::Immediate);
+        // The config itself is kept as it was read, for saving it back.
+        assert_eq!(options.config, config);
+
+        let options = parse(&["--resolution", "640x480", "--msaa", "1"], config).unwrap();
+        assert_eq!(options.resolution, Some([640, 480]));
+        assert_eq!(options.msaa, None);
+    }
+
+    #[test]
+    fn output_implies_one_headless_frame() {
+        let options = parse(&["--output", "frame.png"], RendererConfig::default()).unwrap();
+        assert!(options.headless);
+        assert_eq!(options.frames, Some(1));
+        assert_eq!(options.output_for(0), Some(Path::new("frame.png")));
+
+        // The frame count given is kept, and the last frame is the one saved.
+        let options = parse(
+            &["--output", "frame.png", "--frames", "3"],
+            RendererConfig::default(),
+        )
+        .unwrap();
+        assert!(options.headless);
+        assert_eq!(options.frames, Some(3));
+        assert_eq!(options.output_for(1), None);
+        assert_eq!(options.output_for(2), Some(Path::new("frame.png")));
+    }
+
+    #[test]
+    fn resolutions_are_width_by_height() {
+        assert_eq!(parse_resolution("800x600"), Ok([800, 600]));
+        for value in [
+            "",
+            "800",
+            "800x",
+            "x600",
+            "0x600",
+            "800x0",
+            "800x600x2",
+            "-1x600",
+        ] {
+            let error = parse_resolution(value).unwrap_err();
+            assert!(error.starts_with("--resolution"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn msaa_is_a_supported_sample_count() {
+        assert_eq!(parse_msaa("1"), Ok(None));
+        assert_eq!(parse_msaa("2"), Ok(Some(2)));
+        assert_eq!(parse_msaa("4"), Ok(Some(4)));
+        assert_eq!(parse_msaa("8"), Ok(Some(8)));
+        for value in ["", "0", "3", "16", "four"] {
+            let error = parse_msaa(value).unwrap_err();
+            assert!(error.starts_with("--msaa"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn unknown_flags_are_rejected() {
+        for flag in ["--nope", "--nope=1", "--Headless"] {
+            let error = parse(&[flag], RendererConfig::default()).unwrap_err();
+            assert!(error.starts_with("unknown option --"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn switches_reject_values() {
+        for flag in ["--fullscreen=false", "--headless=1", "--validation=yes"] {
+            let error = parse(&[flag], RendererConfig::default()).unwrap_err();
+            assert!(error.ends_with("doesn't take a value"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn missing_values_are_reported() {
+        let error = parse(&["--frames"], RendererConfig::default()).unwrap_err();
+        assert_eq!(error, "--frames needs a value");
+    }
+}
@@ -0,0 +1,488 @@
+//! Renderer settings read from a TOML or RON file, picked by its extension.
+//!
+//! The file is the one given with `--config <path>`, or [`CONFIG_ENV`], or [`DEFAULT_CONFIG`]
+//! in the working directory when it exists. Every key is optional, unknown keys are an error so
+//! a typo isn't silently ignored, and values are checked by [`RendererConfig::validate`].
+//! Command line flags override what the file says, without being written back to it.
+//!
+//! A TOML file looks like this:
+//!
+//! ```toml
+//! present_mode = "mailbox"
+//! msaa = 4
+//! gpu = "nvidia"
+//! clear_color = [0.1, 0.1, 0.1, 1.0]
+//!
+//! [window]
+//! title = "triangle"
+//! size = [1280, 720]
+//! mode = "maximized"
+//!
+//! [assets]
+//! shader_dir = "shaders"
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use vulkano::swapchain::PresentMode;
+
+use crate::present::{PresentSettings, FPS_LIMIT_RANGE};
+
+pub const CONFIG_ENV: &str = "VULKAN_RUST_CONFIG";
+
+/// The file read when no other one is given.
+pub const DEFAULT_CONFIG: &str = "vulkan-rust.toml";
+
+// The TOML serializer can't write a plain value after a table, so `window` and `assets` have
+// to stay the last fields.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RendererConfig {
+    #[serde(with = "present_mode")]
+    pub present_mode: PresentMode,
+    /// The surface's minimum when `None`.
+    pub image_count: Option<u32>,
+    /// Frames per second, unlimited when `None`.
+    pub fps_limit: Option<f32>,
+    /// Samples per pixel, 1 for no multisampling. Only the demos that support `--msaa` use it,
+    /// the others warn that it's ignored.
+    pub msaa: u32,
+    /// A device index or a part of its name, like `--gpu`.
+    pub gpu: Option<String>,
+    pub validation: bool,
+    /// Linear RGBA, each between 0 and 1.
+    pub clear_color: [f32; 4],
+    /// Whether the egui demo renders the ID of each entity, to pick them with the mouse. Off by
+    /// default, since it costs an extra attachment and a read back every frame. The other demos
+    /// have no editor to select entities in, so they never render IDs. The ID attachment is only
+    /// created at startup, so turning this on takes a restart, and turning it off stops the
+    /// reads.
+    pub picking: bool,
+    pub window: WindowConfig,
+    pub assets: AssetPaths,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowConfig {
+    pub title: String,
+    /// The inner size, in physical pixels. Left to the window system when `None`.
+    pub size: Option<[u32; 2]>,
+    pub mode: WindowMode,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowMode {
+    #[default]
+    Windowed,
+    Maximized,
+    /// Borderless, on the current monitor.
+    Fullscreen,
+}
+
+/// Where assets are loaded from instead of the ones built into the demos.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AssetPaths {
+    /// GLSL files to load and hot reload, like [`crate::shader::SHADER_DIR_ENV`], which wins
+    /// when both are set. Only the egui demo reloads its shaders, the others are built with
+    /// theirs and ignore this.
+    pub shader_dir: Option<PathBuf>,
+    /// An RGBA PNG drawn by the image demo, and by the egui demo on materials without a
+    /// texture of their own.
+    pub texture: Option<PathBuf>,
+}
+
+impl Default for RendererConfig {
+    fn default() -> Self {
+        let present = PresentSettings::default();
+        Self {
+            present_mode: present.present_mode,
+            image_count: present.image_count,
+            fps_limit: present.fps_limit,
+            msaa: 1,
+            gpu: None,
+            validation: false,
+            clear_color: [0.0, 0.0, 0.0, 1.0],
+            picking: false,
+            window: WindowConfig::default(),
+            assets: AssetPaths::default(),
+        }
+    }
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            title: "vulkan-rust".to_owned(),
+            size: None,
+            mode: WindowMode::Windowed,
+        }
+    }
+}
+
+/// The formats config and scene files can be written in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Ron,
+}
+
+impl Format {
+    /// The format of `path`, by its extension.
+    pub fn of(path: &Path) -> Result<Self, ConfigError> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Ok(Self::Toml),
+            Some("ron") => Ok(Self::Ron),
+            _ => Err(ConfigError::new(path, "has to end in .toml or .ron")),
+        }
+    }
+
+    pub fn parse<T: DeserializeOwned>(self, text: &str) -> Result<T, String> {
+        match self {
+            Self::Toml => toml::from_str(text).map_err(|e| e.to_string()),
+            Self::Ron => ron::from_str(text).map_err(|e| e.to_string()),
+        }
+    }
+
+    pub fn write<T: Serialize>(self, value: &T) -> Result<String, String> {
+        match self {
+            Self::Toml => toml::to_string_pretty(value).map_err(|e| e.to_string()),
+            Self::Ron => {
+                ron::ser::to_string_pretty(value, Default::default()).map_err(|e| e.to_string())
+            }
+        }
+    }
+}
+
+/// Reads a file in the format its extension names.
+pub fn read<T: DeserializeOwned>(path: &Path) -> Result<T, ConfigError> {
+    let format = Format::of(path)?;
+    let text = fs::read_to_string(path).map_err(|e| ConfigError::new(path, e))?;
+    format.parse(&text).map_err(|e| ConfigError::new(path, e))
+}
+
+/// Writes a file in the format its extension names.
+pub fn write<T: Serialize>(path: &Path, value: &T) -> Result<(), ConfigError> {
+    let text = Format::of(path)?
+        .write(value)
+        .map_err(|e| ConfigError::new(path, e))?;
+    fs::write(path, text).map_err(|e| ConfigError::new(path, e))
+}
+
+/// What went wrong with a config or scene file, and which file it was.
+#[derive(Debug)]
+pub struct ConfigError {
+    path: PathBuf,
+    message: String,
+}
+
+impl ConfigError {
+    pub fn new(path: &Path, message: impl fmt::Display) -> Self {
+        Self {
+            path: path.to_owned(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.message)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// The config file to read: `explicit` when given, then [`CONFIG_ENV`], then
+/// [`DEFAULT_CONFIG`] if it exists.
+pub fn find(explicit: Option<PathBuf>) -> Option<PathBuf> {
+    explicit
+        .or_else(|| std::env::var_os(CONFIG_ENV).map(PathBuf::from))
+        .or_else(|| Some(PathBuf::from(DEFAULT_CONFIG)).filter(|path| path.exists()))
+}
+
+impl RendererConfig {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let config: Self = read(path)?;
+        config.validate().map_err(|e| ConfigError::new(path, e))?;
+
+        log::info!("loaded config from {}", path.display());
+        Ok(config)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        write(path, self)?;
+
+        log::info!("saved config to {}", path.display());
+        Ok(())
+    }
+
+    /// Checks the values the types don't constrain.
+    pub fn validate(&self) -> Result<(), String> {
+        if !matches!(self.msaa, 1 | 2 | 4 | 8) {
+            return Err(format!("msaa = {} isn't 1, 2, 4 or 8", self.msaa));
+        }
+        if self.image_count == Some(0) {
+            return Err("image_count has to be at least 1".to_owned());
+        }
+        if let Some(fps_limit) = self.fps_limit {
+            if !FPS_LIMIT_RANGE.contains(&fps_limit) {
+                return Err(format!(
+                    "fps_limit = {} isn't between {} and {}",
+                    fps_limit,
+                    FPS_LIMIT_RANGE.start(),
+                    FPS_LIMIT_RANGE.end()
+                ));
+            }
+        }
+        if self.gpu.as_deref() == Some("") {
+            return Err("gpu can't be empty, leave it out to pick the best one".to_owned());
+        }
+        if let Some(channel) = self
+            .clear_color
+            .iter()
+            .find(|channel| !(0.0..=1.0).contains(*channel))
+        {
+            return Err(format!("clear_color has {}, outside 0 to 1", channel));
+        }
+        if let Some([width, height]) = self.window.size {
+            if width == 0 || height == 0 {
+                return Err(format!("window.size = [{}, {}] is empty", width, height));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn present_settings(&self) -> PresentSettings {
+        PresentSettings {
+            present_mode: self.present_mode,
+            image_count: self.image_count,
+            fps_limit: self.fps_limit,
+        }
+    }
+
+    /// Takes the present settings that changed from `before` to `after`. The others keep what
+    /// the file says, so a value only given as a flag isn't saved along with them.
+    pub fn update_present_settings(&mut self, before: &PresentSettings, after: &PresentSettings) {
+        if after.present_mode != before.present_mode {
+            self.present_mode = after.present_mode;
+        }
+        if after.image_count != before.image_count {
+            self.image_count = after.image_count;
+        }
+        if after.fps_limit != before.fps_limit {
+            self.fps_limit = after.fps_limit;
+        }
+    }
+
+    /// Shows the settings [`PresentSettings::ui`] doesn't in a window. The ones listed under
+    /// "on the next start" only take effect when a demo is started again.
+    pub fn ui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Settings")
+            .default_width(300.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.color_edit_button_rgba_unmultiplied(&mut self.clear_color);
+                    ui.label("clear color");
+                });
+
+                ui.separator();
+                ui.label("on the next start:");
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.window.title);
+                    ui.label("title");
+                });
+                egui::ComboBox::from_label("window")
+                    .selected_text(format!("{:?}", self.window.mode))
+                    .show_ui(ui, |ui| {
+                        for mode in [
+                            WindowMode::Windowed,
+                            WindowMode::Maximized,
+                            WindowMode::Fullscreen,
+                        ] {
+                            ui.selectable_value(&mut self.window.mode, mode, format!("{:?}", mode));
+                        }
+                    });
+                egui::ComboBox::from_label("msaa")
+                    .selected_text(self.msaa.to_string())
+                    .show_ui(ui, |ui| {
+                        for samples in [1, 2, 4, 8] {
+                            ui.selectable_value(&mut self.msaa, samples, samples.to_string());
+                        }
+                    });
+                ui.checkbox(&mut self.validation, "validation");
+                ui.checkbox(&mut self.picking, "picking");
+            });
+    }
+}
+
+/// Present modes by their [`crate::present::present_mode_name`].
+mod present_mode {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use vulkano::swapchain::PresentMode;
+
+    use crate::present;
+
+    pub fn serialize<S: Serializer>(mode: &PresentMode, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(present::present_mode_name(*mode))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<PresentMode, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        present::parse_present_mode(&name).ok_or_else(|| {
+            D::Error::custom(format!(
+                "present_mode {:?} isn't fifo, fifo-relaxed, mailbox or immediate",
+                name
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::present::PRESENT_MODES;
+
+    fn invalid(change: impl FnOnce(&mut RendererConfig)) -> String {
+        let mut config = RendererConfig::default();
+        change(&mut config);
+        config.validate().unwrap_err()
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        assert_eq!(RendererConfig::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn values_in_range_are_valid() {
+        for msaa in [1, 2, 4, 8] {
+            let config = RendererConfig {
+                msaa,
+                image_count: Some(1),
+                fps_limit: Some(*FPS_LIMIT_RANGE.end()),
+                gpu: Some("0".to_owned()),
+                clear_color: [0.0, 0.5, 1.0, 1.0],
+                ..RendererConfig::default()
+            };
+            assert_eq!(config.validate(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        assert!(invalid(|c| c.msaa = 3).contains("msaa"));
+        assert!(invalid(|c| c.msaa = 0).contains("msaa"));
+        assert!(invalid(|c| c.image_count = Some(0)).contains("image_count"));
+        assert!(invalid(|c| c.fps_limit = Some(FPS_LIMIT_RANGE.start() - 1.0)).contains("fps"));
+        assert!(invalid(|c| c.fps_limit = Some(FPS_LIMIT_RANGE.end() + 1.0)).contains("fps"));
+        assert!(invalid(|c| c.fps_limit = Some(f32::NAN)).contains("fps"));
+        assert!(invalid(|c| c.gpu = Some(String::new())).contains("gpu"));
+        assert!(invalid(|c| c.clear_color[1] = 1.5).contains("clear_color"));
+        assert!(invalid(|c| c.clear_color[3] = -0.1).contains("clear_color"));
+        assert!(invalid(|c| c.window.size = Some([0, 720])).contains("window.size"));
+        assert!(invalid(|c| c.window.size = Some([1280, 0])).contains("window.size"));
+    }
+
+    #[test]
+    fn present_modes_round_trip() {
+        for format in [Format::Toml, Format::Ron] {
+            for present_mode in PRESENT_MODES {
+                let config = RendererConfig {
+                    present_mode,
+                    ..RendererConfig::default()
+                };
+                let text = format.write(&config).unwrap();
+                let name = crate::present::present_mode_name(present_mode);
+                assert!(text.contains(&format!("\"{}\"", name)), "{}", text);
+                assert_eq!(format.parse::<RendererConfig>(&text), Ok(config));
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_present_modes_are_rejected() {
+        let error = Format::Toml
+            .parse::<RendererConfig>("present_mode = \"vsync\"")
+            .unwrap_err();
+        assert!(error.contains("vsync"), "{}", error);
+        assert!(Format::Ron
+            .parse::<RendererConfig>("(present_mode: \"Mailbox\")")
+            .is_err());
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        let cases = [
+            (
+                Format::Toml,
+                "presnt_mode = \"fifo\"",
+                "presnt_mode",
+                "present_mode",
+            ),
+            (Format::Toml, "[window]\ntitel = \"demo\"", "titel", "title"),
+            (
+                Format::Toml,
+                "[assets]\ntextures = \"a.png\"",
+                "textures",
+                "texture",
+            ),
+            (
+                Format::Ron,
+                "(presnt_mode: \"fifo\")",
+                "presnt_mode",
+                "present_mode",
+            ),
+            (Format::Ron, "(window: (titel: \"demo\"))", "titel", "title"),
+            (
+                Format::Ron,
+                "(assets: (textures: \"a.png\"))",
+                "textures",
+                "texture",
+            ),
+        ];
+        for (format, text, typo, expected) in cases {
+            let error = format.parse::<RendererConfig>(text).unwrap_err();
+            // Both the typo and what was meant, so the user can fix it.
+            assert!(error.contains(typo), "{:?}: {}", format, error);
+            assert!(
+                error.contains(&format!("`{}`", expected)),
+                "{:?}: {}",
+                format,
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn only_changed_present_settings_are_taken() {
+        let mut config = RendererConfig {
+            image_count: Some(3),
+            ..RendererConfig::default()
+        };
+        // `--present-mode mailbox` overrode the file, then the frame rate was limited in the UI.
+        let before = PresentSettings {
+            present_mode: PresentMode::Mailbox,
+            ..config.present_settings()
+        };
+        let after = PresentSettings {
+            fps_limit: Some(60.0),
+            ..before
+        };
+        config.update_present_settings(&before, &after);
+
+        assert_eq!(config.present_mode, PresentMode::Fifo);
+        assert_eq!(config.image_count, Some(3));
+        assert_eq!(config.fps_limit, Some(60.0));
+    }
+}
@@ -0,0 +1,264 @@
+//! Opt-in Vulkan validation, and debug names and labels for graphics debuggers.
+//!
+//! Setting [`VALIDATION_ENV`], or calling [`request_validation`] for `--validation` and the
+//! config file, enables `VK_LAYER_KHRONOS_validation` and `ext_debug_utils`, and routes the
+//! messages through `log`: validation errors are logged as errors, warnings as warnings and so
+//! on, so `RUST_LOG` decides what is shown. Severities `log` would discard are not requested
+//! from the layer at all.
+//!
+//! Setting [`VALIDATION_FRAMES_ENV`] as well turns a demo into a check: it exits after that many
+//! frames, with a failure status if the layer reported any error. Headless runs, see
+//! [`crate::runner::run_headless`], always fail when it did.
+//!
+//! `ext_debug_utils` is enabled whenever it's available, since the object names and command
+//! buffer labels set by the demos need it too. Without it they are skipped.
+
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+
+use log::{Level, LevelFilter};
+use vulkano::buffer::BufferAccess;
+use vulkano::command_buffer::allocator::CommandBufferAllocator;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::device::{Device, DeviceOwned};
+use vulkano::image::ImageAccess;
+use vulkano::instance::debug::{
+    DebugUtilsLabel, DebugUtilsMessageSeverity, DebugUtilsMessageType, DebugUtilsMessenger,
+    DebugUtilsMessengerCreateInfo, Message,
+};
+use vulkano::instance::{Instance, InstanceCreateInfo};
+use vulkano::{VulkanLibrary, VulkanObject};
+
+use crate::error::{Error, Result};
+
+pub const VALIDATION_ENV: &str = "VULKAN_RUST_VALIDATION";
+pub const VALIDATION_FRAMES_ENV: &str = "VULKAN_RUST_VALIDATION_FRAMES";
+
+const VALIDATION_LAYER: &str = "VK_LAYER_KHRONOS_validation";
+
+static VALIDATION_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Asks for validation in every instance created from now on, like [`VALIDATION_ENV`].
+pub fn request_validation() {
+    VALIDATION_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+/// Whether validation was asked for. A strict run implies it.
+pub fn validation_requested() -> bool {
+    VALIDATION_REQUESTED.load(Ordering::Relaxed)
+        || std::env::var_os(VALIDATION_ENV).is_some()
+        || strict_frames().is_some()
+}
+
+fn strict_frames() -> Option<u32> {
+    std::env::var(VALIDATION_FRAMES_ENV).ok()?.parse().ok()
+}
+
+/// Adds `ext_debug_utils` to `create_info` when the library provides it, and the validation
+/// layer when validation was asked for.
+pub fn instance_create_info(
+    library: &VulkanLibrary,
+    mut create_info: InstanceCreateInfo,
+) -> InstanceCreateInfo {
+    let has_debug_utils = library.supported_extensions().ext_debug_utils;
+    create_info.enabled_extensions.ext_debug_utils = has_debug_utils;
+
+    if !validation_requested() {
+        return create_info;
+    }
+
+    let has_layer = library
+        .layer_properties()
+        .map(|mut layers| layers.any(|l| l.name() == VALIDATION_LAYER))
+        .unwrap_or(false);
+    if has_layer {
+        create_info.enabled_layers.push(VALIDATION_LAYER.to_owned());
+    } else {
+        log::warn!("{} is not installed", VALIDATION_LAYER);
+    }
+
+    if !has_debug_utils {
+        log::warn!("ext_debug_utils is not supported, validation messages won't be logged");
+    }
+
+    create_info
+}
+
+/// Receives the validation messages for as long as it's alive, and counts the errors.
+pub struct Validation {
+    messenger: Option<DebugUtilsMessenger>,
+    errors: Arc<AtomicU32>,
+    frames_left: Option<u32>,
+}
+
+impl Validation {
+    /// Installs the messenger when validation was asked for and the instance was created with
+    /// `ext_debug_utils`.
+    pub fn new(instance: &Arc<Instance>) -> Self {
+        let errors = Arc::new(AtomicU32::new(0));
+
+        let messenger = (validation_requested() && instance.enabled_extensions().ext_debug_utils)
+            .then(|| {
+                let errors = errors.clone();
+                let callback = Arc::new(move |message: &Message| {
+                    let level = level(message.severity);
+                    if level == Level::Error {
+                        errors.fetch_add(1, Ordering::Relaxed);
+                    }
+                    log::log!(
+                        target: "vulkan",
+                        level,
+                        "{}: {}",
+                        message.layer_prefix.unwrap_or("vulkan"),
+                        message.description
+                    );
+                });
+
+                // Safety: the callback doesn't call into Vulkan.
+                unsafe {
+                    DebugUtilsMessenger::new(
+                        instance.clone(),
+                        DebugUtilsMessengerCreateInfo {
+                            message_severity: severity_filter(log::max_level()),
+                            message_type: DebugUtilsMessageType {
+                                general: true,
+                                validation: true,
+                                performance: true,
+                                ..DebugUtilsMessageType::empty()
+                            },
+                            ..DebugUtilsMessengerCreateInfo::user_callback(callback)
+                        },
+                    )
+                }
+                .map_err(|e| log::warn!("can't create debug messenger: {}", e))
+                .ok()
+            })
+            .flatten();
+
+        Self {
+            messenger,
+            errors,
+            frames_left: strict_frames(),
+        }
+    }
+
+    pub fn error_count(&self) -> u32 {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    /// Counts a frame. Returns true once a strict run has rendered all its frames, at which
+    /// point the demo should stop and call [`Self::finish`].
+    pub fn frame(&mut self) -> bool {
+        match &mut self.frames_left {
+            Some(0) => true,
+            Some(frames_left) => {
+                *frames_left -= 1;
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Fails if the layer reported any error so far.
+    pub fn check(&self) -> Result<()> {
+        match self.error_count() {
+            0 => Ok(()),
+            errors => Err(Error::Validation(errors)),
+        }
+    }
+
+    /// Ends a strict run, exiting with a failure status if any validation error was reported.
+    /// Does nothing otherwise.
+    pub fn finish(&self) {
+        if self.frames_left.is_none() {
+            return;
+        }
+
+        if let Err(e) = self.check() {
+            log::error!("{}", e);
+            std::process::exit(1);
+        }
+        log::info!("validation reported no errors");
+    }
+}
+
+fn level(severity: DebugUtilsMessageSeverity) -> Level {
+    if severity.error {
+        Level::Error
+    } else if severity.warning {
+        Level::Warn
+    } else if severity.information {
+        Level::Info
+    } else {
+        Level::Trace
+    }
+}
+
+/// The severities `log` would let through at `max_level`. Errors are always requested, since a
+/// strict run counts them.
+fn severity_filter(max_level: LevelFilter) -> DebugUtilsMessageSeverity {
+    DebugUtilsMessageSeverity {
+        error: true,
+        warning: max_level >= LevelFilter::Warn,
+        information: max_level >= LevelFilter::Info,
+        verbose: max_level >= LevelFilter::Trace,
+        ..DebugUtilsMessageSeverity::empty()
+    }
+}
+
+fn debug_utils_enabled(device: &Device) -> bool {
+    device.instance().enabled_extensions().ext_debug_utils
+}
+
+/// Names `object` in validation messages and graphics debugger captures. Does nothing when
+/// `ext_debug_utils` isn't enabled.
+pub fn set_name<T: VulkanObject + DeviceOwned>(object: &T, name: &str) {
+    let device = object.device();
+    if !debug_utils_enabled(device) {
+        return;
+    }
+
+    if let Err(e) = device.set_debug_utils_object_name(object, Some(name)) {
+        log::warn!("can't name {}: {}", name, e);
+    }
+}
+
+/// Names the Vulkan buffer behind `buffer`.
+pub fn set_buffer_name(buffer: &dyn BufferAccess, name: &str) {
+    set_name(buffer.inner().buffer.as_ref(), name);
+}
+
+/// Names the Vulkan image behind `image`.
+pub fn set_image_name(image: &dyn ImageAccess, name: &str) {
+    set_name(image.inner().image.as_ref(), name);
+}
+
+/// Wraps the commands recorded by `record` in a labelled region, which graphics debuggers show
+/// as a group. Only the label is skipped when `ext_debug_utils` isn't enabled.
+pub fn label<L, A, R>(
+    builder: &mut AutoCommandBufferBuilder<L, A>,
+    name: &str,
+    record: impl FnOnce(&mut AutoCommandBufferBuilder<L, A>) -> R,
+) -> R
+where
+    A: CommandBufferAllocator,
+{
+    let enabled = debug_utils_enabled(builder.device());
+    if enabled {
+        builder
+            .begin_debug_utils_label(DebugUtilsLabel {
+                label_name: name.to_owned(),
+                color: [0.0; 4],
+            })
+            .expect("can't begin debug label");
+    }
+
+    let result = record(builder);
+
+    if enabled {
+        // Safety: the region was begun above, in the same command buffer.
+        unsafe { builder.end_debug_utils_label() }.expect("can't end debug label");
+    }
+
+    result
+}
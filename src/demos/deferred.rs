@@ -0,0 +1,877 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use bytemuck::{Pod, Zeroable};
+use cgmath::{Matrix4, Point3, Rad, SquareMatrix, Vector3};
+use rand::{Rng, SeedableRng};
+use vulkano::buffer::{BufferUsage, DeviceLocalBuffer, TypedBufferAccess};
+use vulkano::command_buffer::{RenderPassBeginInfo, SubpassContents};
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::format::{ClearValue, Format};
+use vulkano::image::view::ImageView;
+use vulkano::image::{AttachmentImage, ImageViewAbstract};
+use vulkano::impl_vertex;
+use vulkano::memory::allocator::StandardMemoryAllocator;
+use vulkano::pipeline::graphics::color_blend::ColorBlendState;
+use vulkano::pipeline::graphics::depth_stencil::DepthStencilState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::vertex_input::BuffersDefinition;
+use vulkano::pipeline::graphics::viewport::ViewportState;
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use winit::event_loop::EventLoopWindowTarget;
+
+use crate::cli::Options;
+use crate::debug;
+use crate::error::{Context, Error, Result};
+use crate::runner::{self, App, Frame, Gpu, Target};
+use crate::setup;
+
+// Must match the size of the `lights` array in the shaders. 512 lights of 32 bytes each is
+// exactly the 16KiB every implementation guarantees for a uniform buffer.
+const MAX_LIGHTS: usize = 512;
+const LIGHT_COUNT: usize = 400;
+
+const GBUFFER_ALBEDO_FORMAT: Format = Format::R8G8B8A8_UNORM;
+const GBUFFER_NORMAL_FORMAT: Format = Format::R16G16B16A16_SFLOAT;
+const GBUFFER_MATERIAL_FORMAT: Format = Format::R8G8B8A8_UNORM;
+const DEPTH_FORMAT: Format = Format::D32_SFLOAT;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RendererKind {
+    Forward,
+    Deferred,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
+struct Vertex {
+    position: [f32; 3],
+    normal: [f32; 3],
+    color: [f32; 3],
+    material: [f32; 2],
+}
+
+impl_vertex!(Vertex, position, normal, color, material);
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
+struct PointLight {
+    // xyz: position at time 0, w: radius
+    position: [f32; 4],
+    // rgb: color, w: angular speed of the orbit around the y axis
+    color: [f32; 4],
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
+			#version 450
+
+            layout(location = 0) in vec3 position;
+            layout(location = 1) in vec3 normal;
+            layout(location = 2) in vec3 color;
+            layout(location = 3) in vec2 material;
+
+            layout(location = 0) out vec3 v_position;
+            layout(location = 1) out vec3 v_normal;
+            layout(location = 2) out vec3 v_color;
+            layout(location = 3) out vec2 v_material;
+
+            // Shared with forward_fs, so both stages agree on the push constant range.
+            layout (push_constant) uniform PushConstants {
+                mat4 view_proj;
+                vec4 camera_position;
+                float time;
+                uint light_count;
+            } push;
+
+            void main() {
+                v_position = position;
+                v_normal = normal;
+                v_color = color;
+                v_material = material;
+                gl_Position = push.view_proj * vec4(position, 1.0);
+            }",
+        types_meta: {
+            use bytemuck::{Pod, Zeroable};
+
+            #[derive(Clone, Copy, Zeroable, Pod)]
+        },
+    }
+}
+
+// The lights are written as [`PointLight`]s and the push constants as `vs::ty::PushConstants`,
+// so the structs generated here go unused.
+#[allow(dead_code)]
+mod forward_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        include: ["shaders"],
+        src: "
+			#version 450
+
+            layout(location = 0) in vec3 v_position;
+            layout(location = 1) in vec3 v_normal;
+            layout(location = 2) in vec3 v_color;
+            layout(location = 3) in vec2 v_material;
+
+            layout(location = 0) out vec4 f_color;
+
+            struct PointLight {
+                vec4 position;
+                vec4 color;
+            };
+
+            layout(set = 0, binding = 0) uniform Lights {
+                PointLight lights[512];
+            } u_lights;
+
+            layout (push_constant) uniform PushConstants {
+                mat4 view_proj;
+                vec4 camera_position;
+                float time;
+                uint light_count;
+            } push;
+
+            vec3 light_position(PointLight light) {
+                float a = push.time * light.color.w;
+                vec3 p = light.position.xyz;
+                return vec3(cos(a) * p.x - sin(a) * p.z, p.y, sin(a) * p.x + cos(a) * p.z);
+            }
+
+            // The encoding of the target, see `OutputEncoding`.
+            layout(constant_id = 0) const uint output_encoding = 0;
+
+            #include <encoding.glsl>
+
+            void main() {
+                vec3 normal = normalize(v_normal);
+                vec3 view_dir = normalize(push.camera_position.xyz - v_position);
+                float shininess = 1.0 + v_material.y * 127.0;
+
+                vec3 color = 0.03 * v_color;
+                for (uint i = 0; i < push.light_count; i++) {
+                    PointLight light = u_lights.lights[i];
+                    vec3 to_light = light_position(light) - v_position;
+                    float distance = length(to_light);
+                    if (distance > light.position.w) {
+                        continue;
+                    }
+
+                    vec3 light_dir = to_light / distance;
+                    float attenuation = pow(1.0 - distance / light.position.w, 2.0);
+                    float diffuse = max(dot(normal, light_dir), 0.0);
+                    vec3 half_dir = normalize(light_dir + view_dir);
+                    float specular = pow(max(dot(normal, half_dir), 0.0), shininess) * v_material.x;
+
+                    color += (diffuse * v_color + specular) * light.color.rgb * attenuation;
+                }
+
+                f_color = encode_output(vec4(color, 1.0), output_encoding);
+            }",
+        types_meta: {
+            use bytemuck::{Pod, Zeroable};
+
+            #[derive(Clone, Copy, Zeroable, Pod)]
+        },
+    }
+}
+
+mod gbuffer_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: "
+			#version 450
+
+            layout(location = 1) in vec3 v_normal;
+            layout(location = 2) in vec3 v_color;
+            layout(location = 3) in vec2 v_material;
+
+            layout(location = 0) out vec4 f_albedo;
+            layout(location = 1) out vec4 f_normal;
+            layout(location = 2) out vec4 f_material;
+
+            void main() {
+                f_albedo = vec4(v_color, 1.0);
+                f_normal = vec4(normalize(v_normal), 1.0);
+                f_material = vec4(v_material, 0.0, 1.0);
+            }"
+    }
+}
+
+mod lighting_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
+			#version 450
+
+            // Fullscreen triangle, no vertex buffer needed.
+            void main() {
+                vec2 uv = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+                gl_Position = vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+            }"
+    }
+}
+
+// Only its push constants are used, the lights are [`PointLight`]s.
+#[allow(dead_code)]
+mod lighting_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        include: ["shaders"],
+        src: "
+			#version 450
+
+            layout(input_attachment_index = 0, set = 0, binding = 0) uniform subpassInput u_albedo;
+            layout(input_attachment_index = 1, set = 0, binding = 1) uniform subpassInput u_normal;
+            layout(input_attachment_index = 2, set = 0, binding = 2) uniform subpassInput u_material;
+            layout(input_attachment_index = 3, set = 0, binding = 3) uniform subpassInput u_depth;
+
+            layout(location = 0) out vec4 f_color;
+
+            struct PointLight {
+                vec4 position;
+                vec4 color;
+            };
+
+            layout(set = 0, binding = 4) uniform Lights {
+                PointLight lights[512];
+            } u_lights;
+
+            layout (push_constant) uniform PushConstants {
+                mat4 inverse_view_proj;
+                vec4 camera_position;
+                vec2 screen_size;
+                float time;
+                uint light_count;
+            } push;
+
+            vec3 light_position(PointLight light) {
+                float a = push.time * light.color.w;
+                vec3 p = light.position.xyz;
+                return vec3(cos(a) * p.x - sin(a) * p.z, p.y, sin(a) * p.x + cos(a) * p.z);
+            }
+
+            // The encoding of the target, see `OutputEncoding`.
+            layout(constant_id = 0) const uint output_encoding = 0;
+
+            #include <encoding.glsl>
+
+            void main() {
+                // Nothing was drawn here, leave the clear color.
+                float depth = subpassLoad(u_depth).x;
+                if (depth >= 1.0) {
+                    discard;
+                }
+
+                vec2 ndc = gl_FragCoord.xy / push.screen_size * 2.0 - 1.0;
+                vec4 world = push.inverse_view_proj * vec4(ndc, depth, 1.0);
+                vec3 position = world.xyz / world.w;
+
+                vec3 albedo = subpassLoad(u_albedo).rgb;
+                vec3 normal = normalize(subpassLoad(u_normal).xyz);
+                vec2 material = subpassLoad(u_material).xy;
+                vec3 view_dir = normalize(push.camera_position.xyz - position);
+                float shininess = 1.0 + material.y * 127.0;
+
+                vec3 color = 0.03 * albedo;
+                for (uint i = 0; i < push.light_count; i++) {
+                    PointLight light = u_lights.lights[i];
+                    vec3 to_light = light_position(light) - position;
+                    float distance = length(to_light);
+                    if (distance > light.position.w) {
+                        continue;
+                    }
+
+                    vec3 light_dir = to_light / distance;
+                    float attenuation = pow(1.0 - distance / light.position.w, 2.0);
+                    float diffuse = max(dot(normal, light_dir), 0.0);
+                    vec3 half_dir = normalize(light_dir + view_dir);
+                    float specular = pow(max(dot(normal, half_dir), 0.0), shininess) * material.x;
+
+                    color += (diffuse * albedo + specular) * light.color.rgb * attenuation;
+                }
+
+                f_color = encode_output(vec4(color, 1.0), output_encoding);
+            }",
+        types_meta: {
+            use bytemuck::{Pod, Zeroable};
+
+            #[derive(Clone, Copy, Zeroable, Pod)]
+        },
+    }
+}
+
+pub fn run(options: Options) -> Result<()> {
+    let kind = match options.args.first().map(String::as_str) {
+        Some("forward") => RendererKind::Forward,
+        Some("deferred") | None => RendererKind::Deferred,
+        Some(other) => {
+            return Err(Error::Content(format!(
+                "unknown renderer {other:?}, expected `forward` or `deferred`"
+            )))
+        }
+    };
+    log::info!("renderer: {kind:?}");
+
+    let deferred = Deferred {
+        kind,
+        clear_color: options.config.clear_color,
+        start_time: Instant::now(),
+    };
+    runner::run(options, deferred)
+}
+
+struct Deferred {
+    kind: RendererKind,
+    clear_color: [f32; 4],
+    start_time: Instant,
+}
+
+struct Renderer {
+    vertex_buffer: Arc<DeviceLocalBuffer<[Vertex]>>,
+    index_buffer: Arc<DeviceLocalBuffer<[u32]>>,
+    light_buffer: Arc<DeviceLocalBuffer<[PointLight]>>,
+    render_pass: Arc<RenderPass>,
+    scene_pipeline: Arc<GraphicsPipeline>,
+    /// Only in the deferred path.
+    lighting_pipeline: Option<Arc<GraphicsPipeline>>,
+    /// Only in the forward path.
+    scene_set: Option<Arc<PersistentDescriptorSet>>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    /// One for each framebuffer, only in the deferred path.
+    lighting_sets: Vec<Arc<PersistentDescriptorSet>>,
+}
+
+impl App for Deferred {
+    type Renderer = Renderer;
+
+    fn create(
+        &mut self,
+        gpu: &Gpu,
+        target: &Target,
+        _event_loop: Option<&EventLoopWindowTarget<()>>,
+    ) -> Result<Renderer> {
+        let device = &gpu.device;
+
+        let (vertices, indicies) = scene_geometry();
+
+        let mut uploader = gpu.uploader()?;
+        let vertex_buffer = uploader.buffer_from_iter(
+            &*gpu.memory_allocator,
+            BufferUsage {
+                vertex_buffer: true,
+                ..BufferUsage::empty()
+            },
+            vertices,
+        )?;
+        debug::set_buffer_name(&*vertex_buffer, "vertex buffer");
+
+        let index_buffer = uploader.buffer_from_iter(
+            &*gpu.memory_allocator,
+            BufferUsage {
+                index_buffer: true,
+                ..BufferUsage::empty()
+            },
+            indicies,
+        )?;
+        debug::set_buffer_name(&*index_buffer, "index buffer");
+
+        let light_buffer = uploader.buffer_from_iter(
+            &*gpu.memory_allocator,
+            BufferUsage {
+                uniform_buffer: true,
+                ..BufferUsage::empty()
+            },
+            scene_lights(),
+        )?;
+        debug::set_buffer_name(&*light_buffer, "light buffer");
+        uploader.submit_and_wait()?;
+
+        let vs = vs::load(device.clone()).context("can't load vertex shader")?;
+        let forward_fs = forward_fs::load(device.clone()).context("can't load fragment shader")?;
+        let gbuffer_fs = gbuffer_fs::load(device.clone()).context("can't load fragment shader")?;
+        let lighting_vs = lighting_vs::load(device.clone()).context("can't load vertex shader")?;
+        let lighting_fs =
+            lighting_fs::load(device.clone()).context("can't load fragment shader")?;
+
+        let render_pass = match self.kind {
+            RendererKind::Forward => vulkano::single_pass_renderpass!(
+                device.clone(),
+                attachments: {
+                    color: {
+                        load: Clear,
+                        store: Store,
+                        format: target.format(),
+                        samples: 1,
+                    },
+                    depth: {
+                        load: Clear,
+                        store: DontCare,
+                        format: DEPTH_FORMAT,
+                        samples: 1,
+                    }
+                },
+                pass: {
+                    color: [color],
+                    depth_stencil: {depth}
+                }
+            )
+            .context("can't create render pass")?,
+            RendererKind::Deferred => vulkano::ordered_passes_renderpass!(
+                device.clone(),
+                attachments: {
+                    color: {
+                        load: Clear,
+                        store: Store,
+                        format: target.format(),
+                        samples: 1,
+                    },
+                    albedo: {
+                        load: Clear,
+                        store: DontCare,
+                        format: GBUFFER_ALBEDO_FORMAT,
+                        samples: 1,
+                    },
+                    normal: {
+                        load: Clear,
+                        store: DontCare,
+                        format: GBUFFER_NORMAL_FORMAT,
+                        samples: 1,
+                    },
+                    material: {
+                        load: Clear,
+                        store: DontCare,
+                        format: GBUFFER_MATERIAL_FORMAT,
+                        samples: 1,
+                    },
+                    depth: {
+                        load: Clear,
+                        store: DontCare,
+                        format: DEPTH_FORMAT,
+                        samples: 1,
+                    }
+                },
+                passes: [
+                    // G-buffer pass
+                    { color: [albedo, normal, material], depth_stencil: {depth}, input: [] },
+                    // Lighting pass
+                    { color: [color], depth_stencil: {}, input: [albedo, normal, material, depth] }
+                ]
+            )
+            .context("can't create render pass")?,
+        };
+        debug::set_name(&*render_pass, "render pass");
+
+        let scene_subpass = Subpass::from(render_pass.clone(), 0).expect("can't create subpass");
+        let scene_builder = GraphicsPipeline::start()
+            .vertex_input_state(BuffersDefinition::new().vertex::<Vertex>())
+            .vertex_shader(setup::entry_point(&vs, "main")?, ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .depth_stencil_state(DepthStencilState::simple_depth_test())
+            .color_blend_state(ColorBlendState::new(scene_subpass.num_color_attachments()))
+            .render_pass(scene_subpass)
+            .build_with_cache(gpu.pipeline_cache.cache());
+        // Only the forward path writes to the target, the G-buffer is linear.
+        let scene_pipeline = match self.kind {
+            RendererKind::Forward => scene_builder.fragment_shader(
+                setup::entry_point(&forward_fs, "main")?,
+                forward_fs::SpecializationConstants {
+                    output_encoding: target.encoding() as u32,
+                },
+            ),
+            RendererKind::Deferred => {
+                scene_builder.fragment_shader(setup::entry_point(&gbuffer_fs, "main")?, ())
+            }
+        }
+        .build(device.clone())
+        .context("can't create graphics pipeline")?;
+        debug::set_name(&*scene_pipeline, "scene pipeline");
+
+        let lighting_pipeline = match self.kind {
+            RendererKind::Forward => None,
+            RendererKind::Deferred => {
+                let subpass = Subpass::from(render_pass.clone(), 1).expect("can't create subpass");
+                let pipeline = GraphicsPipeline::start()
+                    .vertex_input_state(BuffersDefinition::new())
+                    .vertex_shader(setup::entry_point(&lighting_vs, "main")?, ())
+                    .input_assembly_state(InputAssemblyState::new())
+                    .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+                    .fragment_shader(
+                        setup::entry_point(&lighting_fs, "main")?,
+                        lighting_fs::SpecializationConstants {
+                            output_encoding: target.encoding() as u32,
+                        },
+                    )
+                    .color_blend_state(ColorBlendState::new(subpass.num_color_attachments()))
+                    .render_pass(subpass)
+                    .build_with_cache(gpu.pipeline_cache.cache())
+                    .build(device.clone())
+                    .context("can't create lighting pipeline")?;
+                debug::set_name(&*pipeline, "lighting pipeline");
+                Some(pipeline)
+            }
+        };
+
+        // In the forward path the lights are read directly while shading the scene.
+        let scene_set = match self.kind {
+            RendererKind::Forward => Some(
+                PersistentDescriptorSet::new(
+                    &gpu.descriptor_set_allocator,
+                    scene_pipeline.layout().set_layouts()[0].clone(),
+                    [WriteDescriptorSet::buffer(0, light_buffer.clone())],
+                )
+                .context("can't create descriptor set")?,
+            ),
+            RendererKind::Deferred => None,
+        };
+
+        let (framebuffers, lighting_sets) = window_size_dependent_setup(
+            &gpu.memory_allocator,
+            &gpu.descriptor_set_allocator,
+            target,
+            render_pass.clone(),
+            lighting_pipeline.as_ref(),
+            light_buffer.clone(),
+        )?;
+
+        Ok(Renderer {
+            vertex_buffer,
+            index_buffer,
+            light_buffer,
+            render_pass,
+            scene_pipeline,
+            lighting_pipeline,
+            scene_set,
+            framebuffers,
+            lighting_sets,
+        })
+    }
+
+    fn resize(&mut self, renderer: &mut Renderer, gpu: &Gpu, target: &Target) -> Result<()> {
+        // The G-buffer follows the size of the target too.
+        (renderer.framebuffers, renderer.lighting_sets) = window_size_dependent_setup(
+            &gpu.memory_allocator,
+            &gpu.descriptor_set_allocator,
+            target,
+            renderer.render_pass.clone(),
+            renderer.lighting_pipeline.as_ref(),
+            renderer.light_buffer.clone(),
+        )?;
+        Ok(())
+    }
+
+    fn draw(
+        &mut self,
+        renderer: &mut Renderer,
+        _gpu: &Gpu,
+        target: &Target,
+        frame: &mut Frame,
+    ) -> Result<()> {
+        let time = self.start_time.elapsed().as_secs_f32();
+        let viewport = target.viewport();
+        let [width, height] = viewport.dimensions;
+
+        let angle = time * 0.1;
+        let camera_position = Point3::new(28.0 * angle.cos(), 14.0, 28.0 * angle.sin());
+        let view = Matrix4::look_at_rh(
+            camera_position,
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        );
+        let proj =
+            cgmath::perspective(Rad(std::f32::consts::FRAC_PI_3), width / height, 0.1, 100.0);
+        // cgmath follows the OpenGL clip space conventions, flip y and map z into 0..1.
+        let correction = Matrix4::new(
+            1.0, 0.0, 0.0, 0.0, //
+            0.0, -1.0, 0.0, 0.0, //
+            0.0, 0.0, 0.5, 0.0, //
+            0.0, 0.0, 0.5, 1.0, //
+        );
+        let view_proj = correction * proj * view;
+        let camera_position = [camera_position.x, camera_position.y, camera_position.z, 1.0];
+
+        let scene_push_constants = vs::ty::PushConstants {
+            view_proj: view_proj.into(),
+            camera_position,
+            time,
+            light_count: LIGHT_COUNT as u32,
+        };
+
+        let clear_values: Vec<Option<ClearValue>> = match self.kind {
+            RendererKind::Forward => vec![
+                Some(target.encoding().encode(self.clear_color).into()),
+                Some(1f32.into()),
+            ],
+            RendererKind::Deferred => vec![
+                Some(target.encoding().encode(self.clear_color).into()),
+                Some([0.0, 0.0, 0.0, 0.0].into()),
+                Some([0.0, 0.0, 0.0, 0.0].into()),
+                Some([0.0, 0.0, 0.0, 0.0].into()),
+                Some(1f32.into()),
+            ],
+        };
+
+        let builder = &mut frame.builder;
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values,
+                    ..RenderPassBeginInfo::framebuffer(
+                        renderer.framebuffers[frame.image_index].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .context("can't begin render pass")?;
+
+        debug::label(builder, "scene pass", |builder| -> Result<()> {
+            builder
+                .set_viewport(0, [viewport.clone()])
+                .bind_pipeline_graphics(renderer.scene_pipeline.clone())
+                .push_constants(
+                    renderer.scene_pipeline.layout().clone(),
+                    0,
+                    scene_push_constants,
+                );
+
+            if let Some(set) = &renderer.scene_set {
+                builder.bind_descriptor_sets(
+                    PipelineBindPoint::Graphics,
+                    renderer.scene_pipeline.layout().clone(),
+                    0,
+                    set.clone(),
+                );
+            }
+
+            builder
+                .bind_vertex_buffers(0, renderer.vertex_buffer.clone())
+                .bind_index_buffer(renderer.index_buffer.clone())
+                .draw_indexed(renderer.index_buffer.len() as u32, 1, 0, 0, 0)
+                .context("can't draw")?;
+            Ok(())
+        })?;
+
+        if let (Some(lighting_pipeline), Some(lighting_set)) = (
+            &renderer.lighting_pipeline,
+            renderer.lighting_sets.get(frame.image_index),
+        ) {
+            let lighting_push_constants = lighting_fs::ty::PushConstants {
+                inverse_view_proj: view_proj
+                    .invert()
+                    .expect("can't invert view projection")
+                    .into(),
+                camera_position,
+                screen_size: viewport.dimensions,
+                time,
+                light_count: LIGHT_COUNT as u32,
+            };
+
+            builder
+                .next_subpass(SubpassContents::Inline)
+                .context("can't begin lighting subpass")?;
+
+            debug::label(builder, "lighting pass", |builder| -> Result<()> {
+                builder
+                    .set_viewport(0, [viewport.clone()])
+                    .bind_pipeline_graphics(lighting_pipeline.clone())
+                    .push_constants(
+                        lighting_pipeline.layout().clone(),
+                        0,
+                        lighting_push_constants,
+                    )
+                    .bind_descriptor_sets(
+                        PipelineBindPoint::Graphics,
+                        lighting_pipeline.layout().clone(),
+                        0,
+                        lighting_set.clone(),
+                    )
+                    .draw(3, 1, 0, 0)
+                    .context("can't draw lighting pass")?;
+                Ok(())
+            })?;
+        }
+
+        builder.end_render_pass().context("can't end render pass")?;
+        Ok(())
+    }
+}
+
+/// Builds a floor with a grid of boxes of varying height, color and shininess on top of it.
+fn scene_geometry() -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    let mut indicies = Vec::new();
+
+    push_box(
+        &mut vertices,
+        &mut indicies,
+        [0.0, -0.05, 0.0],
+        [20.0, 0.05, 20.0],
+        [0.6, 0.6, 0.6],
+        [0.2, 0.1],
+    );
+
+    let mut rng = rand::rngs::StdRng::seed_from_u64(26);
+    for x in -4..=4 {
+        for z in -4..=4 {
+            let height = rng.gen_range(0.5..3.0);
+            push_box(
+                &mut vertices,
+                &mut indicies,
+                [x as f32 * 4.0, height, z as f32 * 4.0],
+                [0.8, height, 0.8],
+                [
+                    rng.gen_range(0.3..1.0),
+                    rng.gen_range(0.3..1.0),
+                    rng.gen_range(0.3..1.0),
+                ],
+                [rng.gen_range(0.0..1.0), rng.gen_range(0.0..1.0)],
+            );
+        }
+    }
+
+    (vertices, indicies)
+}
+
+fn push_box(
+    vertices: &mut Vec<Vertex>,
+    indicies: &mut Vec<u32>,
+    center: [f32; 3],
+    half_extent: [f32; 3],
+    color: [f32; 3],
+    material: [f32; 2],
+) {
+    // (normal, u, v) for every face, with u x v = normal
+    let faces = [
+        ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+        ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+        ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [1.0, 0.0, 0.0]),
+        ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ([0.0, 0.0, -1.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]),
+    ];
+
+    for (normal, u, v) in faces {
+        let base = vertices.len() as u32;
+        for (s, t) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+            let position = std::array::from_fn(|i| {
+                center[i] + half_extent[i] * (normal[i] + s * u[i] + t * v[i])
+            });
+            vertices.push(Vertex {
+                position,
+                normal,
+                color,
+                material,
+            });
+        }
+        indicies.extend([base, base + 1, base + 2, base + 2, base + 3, base]);
+    }
+}
+
+fn scene_lights() -> Vec<PointLight> {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(27);
+    let mut lights = vec![PointLight::default(); MAX_LIGHTS];
+    for light in lights.iter_mut().take(LIGHT_COUNT) {
+        *light = PointLight {
+            position: [
+                rng.gen_range(-18.0..18.0),
+                rng.gen_range(0.5..3.5),
+                rng.gen_range(-18.0..18.0),
+                rng.gen_range(2.5..5.0),
+            ],
+            color: [
+                rng.gen_range(0.2..1.0),
+                rng.gen_range(0.2..1.0),
+                rng.gen_range(0.2..1.0),
+                rng.gen_range(-0.5..0.5),
+            ],
+        };
+    }
+    lights
+}
+
+/// The framebuffers of `target` and, for the deferred renderer, the lighting set of each.
+/// Every framebuffer gets its own depth buffer and G-buffer, as the frames in flight drawing to
+/// different swapchain images can overlap on the GPU.
+fn window_size_dependent_setup(
+    memory_allocator: &StandardMemoryAllocator,
+    descriptor_set_allocator: &StandardDescriptorSetAllocator,
+    target: &Target,
+    render_pass: Arc<RenderPass>,
+    lighting_pipeline: Option<&Arc<GraphicsPipeline>>,
+    light_buffer: Arc<DeviceLocalBuffer<[PointLight]>>,
+) -> Result<(Vec<Arc<Framebuffer>>, Vec<Arc<PersistentDescriptorSet>>)> {
+    let dimensions = target.dimensions();
+
+    let attachment = |format: Format, name: String| -> Result<Arc<dyn ImageViewAbstract>> {
+        let image =
+            AttachmentImage::transient_input_attachment(memory_allocator, dimensions, format)
+                .context("can't create attachment image")?;
+        debug::set_image_name(&*image, &name);
+        Ok(ImageView::new_default(image).context("can't create image view")?)
+    };
+
+    let (mut framebuffers, mut sets) = (Vec::new(), Vec::new());
+    for (i, view) in target.views().iter().enumerate() {
+        let depth = attachment(DEPTH_FORMAT, format!("depth {}", i))?;
+
+        let Some(lighting_pipeline) = lighting_pipeline else {
+            framebuffers.push(
+                Framebuffer::new(
+                    render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone(), depth],
+                        ..Default::default()
+                    },
+                )
+                .context("can't create framebuffer")?,
+            );
+            continue;
+        };
+
+        // The G-buffer is only ever read by the lighting subpass, so it never leaves tile
+        // memory on hardware that supports transient attachments.
+        let albedo = attachment(GBUFFER_ALBEDO_FORMAT, format!("G-buffer albedo {}", i))?;
+        let normal = attachment(GBUFFER_NORMAL_FORMAT, format!("G-buffer normal {}", i))?;
+        let material = attachment(GBUFFER_MATERIAL_FORMAT, format!("G-buffer material {}", i))?;
+
+        framebuffers.push(
+            Framebuffer::new(
+                render_pass.clone(),
+                FramebufferCreateInfo {
+                    attachments: vec![
+                        view.clone(),
+                        albedo.clone(),
+                        normal.clone(),
+                        material.clone(),
+                        depth.clone(),
+                    ],
+                    ..Default::default()
+                },
+            )
+            .context("can't create framebuffer")?,
+        );
+
+        let layout = lighting_pipeline.layout().set_layouts()[0].clone();
+        sets.push(
+            PersistentDescriptorSet::new(
+                descriptor_set_allocator,
+                layout,
+                [
+                    WriteDescriptorSet::image_view(0, albedo),
+                    WriteDescriptorSet::image_view(1, normal),
+                    WriteDescriptorSet::image_view(2, material),
+                    WriteDescriptorSet::image_view(3, depth),
+                    WriteDescriptorSet::buffer(4, light_buffer.clone()),
+                ],
+            )
+            .context("can't create descriptor set")?,
+        );
+    }
+
+    Ok((framebuffers, sets))
+}
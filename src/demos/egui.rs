@@ -0,0 +1,1459 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use bytemuck::{Pod, Zeroable};
+use egui_winit_vulkano::{Gui, GuiConfig};
+use shaderc::ShaderKind;
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer, DeviceLocalBuffer, TypedBufferAccess};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, BufferImageCopy, CommandBufferInheritanceInfo, CommandBufferUsage,
+    CopyImageToBufferInfo, RenderPassBeginInfo, SubpassContents,
+};
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::layout::DescriptorType;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::Device;
+use vulkano::format::{ClearValue, Format};
+use vulkano::image::view::ImageView;
+use vulkano::image::{
+    AttachmentImage, ImageAccess, ImageDimensions, ImageUsage, ImmutableImage, MipmapsCount,
+    SampleCount,
+};
+use vulkano::impl_vertex;
+use vulkano::memory::allocator::StandardMemoryAllocator;
+use vulkano::pipeline::cache::PipelineCache;
+use vulkano::pipeline::graphics::color_blend::{ColorBlendState, ColorComponents};
+use vulkano::pipeline::graphics::input_assembly::{InputAssemblyState, PrimitiveTopology};
+use vulkano::pipeline::graphics::vertex_input::BuffersDefinition;
+use vulkano::pipeline::graphics::viewport::ViewportState;
+use vulkano::pipeline::graphics::GraphicsPipelineCreationError;
+use vulkano::pipeline::Pipeline;
+use vulkano::pipeline::{GraphicsPipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo};
+use vulkano::shader::EntryPoint;
+use winit::event::{
+    ElementState, KeyboardInput, ModifiersState, MouseButton, VirtualKeyCode, WindowEvent,
+};
+use winit::event_loop::EventLoopWindowTarget;
+
+use crate::cli::Options;
+use crate::config::{self, RendererConfig};
+use crate::debug;
+use crate::editor::{Edit, SceneEditor, Selection};
+use crate::error::{Context, Result};
+use crate::frames;
+use crate::gizmo::{Gizmo, GizmoMode, OverlayVertex, Ray};
+use crate::present::OutputEncoding;
+use crate::profiler::Profiler;
+use crate::reflect::{self, ExpectedDescriptor};
+use crate::runner::{self, App, Frame, Gpu, SubmitTimings, Target};
+use crate::scene::{Scene, MAX_LIGHTS};
+use crate::setup;
+use crate::shader::{ShaderError, ShaderWatcher, SHADER_DIR_ENV};
+use crate::stats::{CountingBuilder, FrameCounters};
+use crate::upload::Uploader;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
+struct Vertex {
+    position: [f32; 3],
+    tex_coords: [f32; 2],
+}
+
+impl_vertex!(Vertex, position, tex_coords);
+
+/// The descriptors `main` writes into set 0, which reloaded shaders are checked against.
+const DESCRIPTORS: [ExpectedDescriptor; 2] = [
+    ExpectedDescriptor {
+        set: 0,
+        binding: 0,
+        ty: DescriptorType::CombinedImageSampler,
+    },
+    ExpectedDescriptor {
+        set: 0,
+        binding: 1,
+        ty: DescriptorType::UniformBuffer,
+    },
+];
+
+/// How far the cursor can move between a press and a release that still select the entity
+/// under it, rather than only pan the camera.
+const CLICK_PIXELS: f32 = 4.0;
+
+// Built from `shaders/`, which is what `VULKAN_RUST_SHADER_DIR` usually points to. Its `Data` is
+// the start of [`fs::ty::Data`], which is what gets uploaded.
+#[allow(dead_code)]
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/egui.vert",
+        types_meta: {
+            use bytemuck::{Pod, Zeroable};
+
+            #[derive(Clone, Copy, Zeroable, Pod)]
+        },
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/egui.frag",
+        types_meta: {
+            use bytemuck::{Pod, Zeroable};
+
+            #[derive(Clone, Copy, Zeroable, Pod)]
+        },
+    }
+}
+
+/// The same fragment shader, also writing the entity IDs. Its uniforms are [`fs::ty::Data`].
+#[allow(dead_code)]
+mod picking_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/egui.frag",
+        define: [("PICKING", "")],
+    }
+}
+
+/// Draws the gizmo over the scene, in colors that are already encoded for the output.
+mod overlay_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
+            #version 450
+
+            layout(location = 0) in vec3 position;
+            layout(location = 1) in vec4 color;
+
+            layout(location = 0) out vec4 v_color;
+
+            layout(push_constant) uniform PushConstants {
+                mat4 view_projection;
+            } overlay;
+
+            void main() {
+                gl_Position = overlay.view_projection * vec4(position, 1.0);
+                v_color = color;
+            }",
+        types_meta: {
+            use bytemuck::{Pod, Zeroable};
+
+            #[derive(Clone, Copy, Zeroable, Pod)]
+        },
+    }
+}
+
+mod overlay_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: "
+            #version 450
+
+            layout(location = 0) in vec4 color;
+
+            layout(location = 0) out vec4 f_color;
+
+            void main() {
+                f_color = color;
+            }"
+    }
+}
+
+/// CPU copies of everything the renderer uploads, kept around so the GPU resources can be
+/// created again after the device is lost.
+struct Assets {
+    /// One for each entity of the scene, in the same order.
+    meshes: Vec<MeshAssets>,
+    textures: Vec<TextureAssets>,
+}
+
+struct MeshAssets {
+    vertices: Vec<Vertex>,
+    indices: Vec<u16>,
+    /// An index into [`Assets::textures`].
+    texture: usize,
+}
+
+struct TextureAssets {
+    dimensions: ImageDimensions,
+    data: Vec<u8>,
+}
+
+impl Assets {
+    /// Loads what `scene` is drawn with. Materials without a texture get `default_texture` when
+    /// given, instead of the built-in image. A texture that can't be loaded is logged and
+    /// replaced by the default one, so a path that is still being typed in the editor doesn't
+    /// stop the scene from being drawn.
+    fn load(scene: &Scene, default_texture: Option<&Path>) -> Result<Self> {
+        let png_bytes = match default_texture {
+            Some(path) => std::fs::read(path).context("can't read texture")?,
+            None => include_bytes!("../image.png").to_vec(),
+        };
+        let mut textures = vec![decode_png(png_bytes)?];
+        let mut texture_indices: HashMap<PathBuf, usize> = HashMap::new();
+
+        let meshes = scene
+            .entities
+            .iter()
+            .map(|entity| {
+                let texture = match scene.material(entity).texture {
+                    Some(path) => {
+                        let path = scene.resolve(&path);
+                        match texture_indices.get(&path) {
+                            Some(&texture) => texture,
+                            None => {
+                                let texture = match std::fs::read(&path)
+                                    .context("can't read texture")
+                                    .and_then(decode_png)
+                                {
+                                    Ok(texture) => {
+                                        textures.push(texture);
+                                        textures.len() - 1
+                                    }
+                                    Err(e) => {
+                                        log::error!("{}: {}", path.display(), e);
+                                        0
+                                    }
+                                };
+                                texture_indices.insert(path, texture);
+                                texture
+                            }
+                        }
+                    }
+                    None => 0,
+                };
+
+                let mesh = entity.mesh.data();
+                MeshAssets {
+                    vertices: mesh
+                        .positions
+                        .iter()
+                        .zip(&mesh.tex_coords)
+                        .map(|(&position, &tex_coords)| Vertex {
+                            position,
+                            tex_coords,
+                        })
+                        .collect(),
+                    indices: mesh.indices,
+                    texture,
+                }
+            })
+            .collect();
+
+        Ok(Self { meshes, textures })
+    }
+}
+
+fn decode_png(png_bytes: Vec<u8>) -> Result<TextureAssets> {
+    let cursor = Cursor::new(png_bytes);
+    let decoder = png::Decoder::new(cursor);
+    let mut reader = decoder.read_info().context("can't read png info")?;
+    let info = reader.info();
+    let dimensions = ImageDimensions::Dim2d {
+        width: info.width,
+        height: info.height,
+        array_layers: 1,
+    };
+    let mut data = Vec::new();
+    data.resize((info.width * info.height * 4) as usize, 0);
+    reader.next_frame(&mut data).context("can't decode png")?;
+
+    Ok(TextureAssets { dimensions, data })
+}
+
+/// An entity of the scene, as drawn this frame.
+struct EntityDraw {
+    /// An index into [`Assets::meshes`].
+    mesh: usize,
+    push_constants: vs::ty::PushConstants,
+}
+
+/// What a frame draws, see [`scene_draws`].
+struct SceneDraws {
+    uniforms: fs::ty::Data,
+    entities: Vec<EntityDraw>,
+    overlay: Vec<OverlayVertex>,
+}
+
+/// The GPU side of a [`MeshAssets`].
+struct Mesh {
+    vertex_buffer: Arc<DeviceLocalBuffer<[Vertex]>>,
+    index_buffer: Arc<DeviceLocalBuffer<[u16]>>,
+    texture: usize,
+}
+
+/// What each frame in flight writes or allocates while it's recorded, so it's never touched
+/// while the GPU may still be reading it.
+struct FrameResources {
+    uniform_buffer: Arc<CpuAccessibleBuffer<fs::ty::Data>>,
+    /// One for each texture, as the texture and the uniform buffer share a set.
+    sets: Vec<Arc<PersistentDescriptorSet>>,
+    /// Where the entity ID under the cursor is copied to.
+    pick_buffer: Arc<CpuAccessibleBuffer<[u32]>>,
+    /// Whether the frame copied an ID into `pick_buffer`.
+    picking: bool,
+}
+
+pub fn run(options: Options) -> Result<()> {
+    let app = Egui::new(&options)?;
+    runner::run(options, app)
+}
+
+struct Egui {
+    /// The settings as changed in the settings windows, which are saved to `config_path` once
+    /// the pointer is released, rather than on every step of a dragged slider. Flags aren't
+    /// saved unless they're changed there.
+    config: RendererConfig,
+    config_path: PathBuf,
+    config_changed: bool,
+    stats_csv: Option<PathBuf>,
+    editor: SceneEditor,
+    gizmo: Gizmo,
+    /// What the scene is drawn with, for uploading it again when the device is lost.
+    assets: Assets,
+    default_texture: Option<PathBuf>,
+    shader_watcher: Option<ShaderWatcher>,
+    shader_error: Option<String>,
+    /// Dragging where there is no gizmo handle pans the camera.
+    cursor: [f32; 2],
+    panning: bool,
+    modifiers: ModifiersState,
+    /// The entity under the cursor, as of the last ID read back. Clicking without dragging
+    /// selects it.
+    hovered: Option<usize>,
+    press_cursor: Option<[f32; 2]>,
+    pointer_over_gui: bool,
+}
+
+/// Everything created from the device. When the device is lost it's built again from the
+/// [`Assets`].
+struct Renderer {
+    render_pass: Arc<RenderPass>,
+    subpass: Subpass,
+    pipeline: Arc<GraphicsPipeline>,
+    overlay_pipeline: Arc<GraphicsPipeline>,
+    /// One for each frame in flight, indexed by [`Frame::in_flight`].
+    frames: Vec<FrameResources>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    /// The entity ID attachment of each framebuffer, none when picking is off.
+    ids: Vec<Arc<ImageView<AttachmentImage>>>,
+    /// The last ID read back, 0 for no entity and the index of the entity plus 1 otherwise.
+    picked: Option<u32>,
+    meshes: Vec<Mesh>,
+    textures: Vec<Arc<ImageView<ImmutableImage>>>,
+    sampler: Arc<Sampler>,
+    profiler: Profiler,
+    /// `None` when running headless, which draws the scene without the GUI.
+    gui: Option<Gui>,
+    /// The size of the target, which window events are interpreted at.
+    dimensions: [u32; 2],
+    picking: bool,
+    /// What the last frame recorded, for when it's submitted: its frame in flight, whether it
+    /// copies an ID and what it counted.
+    in_flight: usize,
+    pick_pending: bool,
+    counters: FrameCounters,
+}
+
+impl App for Egui {
+    type Renderer = Renderer;
+
+    fn create(
+        &mut self,
+        gpu: &Gpu,
+        target: &Target,
+        event_loop: Option<&EventLoopWindowTarget<()>>,
+    ) -> Result<Renderer> {
+        let device = &gpu.device;
+
+        let mut uploader = gpu.uploader()?;
+        let (meshes, textures) = upload_assets(&gpu.memory_allocator, &mut uploader, &self.assets)?;
+
+        let picking = self.config.picking;
+        let vs = vs::load(device.clone()).context("can't load vertex shader")?;
+        let fs = if picking {
+            picking_fs::load(device.clone())
+        } else {
+            fs::load(device.clone())
+        }
+        .context("can't load fragment shader")?;
+
+        let render_pass = create_render_pass(device, target.format(), picking)?;
+        debug::set_name(&*render_pass, "render pass");
+
+        let subpass = Subpass::from(render_pass.clone(), 0).expect("can't create subpass");
+        let pipeline = create_pipeline(
+            device.clone(),
+            gpu.pipeline_cache.cache(),
+            setup::entry_point(&vs, "main")?,
+            setup::entry_point(&fs, "main")?,
+            subpass.clone(),
+        )
+        .context("can't create graphics pipeline")?;
+        debug::set_name(&*pipeline, "pipeline");
+
+        let overlay_pipeline = {
+            let vs =
+                overlay_vs::load(device.clone()).context("can't load overlay vertex shader")?;
+            let fs =
+                overlay_fs::load(device.clone()).context("can't load overlay fragment shader")?;
+            GraphicsPipeline::start()
+                .vertex_input_state(BuffersDefinition::new().vertex::<OverlayVertex>())
+                .vertex_shader(setup::entry_point(&vs, "main")?, ())
+                .input_assembly_state(InputAssemblyState::new())
+                .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+                .fragment_shader(setup::entry_point(&fs, "main")?, ())
+                .color_blend_state({
+                    // The gizmo isn't an entity, it keeps the ID of what is behind it.
+                    let mut state = color_blend_state(&subpass);
+                    for attachment in state.attachments.iter_mut().skip(1) {
+                        attachment.color_write_mask = ColorComponents::empty();
+                    }
+                    state
+                })
+                .render_pass(subpass.clone())
+                .build_with_cache(gpu.pipeline_cache.cache())
+                .build(device.clone())
+                .context("can't create overlay pipeline")?
+        };
+        debug::set_name(&*overlay_pipeline, "overlay pipeline");
+
+        let (framebuffers, ids) = window_size_dependent_setup(
+            target,
+            render_pass.clone(),
+            &gpu.memory_allocator,
+            picking,
+        )?;
+
+        let sampler = Sampler::new(
+            device.clone(),
+            SamplerCreateInfo {
+                mag_filter: Filter::Linear,
+                min_filter: Filter::Linear,
+                address_mode: [SamplerAddressMode::Repeat; 3],
+                ..Default::default()
+            },
+        )
+        .context("can't create sampler")?;
+
+        // Everything is uploaded once, so the frames can simply start after it.
+        uploader.submit_and_wait()?;
+
+        let frames_in_flight = frames::frames_in_flight();
+        let frames = (0..frames_in_flight)
+            .map(|i| -> Result<_> {
+                let uniform_buffer = CpuAccessibleBuffer::from_data(
+                    &*gpu.memory_allocator,
+                    BufferUsage {
+                        uniform_buffer: true,
+                        ..BufferUsage::empty()
+                    },
+                    false,
+                    Zeroable::zeroed(),
+                )
+                .context("can't create uniform buffer")?;
+                debug::set_buffer_name(&*uniform_buffer, &format!("uniform buffer {}", i));
+
+                let pick_buffer = CpuAccessibleBuffer::from_iter(
+                    &*gpu.memory_allocator,
+                    BufferUsage {
+                        transfer_dst: true,
+                        ..BufferUsage::empty()
+                    },
+                    true,
+                    [0u32],
+                )
+                .context("can't create pick buffer")?;
+                debug::set_buffer_name(&*pick_buffer, &format!("pick buffer {}", i));
+
+                Ok(FrameResources {
+                    sets: create_sets(
+                        &gpu.descriptor_set_allocator,
+                        &pipeline,
+                        &textures,
+                        &sampler,
+                        &uniform_buffer,
+                    )?,
+                    uniform_buffer,
+                    pick_buffer,
+                    picking: false,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // Headless runs draw the scene without it.
+        let gui = event_loop
+            .zip(target.surface())
+            .map(|(event_loop, surface)| {
+                Gui::new_with_subpass(
+                    event_loop,
+                    surface.clone(),
+                    gpu.queues.graphics.clone(),
+                    Subpass::from(render_pass.clone(), 1).expect("can't create subpass"),
+                    GuiConfig::default(),
+                )
+            });
+        let mut profiler = Profiler::new(&gpu.queues.graphics, frames_in_flight)?;
+        if let Some(path) = &self.stats_csv {
+            profiler.set_export_path(path.clone());
+        }
+
+        // Nothing created from a lost device can be reused, including the shader modules of a
+        // hot reloaded pipeline, so they're compiled again for this one.
+        if let Some(watcher) = self.shader_watcher.as_mut() {
+            watcher.reset();
+        }
+
+        Ok(Renderer {
+            render_pass,
+            subpass,
+            pipeline,
+            overlay_pipeline,
+            frames,
+            framebuffers,
+            ids,
+            picked: None,
+            meshes,
+            textures,
+            sampler,
+            profiler,
+            gui,
+            dimensions: target.dimensions(),
+            picking,
+            in_flight: 0,
+            pick_pending: false,
+            counters: FrameCounters::default(),
+        })
+    }
+
+    fn resize(&mut self, renderer: &mut Renderer, gpu: &Gpu, target: &Target) -> Result<()> {
+        (renderer.framebuffers, renderer.ids) = window_size_dependent_setup(
+            target,
+            renderer.render_pass.clone(),
+            &gpu.memory_allocator,
+            renderer.picking,
+        )?;
+        renderer.dimensions = target.dimensions();
+        Ok(())
+    }
+
+    fn window_event(&mut self, renderer: &mut Renderer, event: &WindowEvent) {
+        // Presses and keys egui uses don't reach the gizmo or the camera. Releases and cursor
+        // moves always do, so a drag ends even when it ends over a window.
+        let pass_events_to_game = !renderer.gui.as_mut().map_or(false, |gui| gui.update(event));
+        let dimensions = renderer.dimensions;
+        let view_projection = {
+            let camera = &self.editor.scene().camera;
+            camera.projection(dimensions) * camera.view()
+        };
+        let editor = &mut self.editor;
+
+        match event {
+            WindowEvent::ModifiersChanged(state) => {
+                self.modifiers = *state;
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                let position = [position.x as f32, position.y as f32];
+                if self.panning {
+                    pan_camera(editor, self.cursor, position, dimensions, view_projection);
+                } else {
+                    self.gizmo
+                        .cursor_moved(editor, position, dimensions, view_projection);
+                }
+                self.cursor = position;
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => match state {
+                ElementState::Pressed if pass_events_to_game => {
+                    self.panning =
+                        !self
+                            .gizmo
+                            .press(editor, self.cursor, dimensions, view_projection);
+                    self.press_cursor = self.panning.then_some(self.cursor);
+                }
+                ElementState::Pressed => (),
+                ElementState::Released => {
+                    self.gizmo.release(editor);
+                    self.panning = false;
+                    let cursor = self.cursor;
+                    let clicked = self.press_cursor.take().map_or(false, |[x, y]| {
+                        (cursor[0] - x).hypot(cursor[1] - y) < CLICK_PIXELS
+                    });
+                    if clicked {
+                        editor.select(self.hovered.map(Selection::Entity));
+                    }
+                }
+            },
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } if pass_events_to_game => match key {
+                VirtualKeyCode::W => self.gizmo.mode = GizmoMode::Translate,
+                VirtualKeyCode::E => self.gizmo.mode = GizmoMode::Rotate,
+                VirtualKeyCode::R => self.gizmo.mode = GizmoMode::Scale,
+                VirtualKeyCode::Z if self.modifiers.ctrl() && self.modifiers.shift() => {
+                    self.gizmo.redo(editor)
+                }
+                VirtualKeyCode::Z if self.modifiers.ctrl() => self.gizmo.undo(editor),
+                VirtualKeyCode::Y if self.modifiers.ctrl() => self.gizmo.redo(editor),
+                _ => (),
+            },
+            _ => (),
+        }
+    }
+
+    fn draw(
+        &mut self,
+        renderer: &mut Renderer,
+        gpu: &Gpu,
+        target: &Target,
+        frame: &mut Frame,
+    ) -> Result<()> {
+        self.reload_shaders(renderer, gpu);
+
+        let mut scene_edit = Edit::None;
+        if let (Some(gui), Some(support)) = (renderer.gui.as_mut(), target.support()) {
+            let profiler = &mut renderer.profiler;
+            gui.immediate_ui(|gui| {
+                let ctx = gui.context();
+                profiler.ui(&ctx);
+                profiler.counters_ui(&ctx);
+                // The runner recreates the swapchain when the present settings change.
+                let present_before = frame.present;
+                frame.present.ui(&ctx, support);
+                let config_before = self.config.clone();
+                self.config
+                    .update_present_settings(&present_before, &frame.present);
+                self.config.ui(&ctx);
+                self.config_changed |= self.config != config_before;
+                if self.config_changed && !ctx.is_using_pointer() {
+                    if let Err(e) = self.config.save(&self.config_path) {
+                        log::error!("can't save config: {}", e);
+                    }
+                    self.config_changed = false;
+                }
+                if let Some(error) = &self.shader_error {
+                    egui::Window::new("Shader Errors")
+                        .default_width(500.0)
+                        .show(&ctx, |ui| {
+                            egui::ScrollArea::vertical().show(ui, |ui| {
+                                ui.colored_label(egui::Color32::RED, error);
+                            });
+                        });
+                }
+                scene_edit = self.editor.ui(&ctx);
+                self.gizmo.ui(&ctx, &mut self.editor);
+                self.pointer_over_gui = ctx.is_pointer_over_area();
+            });
+        }
+
+        if scene_edit == Edit::Structure {
+            self.gizmo.clear_history();
+            // A failed upload leaves the last scene that uploaded, drawn with its meshes.
+            let uploaded = Assets::load(self.editor.scene(), self.default_texture.as_deref())
+                .and_then(|assets| {
+                    renderer.set_assets(gpu, &assets)?;
+                    self.assets = assets;
+                    Ok(())
+                });
+            if let Err(e) = uploaded {
+                log::error!("can't upload scene: {}", e);
+            }
+        }
+
+        // The frame's fence was waited for, so the copy it recorded last time is done.
+        let resources = &mut renderer.frames[frame.in_flight];
+        if std::mem::take(&mut resources.picking) {
+            if let Ok(ids) = resources.pick_buffer.read() {
+                renderer.picked = Some(ids[0]);
+            }
+        }
+        if let Some(picked) = renderer.take_picked() {
+            self.hovered = picked;
+        }
+        let entity_count = self.editor.scene().entities.len();
+        self.hovered = self.hovered.filter(|&entity| entity < entity_count);
+
+        let draws = scene_draws(
+            &mut self.editor,
+            &self.gizmo,
+            &self.assets,
+            self.hovered,
+            target.dimensions(),
+            target.encoding(),
+        );
+        // Headless runs have no cursor to pick with.
+        let pick = (self.config.picking
+            && renderer.gui.is_some()
+            && !self.pointer_over_gui
+            && !self.gizmo.is_dragging())
+        .then(|| self.cursor.map(|c| c.max(0.0) as u32));
+        if pick.is_none() {
+            self.hovered = None;
+        }
+
+        renderer.record(gpu, target, frame, &draws, pick, self.config.clear_color)
+    }
+
+    fn submitted(&mut self, renderer: &mut Renderer, timings: SubmitTimings) {
+        renderer.frames[renderer.in_flight].picking = renderer.pick_pending;
+        let counters = std::mem::take(&mut renderer.counters);
+        renderer
+            .profiler
+            .end_frame(timings.wait, timings.record, timings.submit, counters);
+    }
+}
+
+impl Egui {
+    fn new(options: &Options) -> Result<Self> {
+        // A scene given on the command line is saved back to its file, the built-in one to
+        // `scene.toml` in the working directory unless another path is entered.
+        let (scene, scene_path) = match options.args.first() {
+            Some(path) => {
+                let path = PathBuf::from(path);
+                (Scene::load(&path).context("can't load scene")?, path)
+            }
+            None => (
+                Scene::builtin(config::Format::Toml, include_str!("../../scenes/egui.toml"))
+                    .expect("can't load built-in scene"),
+                PathBuf::from("scene.toml"),
+            ),
+        };
+        let default_texture = options.config.assets.texture.clone();
+        let assets = Assets::load(&scene, default_texture.as_deref())?;
+
+        // When set, the built-in shaders are replaced by the GLSL files in that directory, which
+        // are recompiled whenever they change.
+        let shader_dir = std::env::var_os(SHADER_DIR_ENV)
+            .map(PathBuf::from)
+            .or_else(|| options.config.assets.shader_dir.clone());
+        let shader_watcher = shader_dir
+            .map(|dir| {
+                ShaderWatcher::new([
+                    (dir.join("egui.vert"), ShaderKind::Vertex),
+                    (dir.join("egui.frag"), ShaderKind::Fragment),
+                ])
+            })
+            .transpose()
+            .context("can't create shader watcher")?;
+
+        Ok(Self {
+            config: options.config.clone(),
+            config_path: options
+                .config_path
+                .clone()
+                .unwrap_or_else(|| PathBuf::from(config::DEFAULT_CONFIG)),
+            config_changed: false,
+            stats_csv: options.stats_csv.clone(),
+            editor: SceneEditor::new(scene, scene_path),
+            gizmo: Gizmo::new(),
+            assets,
+            default_texture,
+            shader_watcher,
+            shader_error: None,
+            cursor: [0.0; 2],
+            panning: false,
+            modifiers: ModifiersState::empty(),
+            hovered: None,
+            press_cursor: None,
+            pointer_over_gui: false,
+        })
+    }
+
+    /// Rebuilds the pipeline when the watched shaders changed. When they don't compile, or
+    /// don't match what the renderer binds, the error is shown and the last pipeline that
+    /// worked is kept.
+    fn reload_shaders(&mut self, renderer: &mut Renderer, gpu: &Gpu) {
+        let Some(watcher) = self.shader_watcher.as_mut() else {
+            return;
+        };
+        if !watcher.poll() {
+            return;
+        }
+        let reloaded = reload_pipeline(
+            watcher,
+            &gpu.device,
+            gpu.pipeline_cache.cache(),
+            renderer.subpass.clone(),
+            renderer.picking,
+        );
+        match reloaded {
+            Ok(pipeline) => match renderer.set_pipeline(gpu, pipeline) {
+                Ok(()) => {
+                    self.shader_error = None;
+                    log::info!("reloaded shaders");
+                }
+                Err(e) => self.shader_error = Some(e.to_string()),
+            },
+            Err(e) => self.shader_error = Some(e.to_string()),
+        }
+    }
+}
+
+impl Renderer {
+    fn set_pipeline(&mut self, gpu: &Gpu, pipeline: Arc<GraphicsPipeline>) -> Result<()> {
+        debug::set_name(&*pipeline, "reloaded pipeline");
+        // The new pipeline has its own layout, so the sets have to follow it.
+        for frame in &mut self.frames {
+            frame.sets = create_sets(
+                &gpu.descriptor_set_allocator,
+                &pipeline,
+                &self.textures,
+                &self.sampler,
+                &frame.uniform_buffer,
+            )?;
+        }
+        self.pipeline = pipeline;
+        Ok(())
+    }
+
+    /// Replaces the meshes and textures with `assets`, after the scene was edited.
+    fn set_assets(&mut self, gpu: &Gpu, assets: &Assets) -> Result<()> {
+        let mut uploader = gpu.uploader()?;
+        let (meshes, textures) = upload_assets(&gpu.memory_allocator, &mut uploader, assets)?;
+        // Frames in flight hold on to the old ones until they're done.
+        uploader.submit_and_wait()?;
+
+        for frame in &mut self.frames {
+            frame.sets = create_sets(
+                &gpu.descriptor_set_allocator,
+                &self.pipeline,
+                &textures,
+                &self.sampler,
+                &frame.uniform_buffer,
+            )?;
+        }
+        self.meshes = meshes;
+        self.textures = textures;
+        Ok(())
+    }
+
+    /// Records `draws` into `frame`. When picking is on, the entity ID at pixel `pick` is read
+    /// back and turns up in [`Self::take_picked`] once this frame in flight comes around again.
+    fn record(
+        &mut self,
+        gpu: &Gpu,
+        target: &Target,
+        frame: &mut Frame,
+        draws: &SceneDraws,
+        pick: Option<[u32; 2]>,
+        clear_color: [f32; 4],
+    ) -> Result<()> {
+        // Clearing writes the color as it is, the shaders don't get to encode it.
+        let clear_color = target.encoding().encode(clear_color);
+        let queue_family_index = gpu.queues.graphics.queue_family_index();
+
+        // Only what the demo records itself is counted, not the gui.
+        let mut counters = FrameCounters::default();
+        let resources = &self.frames[frame.in_flight];
+        // The frame's fence was waited for, so the GPU is done with its buffer.
+        *resources
+            .uniform_buffer
+            .write()
+            .context("can't write uniform buffer")? = draws.uniforms;
+        counters.bytes_uploaded += std::mem::size_of_val(&draws.uniforms) as u64;
+
+        let builder = &mut frame.builder;
+        self.profiler.begin_frame(builder, frame.in_flight)?;
+
+        // Timestamps can't be written in a subpass that only executes secondary command buffers,
+        // so the render pass is timed as a whole and the scene inside its secondary buffer.
+        self.profiler
+            .pass(builder, "render pass", |builder| -> Result<()> {
+                builder
+                    .begin_render_pass(
+                        RenderPassBeginInfo {
+                            clear_values: if self.picking {
+                                vec![Some(clear_color.into()), Some(ClearValue::Uint([0; 4]))]
+                            } else {
+                                vec![Some(clear_color.into())]
+                            },
+                            ..RenderPassBeginInfo::framebuffer(
+                                self.framebuffers[frame.image_index].clone(),
+                            )
+                        },
+                        SubpassContents::SecondaryCommandBuffers,
+                    )
+                    .context("can't begin render pass")?;
+
+                let mut secondary_builder = AutoCommandBufferBuilder::secondary(
+                    &gpu.command_buffer_allocator,
+                    queue_family_index,
+                    CommandBufferUsage::MultipleSubmit,
+                    CommandBufferInheritanceInfo {
+                        render_pass: Some(self.subpass.clone().into()),
+                        ..Default::default()
+                    },
+                )
+                .context("can't create command buffer builder")?;
+
+                self.profiler
+                    .pass(&mut secondary_builder, "scene pass", |builder| {
+                        self.profiler.statistics(builder, |builder| -> Result<()> {
+                            let mut builder = CountingBuilder::new(builder, &mut counters);
+                            builder
+                                .set_viewport(0, [target.viewport()])
+                                .bind_pipeline_graphics(self.pipeline.clone());
+                            for entity in &draws.entities {
+                                let mesh = &self.meshes[entity.mesh];
+                                builder
+                                    .bind_descriptor_sets(
+                                        PipelineBindPoint::Graphics,
+                                        self.pipeline.layout().clone(),
+                                        0,
+                                        resources.sets[mesh.texture].clone(),
+                                    )
+                                    .push_constants(
+                                        self.pipeline.layout().clone(),
+                                        0,
+                                        entity.push_constants,
+                                    )
+                                    .bind_vertex_buffers(0, mesh.vertex_buffer.clone())
+                                    .bind_index_buffer(mesh.index_buffer.clone())
+                                    .draw_indexed(mesh.index_buffer.len() as u32, 1, 0, 0, 0)
+                                    .context("can't draw")?;
+                            }
+
+                            if !draws.overlay.is_empty() {
+                                // Rebuilt every frame, it's a few hundred vertices at most.
+                                let vertex_buffer = CpuAccessibleBuffer::from_iter(
+                                    &*gpu.memory_allocator,
+                                    BufferUsage {
+                                        vertex_buffer: true,
+                                        ..BufferUsage::empty()
+                                    },
+                                    false,
+                                    draws.overlay.iter().copied(),
+                                )
+                                .context("can't create overlay vertex buffer")?;
+                                builder
+                                    .bind_pipeline_graphics(self.overlay_pipeline.clone())
+                                    .push_constants(
+                                        self.overlay_pipeline.layout().clone(),
+                                        0,
+                                        overlay_vs::ty::PushConstants {
+                                            view_projection: draws.uniforms.view_projection,
+                                        },
+                                    )
+                                    .bind_vertex_buffers(0, vertex_buffer)
+                                    .draw(draws.overlay.len() as u32, 1, 0, 0)
+                                    .context("can't draw overlay")?;
+                            }
+                            Ok(())
+                        })
+                    })?;
+
+                let cb = secondary_builder
+                    .build()
+                    .context("can't build command buffer")?;
+                builder
+                    .execute_commands(cb)
+                    .context("can't execute secondary command buffer")?;
+
+                // Move on to next subpass for gui
+                builder
+                    .next_subpass(SubpassContents::SecondaryCommandBuffers)
+                    .context("can't begin gui subpass")?;
+                // Draw gui on subpass
+                if let Some(gui) = self.gui.as_mut() {
+                    let cb = gui.draw_on_subpass_image(target.dimensions());
+                    debug::label(builder, "gui subpass", |builder| -> Result<()> {
+                        builder
+                            .execute_commands(cb)
+                            .context("can't execute gui command buffer")?;
+                        Ok(())
+                    })?;
+                }
+
+                // Last end render pass
+                builder.end_render_pass().context("can't end render pass")?;
+                Ok(())
+            })?;
+
+        let pick = pick
+            .zip(self.ids.get(frame.image_index))
+            .filter(|([x, y], ids)| {
+                let [width, height] = ids.image().dimensions().width_height();
+                *x < width && *y < height
+            });
+        if let Some(([x, y], ids)) = pick {
+            builder
+                .copy_image_to_buffer(CopyImageToBufferInfo {
+                    regions: [BufferImageCopy {
+                        image_subresource: ids.image().subresource_layers(),
+                        image_offset: [x, y, 0],
+                        image_extent: [1, 1, 1],
+                        ..Default::default()
+                    }]
+                    .into(),
+                    ..CopyImageToBufferInfo::image_buffer(
+                        ids.image().clone(),
+                        resources.pick_buffer.clone(),
+                    )
+                })
+                .context("can't copy picked id")?;
+        }
+
+        self.in_flight = frame.in_flight;
+        self.pick_pending = pick.is_some();
+        self.counters = counters;
+        Ok(())
+    }
+
+    /// The entity index of the last ID read back, `Some(None)` when there was no entity.
+    fn take_picked(&mut self) -> Option<Option<usize>> {
+        self.picked
+            .take()
+            .map(|id| id.checked_sub(1).map(|index| index as usize))
+    }
+}
+
+/// The uniforms, the entities of the scene with `hovered` highlighted, and the gizmo over
+/// them, as drawn at `dimensions`.
+fn scene_draws(
+    editor: &mut SceneEditor,
+    gizmo: &Gizmo,
+    assets: &Assets,
+    hovered: Option<usize>,
+    dimensions: [u32; 2],
+    encoding: OutputEncoding,
+) -> SceneDraws {
+    let scene = editor.scene();
+    let view_projection = scene.camera.projection(dimensions) * scene.camera.view();
+    let lights = std::array::from_fn(|i| {
+        scene.lights.get(i).map_or_else(Zeroable::zeroed, |light| {
+            let (position, color) = light.shader_data();
+            fs::ty::Light { position, color }
+        })
+    });
+    let light_count = scene.lights.len().min(MAX_LIGHTS) as u32;
+    let colors = scene
+        .entities
+        .iter()
+        .enumerate()
+        .map(|(i, entity)| {
+            let color = scene.material(entity).color;
+            if hovered == Some(i) {
+                highlight(color)
+            } else {
+                color
+            }
+        })
+        .collect::<Vec<_>>();
+    let entities = editor
+        .draw_items()
+        .filter(|item| *item.item < assets.meshes.len())
+        .map(|item| EntityDraw {
+            mesh: *item.item,
+            push_constants: vs::ty::PushConstants {
+                model: item.world.into(),
+                color: colors[*item.item],
+                id: *item.item as u32 + 1,
+            },
+        })
+        .collect::<Vec<_>>();
+    let overlay = gizmo.vertices(editor, dimensions, view_projection, encoding);
+
+    let uniforms = fs::ty::Data {
+        view_projection: view_projection.into(),
+        lights,
+        light_count,
+        output_encoding: encoding as u32,
+    };
+    SceneDraws {
+        uniforms,
+        entities,
+        overlay,
+    }
+}
+
+/// A set for each of `textures`, sharing `uniform_buffer`.
+fn create_sets(
+    allocator: &StandardDescriptorSetAllocator,
+    pipeline: &Arc<GraphicsPipeline>,
+    textures: &[Arc<ImageView<ImmutableImage>>],
+    sampler: &Arc<Sampler>,
+    uniform_buffer: &Arc<CpuAccessibleBuffer<fs::ty::Data>>,
+) -> Result<Vec<Arc<PersistentDescriptorSet>>> {
+    let layout = pipeline.layout().set_layouts().get(0).unwrap();
+    textures
+        .iter()
+        .map(|texture| {
+            PersistentDescriptorSet::new(
+                allocator,
+                layout.clone(),
+                [
+                    WriteDescriptorSet::image_view_sampler(0, texture.clone(), sampler.clone()),
+                    WriteDescriptorSet::buffer(1, uniform_buffer.clone()),
+                ],
+            )
+            .context("can't create descriptor set")
+        })
+        .collect()
+}
+
+/// Records the uploads of every mesh and texture in `assets`.
+fn upload_assets(
+    memory_allocator: &StandardMemoryAllocator,
+    uploader: &mut Uploader,
+    assets: &Assets,
+) -> Result<(Vec<Mesh>, Vec<Arc<ImageView<ImmutableImage>>>)> {
+    let meshes = assets
+        .meshes
+        .iter()
+        .enumerate()
+        .map(|(i, mesh)| -> Result<_> {
+            let vertex_buffer = uploader.buffer_from_iter(
+                memory_allocator,
+                BufferUsage {
+                    vertex_buffer: true,
+                    ..BufferUsage::empty()
+                },
+                mesh.vertices.iter().copied(),
+            )?;
+            debug::set_buffer_name(&*vertex_buffer, &format!("vertex buffer {}", i));
+
+            let index_buffer = uploader.buffer_from_iter(
+                memory_allocator,
+                BufferUsage {
+                    index_buffer: true,
+                    ..BufferUsage::empty()
+                },
+                mesh.indices.iter().copied(),
+            )?;
+            debug::set_buffer_name(&*index_buffer, &format!("index buffer {}", i));
+
+            Ok(Mesh {
+                vertex_buffer,
+                index_buffer,
+                texture: mesh.texture,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let textures = assets
+        .textures
+        .iter()
+        .enumerate()
+        .map(|(i, texture)| -> Result<_> {
+            let image = ImmutableImage::from_iter(
+                memory_allocator,
+                texture.data.iter().copied(),
+                texture.dimensions,
+                MipmapsCount::One,
+                Format::R8G8B8A8_SRGB,
+                uploader.builder(),
+            )
+            .context("can't create image")?;
+            debug::set_image_name(&*image, &format!("texture {}", i));
+            ImageView::new_default(image).context("can't create image view")
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok((meshes, textures))
+}
+
+/// Moves the camera so the point of the z = 0 plane that was under `from` is under `to`.
+fn pan_camera(
+    editor: &mut SceneEditor,
+    from: [f32; 2],
+    to: [f32; 2],
+    dimensions: [u32; 2],
+    view_projection: cgmath::Matrix4<f32>,
+) {
+    let on_plane = |cursor| {
+        Ray::from_cursor(cursor, dimensions, view_projection)?.intersect_plane(
+            cgmath::Vector3::new(0.0, 0.0, 0.0),
+            cgmath::Vector3::unit_z(),
+        )
+    };
+    if let (Some(from), Some(to)) = (on_plane(from), on_plane(to)) {
+        let translation = &mut editor.camera_mut().transform.translation;
+        for axis in 0..3 {
+            translation[axis] += from[axis] - to[axis];
+        }
+    }
+}
+
+/// Moves `color` a third of the way to white, keeping its alpha.
+fn highlight(color: [f32; 4]) -> [f32; 4] {
+    let [r, g, b, a] = color;
+    let lighten = |c: f32| c + (1.0 - c) / 3.0;
+    [lighten(r), lighten(g), lighten(b), a]
+}
+
+/// The scene goes through the first subpass, which also writes entity IDs when picking is on.
+fn create_render_pass(
+    device: &Arc<Device>,
+    format: Format,
+    picking: bool,
+) -> Result<Arc<RenderPass>> {
+    if !picking {
+        return vulkano::ordered_passes_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: format,
+                    samples: SampleCount::Sample1,
+                }
+            },
+            passes: [
+                { color: [color], depth_stencil: {}, input: [] }, // Draw what you want on this pass
+                { color: [color], depth_stencil: {}, input: [] } // Gui render pass
+            ]
+        )
+        .context("can't create render pass");
+    }
+
+    vulkano::ordered_passes_renderpass!(
+        device.clone(),
+        attachments: {
+            color: {
+                load: Clear,
+                store: Store,
+                format: format,
+                samples: SampleCount::Sample1,
+            },
+            ids: {
+                load: Clear,
+                store: Store,
+                format: Format::R32_UINT,
+                samples: SampleCount::Sample1,
+            }
+        },
+        passes: [
+            { color: [color, ids], depth_stencil: {}, input: [] },
+            { color: [color], depth_stencil: {}, input: [] } // Gui render pass
+        ]
+    )
+    .context("can't create render pass")
+}
+
+/// Alpha blending on the color attachment. Integer attachments such as the entity IDs can't be
+/// blended, they're overwritten.
+fn color_blend_state(subpass: &Subpass) -> ColorBlendState {
+    let mut state = ColorBlendState::new(subpass.num_color_attachments()).blend_alpha();
+    for attachment in state.attachments.iter_mut().skip(1) {
+        attachment.blend = None;
+    }
+    state
+}
+
+fn create_pipeline(
+    device: Arc<Device>,
+    cache: Arc<PipelineCache>,
+    vs: EntryPoint,
+    fs: EntryPoint,
+    subpass: Subpass,
+) -> Result<Arc<GraphicsPipeline>, GraphicsPipelineCreationError> {
+    GraphicsPipeline::start()
+        .vertex_input_state(BuffersDefinition::new().vertex::<Vertex>())
+        .vertex_shader(vs, ())
+        .input_assembly_state(InputAssemblyState::new().topology(PrimitiveTopology::TriangleList))
+        .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+        .fragment_shader(fs, ())
+        .color_blend_state(color_blend_state(&subpass))
+        .render_pass(subpass)
+        .build_with_cache(cache)
+        .build(device)
+}
+
+/// Recompiles the watched GLSL files and builds a pipeline out of them, with `PICKING` defined
+/// when the subpass has the entity ID attachment.
+fn reload_pipeline(
+    watcher: &ShaderWatcher,
+    device: &Arc<Device>,
+    cache: Arc<PipelineCache>,
+    subpass: Subpass,
+    picking: bool,
+) -> Result<Arc<GraphicsPipeline>, ShaderError> {
+    let defines: &[_] = if picking { &[("PICKING", None)] } else { &[] };
+    let modules = watcher.load(device, defines)?;
+    let path = |i: usize| watcher.paths().nth(i).unwrap().to_owned();
+    let entry_point = |i: usize| {
+        modules[i]
+            .entry_point("main")
+            .ok_or_else(|| ShaderError::MissingEntryPoint {
+                path: path(i),
+                name: "main",
+            })
+    };
+    let (vs, fs) = (entry_point(0)?, entry_point(1)?);
+
+    // Catch layout mismatches here, with the file and location in the message, rather than as
+    // a generic pipeline creation error.
+    reflect::check_vertex_input::<Vertex>(&vs)
+        .and_then(|_| reflect::check_descriptors(&vs, &DESCRIPTORS))
+        .map_err(|error| ShaderError::Reflect {
+            path: path(0),
+            error,
+        })?;
+    reflect::check_descriptors(&fs, &DESCRIPTORS).map_err(|error| ShaderError::Reflect {
+        path: path(1),
+        error,
+    })?;
+
+    create_pipeline(device.clone(), cache, vs, fs, subpass).map_err(ShaderError::Pipeline)
+}
+
+/// The framebuffers of `target` and, when `picking`, the entity ID images.
+fn window_size_dependent_setup(
+    target: &Target,
+    render_pass: Arc<RenderPass>,
+    memory_allocator: &StandardMemoryAllocator,
+    picking: bool,
+) -> Result<(Vec<Arc<Framebuffer>>, Vec<Arc<ImageView<AttachmentImage>>>)> {
+    let dimensions = target.dimensions();
+
+    let (mut framebuffers, mut ids) = (Vec::new(), Vec::new());
+    for view in target.views() {
+        let mut attachments = vec![view.clone()];
+        if picking {
+            let image = AttachmentImage::with_usage(
+                memory_allocator,
+                dimensions,
+                Format::R32_UINT,
+                ImageUsage {
+                    color_attachment: true,
+                    transfer_src: true,
+                    ..ImageUsage::empty()
+                },
+            )
+            .context("can't create id image")?;
+            let id_view = ImageView::new_default(image).context("can't create image view")?;
+            attachments.push(id_view.clone());
+            ids.push(id_view);
+        }
+        framebuffers.push(
+            Framebuffer::new(
+                render_pass.clone(),
+                FramebufferCreateInfo {
+                    attachments,
+                    ..Default::default()
+                },
+            )
+            .context("can't create framebuffer")?,
+        );
+    }
+    Ok((framebuffers, ids))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::fs::File;
+    use std::rc::Rc;
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+    use crate::config::AssetPaths;
+
+    /// Draws like [`Egui`], breaking the fragment shader once the first frame loaded it, and
+    /// keeps the pipeline and the shader error of each frame.
+    struct BreakShader {
+        egui: Egui,
+        dir: PathBuf,
+        frames: Rc<RefCell<Vec<(Arc<GraphicsPipeline>, Option<String>)>>>,
+    }
+
+    impl App for BreakShader {
+        type Renderer = Renderer;
+
+        fn create(
+            &mut self,
+            gpu: &Gpu,
+            target: &Target,
+            event_loop: Option<&EventLoopWindowTarget<()>>,
+        ) -> Result<Renderer> {
+            self.egui.create(gpu, target, event_loop)
+        }
+
+        fn resize(&mut self, renderer: &mut Renderer, gpu: &Gpu, target: &Target) -> Result<()> {
+            self.egui.resize(renderer, gpu, target)
+        }
+
+        fn draw(
+            &mut self,
+            renderer: &mut Renderer,
+            gpu: &Gpu,
+            target: &Target,
+            frame: &mut Frame,
+        ) -> Result<()> {
+            self.egui.draw(renderer, gpu, target, frame)?;
+            let mut frames = self.frames.borrow_mut();
+            frames.push((renderer.pipeline.clone(), self.egui.shader_error.clone()));
+
+            if frames.len() == 1 {
+                let path = self.dir.join("egui.frag");
+                let source = std::fs::read_to_string(&path).unwrap();
+                std::fs::write(&path, source.replace("void main() {", "void main() { oops"))
+                    .unwrap();
+                // Later than the first load, even with coarse timestamps.
+                File::options()
+                    .write(true)
+                    .open(&path)
+                    .unwrap()
+                    .set_modified(SystemTime::now() + Duration::from_secs(10))
+                    .unwrap();
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn broken_shaders_keep_the_last_pipeline() {
+        let dir = std::env::temp_dir().join("vulkan_rust-egui-shaders");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for entry in std::fs::read_dir("shaders").unwrap() {
+            let path = entry.unwrap().path();
+            std::fs::copy(&path, dir.join(path.file_name().unwrap())).unwrap();
+        }
+
+        let options = Options {
+            headless: true,
+            frames: Some(3),
+            config: RendererConfig {
+                assets: AssetPaths {
+                    shader_dir: Some(dir.clone()),
+                    ..AssetPaths::default()
+                },
+                ..RendererConfig::default()
+            },
+            ..Default::default()
+        };
+        let frames = Rc::new(RefCell::new(Vec::new()));
+        let app = BreakShader {
+            egui: Egui::new(&options).unwrap(),
+            dir,
+            frames: frames.clone(),
+        };
+        runner::run(options, app).unwrap();
+
+        let frames = frames.borrow();
+        assert_eq!(frames.len(), 3);
+        let (loaded, error) = &frames[0];
+        assert_eq!(error, &None);
+        for (pipeline, error) in &frames[1..] {
+            assert!(Arc::ptr_eq(pipeline, loaded));
+            let error = error.as_deref().unwrap_or_default();
+            assert!(error.contains("egui.frag"), "{}", error);
+        }
+    }
+}
@@ -0,0 +1,494 @@
+use std::fs::File;
+use std::io::BufWriter;
+
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferToImageInfo, CopyImageToBufferInfo,
+};
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{Device, DeviceCreateInfo, DeviceExtensions, Features, QueueCreateInfo};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageDimensions, StorageImage};
+use vulkano::instance::{Instance, InstanceCreateInfo};
+use vulkano::memory::allocator::StandardMemoryAllocator;
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sync::GpuFuture;
+use vulkano::{sync, VulkanLibrary};
+
+use crate::cli::Options;
+use crate::debug::{self, Validation};
+use crate::error::{Context, Error, Result};
+use crate::gpu;
+use crate::pipeline_cache::PersistentPipelineCache;
+use crate::setup;
+
+// Must match `local_size_x` and `local_size_y` in the shaders.
+const WORKGROUP_SIZE: u32 = 16;
+const HISTOGRAM_BINS: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kernel {
+    Grayscale,
+    Blur,
+    Edges,
+    Histogram,
+}
+
+pub fn run(options: Options) -> Result<()> {
+    let mut args = options.args.iter().cloned();
+    let input_path = args.next().unwrap_or_else(|| "src/image.png".to_owned());
+    let output_path = args.next().unwrap_or_else(|| "output.png".to_owned());
+    // `--output` wins over the positional path, which still has to be given to name kernels.
+    let output_path = options
+        .output
+        .as_ref()
+        .map_or(output_path, |path| path.display().to_string());
+    let kernels = args
+        .next()
+        .unwrap_or_else(|| "histogram,grayscale,blur,edges".to_owned())
+        .split(',')
+        .map(|name| match name {
+            "grayscale" => Ok(Kernel::Grayscale),
+            "blur" => Ok(Kernel::Blur),
+            "edges" => Ok(Kernel::Edges),
+            "histogram" => Ok(Kernel::Histogram),
+            other => Err(Error::Content(format!(
+                "unknown kernel {other:?}, expected one of grayscale, blur, edges, histogram"
+            ))),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let (width, height, image_data) = load_png(&input_path)?;
+    println!("loaded {input_path}: {width}x{height}");
+
+    let library = VulkanLibrary::new()?;
+
+    // No surface, so no windowing extensions either.
+    let create_info = debug::instance_create_info(
+        &library,
+        InstanceCreateInfo {
+            enumerate_portability: true,
+            ..Default::default()
+        },
+    );
+    let instance = Instance::new(library, create_info)?;
+    if options.list_gpus {
+        return gpu::list(&instance);
+    }
+    let validation = Validation::new(&instance);
+
+    // Nothing is drawn, so a compute-only family is preferred, it's usually the hardware meant
+    // for async compute.
+    let (physical_device, queue_family_index) = gpu::select(
+        &instance,
+        options.gpu.as_ref(),
+        &DeviceExtensions::empty(),
+        &Features::empty(),
+        |p| {
+            setup::dedicated_compute_family(p).or_else(|| {
+                p.queue_family_properties()
+                    .iter()
+                    .position(|q| q.queue_flags.compute)
+                    .map(|i| i as u32)
+            })
+        },
+    )?;
+    log::info!(
+        "physical device: {}",
+        physical_device.properties().device_name
+    );
+
+    let (device, mut queues) = Device::new(
+        physical_device,
+        DeviceCreateInfo {
+            queue_create_infos: vec![QueueCreateInfo {
+                queue_family_index,
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+    )?;
+    let queue = queues
+        .next()
+        .ok_or(Error::Unsupported("the device has no queue"))?;
+
+    let pipeline_cache = PersistentPipelineCache::load(&device)?;
+
+    let memory_allocator = StandardMemoryAllocator::new_default(device.clone());
+    let descriptor_set_allocator = StandardDescriptorSetAllocator::new(device.clone());
+    let command_buffer_allocator =
+        StandardCommandBufferAllocator::new(device.clone(), Default::default());
+
+    mod grayscale_cs {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            src: "
+			#version 450
+
+            layout(local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
+
+            layout(set = 0, binding = 0, rgba8) uniform readonly image2D src_image;
+            layout(set = 0, binding = 1, rgba8) uniform writeonly image2D dst_image;
+
+            void main() {
+                ivec2 p = ivec2(gl_GlobalInvocationID.xy);
+                if (any(greaterThanEqual(p, imageSize(src_image)))) {
+                    return;
+                }
+
+                vec4 color = imageLoad(src_image, p);
+                float luminance = dot(color.rgb, vec3(0.2126, 0.7152, 0.0722));
+                imageStore(dst_image, p, vec4(vec3(luminance), color.a));
+            }"
+        }
+    }
+
+    mod blur_cs {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            src: "
+			#version 450
+
+            layout(local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
+
+            layout(set = 0, binding = 0, rgba8) uniform readonly image2D src_image;
+            layout(set = 0, binding = 1, rgba8) uniform writeonly image2D dst_image;
+
+            // 5x5 gaussian, the outer product of [1 4 6 4 1] / 16 with itself.
+            const float weights[5] = float[](1.0, 4.0, 6.0, 4.0, 1.0);
+
+            void main() {
+                ivec2 p = ivec2(gl_GlobalInvocationID.xy);
+                ivec2 size = imageSize(src_image);
+                if (any(greaterThanEqual(p, size))) {
+                    return;
+                }
+
+                vec4 color = vec4(0.0);
+                for (int y = -2; y <= 2; y++) {
+                    for (int x = -2; x <= 2; x++) {
+                        ivec2 q = clamp(p + ivec2(x, y), ivec2(0), size - 1);
+                        color += imageLoad(src_image, q) * weights[x + 2] * weights[y + 2];
+                    }
+                }
+                imageStore(dst_image, p, color / 256.0);
+            }"
+        }
+    }
+
+    mod edges_cs {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            src: "
+			#version 450
+
+            layout(local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
+
+            layout(set = 0, binding = 0, rgba8) uniform readonly image2D src_image;
+            layout(set = 0, binding = 1, rgba8) uniform writeonly image2D dst_image;
+
+            float luminance(ivec2 p, ivec2 size) {
+                vec3 color = imageLoad(src_image, clamp(p, ivec2(0), size - 1)).rgb;
+                return dot(color, vec3(0.2126, 0.7152, 0.0722));
+            }
+
+            // Sobel operator on the luminance.
+            void main() {
+                ivec2 p = ivec2(gl_GlobalInvocationID.xy);
+                ivec2 size = imageSize(src_image);
+                if (any(greaterThanEqual(p, size))) {
+                    return;
+                }
+
+                float tl = luminance(p + ivec2(-1, -1), size);
+                float t = luminance(p + ivec2(0, -1), size);
+                float tr = luminance(p + ivec2(1, -1), size);
+                float l = luminance(p + ivec2(-1, 0), size);
+                float r = luminance(p + ivec2(1, 0), size);
+                float bl = luminance(p + ivec2(-1, 1), size);
+                float b = luminance(p + ivec2(0, 1), size);
+                float br = luminance(p + ivec2(1, 1), size);
+
+                float gx = (tr + 2.0 * r + br) - (tl + 2.0 * l + bl);
+                float gy = (bl + 2.0 * b + br) - (tl + 2.0 * t + tr);
+                float magnitude = clamp(length(vec2(gx, gy)), 0.0, 1.0);
+
+                imageStore(dst_image, p, vec4(vec3(magnitude), imageLoad(src_image, p).a));
+            }"
+        }
+    }
+
+    // The bins are read back as plain `u32`s.
+    #[allow(dead_code)]
+    mod histogram_cs {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            src: "
+			#version 450
+
+            layout(local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
+
+            layout(set = 0, binding = 0, rgba8) uniform readonly image2D src_image;
+            layout(set = 0, binding = 1) buffer Histogram {
+                uint bins[256];
+            };
+
+            void main() {
+                ivec2 p = ivec2(gl_GlobalInvocationID.xy);
+                if (any(greaterThanEqual(p, imageSize(src_image)))) {
+                    return;
+                }
+
+                vec3 color = imageLoad(src_image, p).rgb;
+                float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+                uint bin = uint(clamp(luminance, 0.0, 1.0) * 255.0 + 0.5);
+                atomicAdd(bins[bin], 1u);
+            }"
+        }
+    }
+
+    let grayscale_cs = grayscale_cs::load(device.clone()).context("can't load compute shader")?;
+    let blur_cs = blur_cs::load(device.clone()).context("can't load compute shader")?;
+    let edges_cs = edges_cs::load(device.clone()).context("can't load compute shader")?;
+    let histogram_cs = histogram_cs::load(device.clone()).context("can't load compute shader")?;
+
+    let compute_pipeline = |entry_point, name| {
+        let pipeline = ComputePipeline::new(
+            device.clone(),
+            entry_point,
+            &(),
+            Some(pipeline_cache.cache()),
+            |_| {},
+        )
+        .context("can't create compute pipeline")?;
+        debug::set_name(&*pipeline, name);
+        Ok::<_, Error>(pipeline)
+    };
+    let grayscale_pipeline = compute_pipeline(
+        setup::entry_point(&grayscale_cs, "main")?,
+        "grayscale pipeline",
+    )?;
+    let blur_pipeline = compute_pipeline(setup::entry_point(&blur_cs, "main")?, "blur pipeline")?;
+    let edges_pipeline =
+        compute_pipeline(setup::entry_point(&edges_cs, "main")?, "edges pipeline")?;
+    let histogram_pipeline = compute_pipeline(
+        setup::entry_point(&histogram_cs, "main")?,
+        "histogram pipeline",
+    )?;
+
+    // sRGB formats usually can't be used as storage images, so the bytes are processed as is.
+    let storage_image = |name| {
+        let image = StorageImage::new(
+            &memory_allocator,
+            ImageDimensions::Dim2d {
+                width,
+                height,
+                array_layers: 1,
+            },
+            Format::R8G8B8A8_UNORM,
+            [queue_family_index],
+        )
+        .context("can't create storage image")?;
+        debug::set_image_name(&*image, name);
+        ImageView::new_default(image).context("can't create image view")
+    };
+    // Every filter reads one image and writes the other, then they swap.
+    let mut src_image = storage_image("ping image")?;
+    let mut dst_image = storage_image("pong image")?;
+
+    let input_buffer = CpuAccessibleBuffer::from_iter(
+        &memory_allocator,
+        BufferUsage {
+            transfer_src: true,
+            ..BufferUsage::empty()
+        },
+        false,
+        image_data,
+    )
+    .context("can't create input buffer")?;
+    debug::set_buffer_name(&*input_buffer, "input buffer");
+
+    let output_buffer = CpuAccessibleBuffer::from_iter(
+        &memory_allocator,
+        BufferUsage {
+            transfer_dst: true,
+            ..BufferUsage::empty()
+        },
+        false,
+        (0..width * height * 4).map(|_| 0u8),
+    )
+    .context("can't create output buffer")?;
+    debug::set_buffer_name(&*output_buffer, "output buffer");
+
+    let mut builder = AutoCommandBufferBuilder::primary(
+        &command_buffer_allocator,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+    .context("can't create command buffer builder")?;
+
+    builder
+        .copy_buffer_to_image(CopyBufferToImageInfo::buffer_image(
+            input_buffer,
+            src_image.image().clone(),
+        ))
+        .context("can't upload image")?;
+
+    let group_count = [
+        (width + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE,
+        (height + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE,
+        1,
+    ];
+
+    let mut histograms = Vec::new();
+    for (step, kernel) in kernels.iter().enumerate() {
+        let (pipeline, output) = match kernel {
+            Kernel::Grayscale => (&grayscale_pipeline, None),
+            Kernel::Blur => (&blur_pipeline, None),
+            Kernel::Edges => (&edges_pipeline, None),
+            Kernel::Histogram => {
+                let histogram = CpuAccessibleBuffer::from_iter(
+                    &memory_allocator,
+                    BufferUsage {
+                        storage_buffer: true,
+                        ..BufferUsage::empty()
+                    },
+                    false,
+                    [0u32; HISTOGRAM_BINS],
+                )
+                .context("can't create histogram buffer")?;
+                debug::set_buffer_name(&*histogram, "histogram buffer");
+                histograms.push((step, histogram.clone()));
+                (&histogram_pipeline, Some(histogram))
+            }
+        };
+
+        let second_binding = match output {
+            Some(histogram) => WriteDescriptorSet::buffer(1, histogram),
+            None => WriteDescriptorSet::image_view(1, dst_image.clone()),
+        };
+        let set = PersistentDescriptorSet::new(
+            &descriptor_set_allocator,
+            pipeline.layout().set_layouts()[0].clone(),
+            [
+                WriteDescriptorSet::image_view(0, src_image.clone()),
+                second_binding,
+            ],
+        )
+        .context("can't create descriptor set")?;
+
+        debug::label(
+            &mut builder,
+            &format!("{:?}", kernel),
+            |builder| -> Result<()> {
+                builder
+                    .bind_pipeline_compute(pipeline.clone())
+                    .bind_descriptor_sets(
+                        PipelineBindPoint::Compute,
+                        pipeline.layout().clone(),
+                        0,
+                        set,
+                    )
+                    .dispatch(group_count)
+                    .context("can't dispatch kernel")?;
+                Ok(())
+            },
+        )?;
+
+        if *kernel != Kernel::Histogram {
+            std::mem::swap(&mut src_image, &mut dst_image);
+        }
+    }
+
+    builder
+        .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
+            src_image.image().clone(),
+            output_buffer.clone(),
+        ))
+        .context("can't download image")?;
+
+    let command_buffer = builder.build().context("can't build command buffer")?;
+
+    sync::now(device.clone())
+        .then_execute(queue.clone(), command_buffer)
+        .context("can't execute command buffer")?
+        .then_signal_fence_and_flush()
+        .context("can't flush future")?
+        .wait(None)
+        .context("can't wait for future")?;
+
+    pipeline_cache.save();
+    validation.check()?;
+
+    for (step, histogram) in histograms {
+        let bins = histogram.read().context("can't read histogram")?;
+        println!("luminance histogram before step {step}:");
+        print_histogram(&bins);
+    }
+
+    let output = output_buffer.read().context("can't read output buffer")?;
+    save_png(&output_path, width, height, &output)?;
+    println!("wrote {output_path}");
+    Ok(())
+}
+
+/// Decodes a PNG of any color type and bit depth into tightly packed 8-bit RGBA.
+fn load_png(path: &str) -> Result<(u32, u32, Vec<u8>)> {
+    let file = File::open(path).context("can't open input image")?;
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().context("can't read png info")?;
+
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).context("can't decode png")?;
+    let pixels = &buffer[..info.buffer_size()];
+
+    let rgba = match info.color_type {
+        png::ColorType::Rgba => pixels.to_vec(),
+        png::ColorType::Rgb => pixels
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => pixels
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => pixels.iter().flat_map(|&p| [p, p, p, 255]).collect(),
+        png::ColorType::Indexed => unreachable!("palette is expanded by the decoder"),
+    };
+
+    Ok((info.width, info.height, rgba))
+}
+
+fn save_png(path: &str, width: u32, height: u32, data: &[u8]) -> Result<()> {
+    let file = File::create(path).context("can't create output image")?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().context("can't write png header")?;
+    writer
+        .write_image_data(data)
+        .context("can't write png data")?;
+    Ok(())
+}
+
+/// Prints the histogram folded into 16 buckets as horizontal bars.
+fn print_histogram(bins: &[u32]) {
+    let buckets = bins
+        .chunks(HISTOGRAM_BINS / 16)
+        .map(|chunk| chunk.iter().sum::<u32>())
+        .collect::<Vec<_>>();
+    let max = buckets.iter().copied().max().unwrap_or(0).max(1);
+
+    for (i, count) in buckets.iter().enumerate() {
+        let start = i * HISTOGRAM_BINS / 16;
+        let bar = "#".repeat((*count as u64 * 50 / max as u64) as usize);
+        println!(
+            "{start:>4}..{:<4} {count:>8} {bar}",
+            start + HISTOGRAM_BINS / 16
+        );
+    }
+}
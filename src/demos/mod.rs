@@ -0,0 +1,143 @@
+//! The demos, each a `run` function taking the shared [`Options`].
+//!
+//! The windowed demos draw through [`crate::runner`], which owns the window and event loop, so
+//! their `run` only returns when setting up failed.
+
+pub mod deferred;
+pub mod egui;
+pub mod image;
+pub mod image_compute;
+pub mod mvp;
+pub mod particles;
+pub mod projection;
+pub mod rectangle;
+pub mod rotating_rectangle;
+pub mod triangle;
+
+use crate::cli::Options;
+use crate::error::{Error, Result};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Demo {
+    Triangle,
+    Rectangle,
+    RotatingRectangle,
+    Image,
+    Projection,
+    Mvp,
+    Egui,
+    Deferred,
+    Particles,
+    ImageCompute,
+}
+
+impl Demo {
+    pub const ALL: [Demo; 10] = [
+        Demo::Triangle,
+        Demo::Rectangle,
+        Demo::RotatingRectangle,
+        Demo::Image,
+        Demo::Projection,
+        Demo::Mvp,
+        Demo::Egui,
+        Demo::Deferred,
+        Demo::Particles,
+        Demo::ImageCompute,
+    ];
+
+    /// The name of the demo's subcommand and binary.
+    pub fn name(self) -> &'static str {
+        match self {
+            Demo::Triangle => "triangle",
+            Demo::Rectangle => "rectangle",
+            Demo::RotatingRectangle => "rotating-rectangle",
+            Demo::Image => "image",
+            Demo::Projection => "projection",
+            Demo::Mvp => "mvp",
+            Demo::Egui => "egui",
+            Demo::Deferred => "deferred",
+            Demo::Particles => "particles",
+            Demo::ImageCompute => "image-compute",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|demo| demo.name() == name)
+    }
+
+    /// Whether the demo draws with `--msaa`.
+    pub fn supports_msaa(self) -> bool {
+        matches!(self, Demo::Triangle)
+    }
+
+    /// Runs the demo, and exits with a failure status when it can't.
+    pub fn run(self, options: Options) {
+        if let Err(e) = self.try_run(options) {
+            log::error!("{}", e);
+            std::process::exit(1);
+        }
+    }
+
+    pub fn try_run(self, options: Options) -> Result<()> {
+        // A sample count from the config file is for the demos that support it, the others warn
+        // and draw without it. Only `--msaa` itself is refused.
+        let from_config = (options.config.msaa > 1).then_some(options.config.msaa);
+        if options.msaa.is_some() && !self.supports_msaa() {
+            if options.msaa != from_config {
+                return Err(Error::Unsupported("this demo doesn't support --msaa"));
+            }
+            log::warn!(
+                "{} doesn't support multisampling, ignoring msaa = {} from the config",
+                self.name(),
+                options.config.msaa
+            );
+        }
+
+        match self {
+            Demo::Triangle => triangle::run(options),
+            Demo::Rectangle => rectangle::run(options),
+            Demo::RotatingRectangle => rotating_rectangle::run(options),
+            Demo::Image => image::run(options),
+            Demo::Projection => projection::run(options),
+            Demo::Mvp => mvp::run(options),
+            Demo::Egui => egui::run(options),
+            Demo::Deferred => deferred::run(options),
+            Demo::Particles => particles::run(options),
+            Demo::ImageCompute => image_compute::run(options),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::debug;
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn every_demo_runs_headless_under_validation() {
+        debug::request_validation();
+
+        for demo in Demo::ALL {
+            let args = match demo {
+                Demo::ImageCompute => {
+                    let output = std::env::temp_dir().join("vulkan_rust-image-compute.png");
+                    vec![
+                        "src/image.png".to_owned(),
+                        output.to_string_lossy().into_owned(),
+                    ]
+                }
+                _ => Vec::new(),
+            };
+            let options = Options {
+                headless: true,
+                frames: Some(3),
+                args,
+                ..Default::default()
+            };
+            if let Err(e) = demo.try_run(options) {
+                panic!("{} failed: {}", demo.name(), e);
+            }
+        }
+    }
+}
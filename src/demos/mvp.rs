@@ -0,0 +1,487 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use bytemuck::{Pod, Zeroable};
+use cgmath::Matrix;
+use vulkano::buffer::{BufferUsage, DeviceLocalBuffer, TypedBufferAccess};
+use vulkano::command_buffer::{RenderPassBeginInfo, SubpassContents};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageDimensions, ImmutableImage, MipmapsCount};
+use vulkano::impl_vertex;
+use vulkano::memory::allocator::StandardMemoryAllocator;
+use vulkano::pipeline::graphics::color_blend::ColorBlendState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::vertex_input::BuffersDefinition;
+use vulkano::pipeline::graphics::viewport::ViewportState;
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo};
+use winit::event_loop::EventLoopWindowTarget;
+
+use crate::cli::Options;
+use crate::config;
+use crate::debug;
+use crate::error::{Context, Error, Result};
+use crate::runner::{self, App, Frame, Gpu, Target};
+use crate::scene::{Scene, SceneFile, MAX_LIGHTS};
+use crate::scene_graph::SceneGraph;
+use crate::setup;
+use crate::upload::Uploader;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
+struct Vertex {
+    position: [f32; 3],
+    tex_coords: [f32; 2],
+}
+
+impl_vertex!(Vertex, position, tex_coords);
+
+/// What an entity of the scene is drawn with, where it's drawn is in the scene graph.
+struct Draw {
+    vertex_buffer: Arc<DeviceLocalBuffer<[Vertex]>>,
+    index_buffer: Arc<DeviceLocalBuffer<[u16]>>,
+    set: Arc<PersistentDescriptorSet>,
+    color: [f32; 4],
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
+			#version 450
+
+            layout(location = 0) in vec3 position;
+            layout(location = 1) in vec2 tex_coords;
+
+            layout(location = 0) out vec2 v_tex_coords;
+            layout(location = 1) out vec4 v_color;
+            layout(location = 2) out vec3 v_position;
+
+            layout (push_constant) uniform PushConstants {
+                mat4 mvp;
+                vec4 color;
+                // The first three rows of the model matrix, so all of it fits in 128 bytes.
+                mat3x4 model_rows;
+            } push;
+
+            void main() {
+                gl_Position = push.mvp * vec4(position, 1.0);
+                v_tex_coords = tex_coords;
+                v_color = push.color;
+                v_position = vec4(position, 1.0) * push.model_rows;
+            }",
+        types_meta: {
+            use bytemuck::{Pod, Zeroable};
+
+            #[derive(Clone, Copy, Zeroable, Pod)]
+        },
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        include: ["shaders"],
+        src: "
+			#version 450
+
+            layout(location = 0) in vec2 tex_coords;
+            layout(location = 1) in vec4 color;
+            layout(location = 2) in vec3 position;
+
+            layout(location = 0) out vec4 f_color;
+            layout(set = 0, binding = 0) uniform sampler2D tex;
+
+            // The encoding of the target, see `OutputEncoding`.
+            layout(constant_id = 0) const uint output_encoding = 0;
+
+            #include <encoding.glsl>
+            #include <lighting.glsl>
+
+            layout(set = 0, binding = 1) uniform Lights {
+                Light lights[MAX_LIGHTS];
+                uint count;
+            } lights;
+
+            void main() {
+                vec4 base = texture(tex, tex_coords) * color;
+                vec4 lit = shade(base, position, lights.lights, lights.count);
+                f_color = encode_output(lit, output_encoding);
+            }",
+        types_meta: {
+            use bytemuck::{Pod, Zeroable};
+
+            #[derive(Clone, Copy, Zeroable, Pod)]
+        },
+    }
+}
+
+pub fn run(options: Options) -> Result<()> {
+    // A scene given on the command line is reloaded whenever it changes, the built-in one is
+    // drawn otherwise.
+    let mut scene_file = options
+        .args
+        .first()
+        .map(|path| SceneFile::new(PathBuf::from(path)));
+    let scene = match scene_file.as_mut().and_then(SceneFile::poll) {
+        Some(loaded) => loaded.context("can't load scene")?,
+        None => Scene::builtin(config::Format::Toml, include_str!("../../scenes/mvp.toml"))
+            .expect("can't load built-in scene"),
+    };
+    // Each node carries the index of its entity, which is also the index of its draw.
+    let graph = SceneGraph::from_scene(&scene);
+
+    let mvp = Mvp {
+        clear_color: options.config.clear_color,
+        scene_file,
+        scene,
+        graph,
+    };
+    runner::run(options, mvp)
+}
+
+struct Mvp {
+    clear_color: [f32; 4],
+    scene_file: Option<SceneFile>,
+    scene: Scene,
+    graph: SceneGraph<usize>,
+}
+
+struct Renderer {
+    render_pass: Arc<RenderPass>,
+    pipeline: Arc<GraphicsPipeline>,
+    sampler: Arc<Sampler>,
+    draws: Vec<Draw>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+}
+
+impl Mvp {
+    /// Draws the scene file from now on if it changed, or keeps drawing the last scene that
+    /// loaded when the new one can't be.
+    fn reload(&mut self, renderer: &mut Renderer, gpu: &Gpu) {
+        let Some(reloaded) = self.scene_file.as_mut().and_then(SceneFile::poll) else {
+            return;
+        };
+
+        let uploaded = reloaded.context("can't read scene").and_then(|scene| {
+            let mut uploader = gpu.uploader()?;
+            let draws = upload_scene(
+                &scene,
+                gpu,
+                &mut uploader,
+                &renderer.pipeline,
+                &renderer.sampler,
+            )?;
+            uploader.submit_and_wait()?;
+            Ok((scene, draws))
+        });
+        match uploaded {
+            Ok((scene, draws)) => {
+                self.graph = SceneGraph::from_scene(&scene);
+                self.scene = scene;
+                renderer.draws = draws;
+                log::info!("reloaded scene");
+            }
+            Err(e) => log::error!("can't reload scene: {}", e),
+        }
+    }
+}
+
+impl App for Mvp {
+    type Renderer = Renderer;
+
+    fn create(
+        &mut self,
+        gpu: &Gpu,
+        target: &Target,
+        _event_loop: Option<&EventLoopWindowTarget<()>>,
+    ) -> Result<Renderer> {
+        let device = &gpu.device;
+
+        let vs = vs::load(device.clone()).context("can't load vertex shader")?;
+        let fs = fs::load(device.clone()).context("can't load fragment shader")?;
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: target.format(),
+                    samples: 1,
+                }
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {}
+            }
+        )
+        .context("can't create render pass")?;
+        debug::set_name(&*render_pass, "render pass");
+
+        let subpass = Subpass::from(render_pass.clone(), 0).expect("can't create subpass");
+        let pipeline = GraphicsPipeline::start()
+            .vertex_input_state(BuffersDefinition::new().vertex::<Vertex>())
+            .vertex_shader(setup::entry_point(&vs, "main")?, ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(
+                setup::entry_point(&fs, "main")?,
+                fs::SpecializationConstants {
+                    output_encoding: target.encoding() as u32,
+                },
+            )
+            .color_blend_state(ColorBlendState::new(subpass.num_color_attachments()).blend_alpha())
+            .render_pass(subpass)
+            .build_with_cache(gpu.pipeline_cache.cache())
+            .build(device.clone())
+            .context("can't create graphics pipeline")?;
+        debug::set_name(&*pipeline, "pipeline");
+
+        let sampler = Sampler::new(
+            device.clone(),
+            SamplerCreateInfo {
+                mag_filter: Filter::Linear,
+                min_filter: Filter::Linear,
+                address_mode: [SamplerAddressMode::Repeat; 3],
+                ..Default::default()
+            },
+        )
+        .context("can't create sampler")?;
+
+        let mut uploader = gpu.uploader()?;
+        let draws = upload_scene(&self.scene, gpu, &mut uploader, &pipeline, &sampler)?;
+        uploader.submit_and_wait()?;
+
+        let framebuffers = window_size_dependent_setup(target, render_pass.clone())?;
+
+        Ok(Renderer {
+            render_pass,
+            pipeline,
+            sampler,
+            draws,
+            framebuffers,
+        })
+    }
+
+    fn resize(&mut self, renderer: &mut Renderer, _gpu: &Gpu, target: &Target) -> Result<()> {
+        renderer.framebuffers = window_size_dependent_setup(target, renderer.render_pass.clone())?;
+        Ok(())
+    }
+
+    fn draw(
+        &mut self,
+        renderer: &mut Renderer,
+        gpu: &Gpu,
+        target: &Target,
+        frame: &mut Frame,
+    ) -> Result<()> {
+        self.reload(renderer, gpu);
+
+        let view_projection =
+            self.scene.camera.projection(target.dimensions()) * self.scene.camera.view();
+        self.graph.update();
+
+        let pipeline = &renderer.pipeline;
+        debug::label(&mut frame.builder, "scene pass", |builder| {
+            builder
+                .begin_render_pass(
+                    RenderPassBeginInfo {
+                        clear_values: vec![Some(target.encoding().encode(self.clear_color).into())],
+                        ..RenderPassBeginInfo::framebuffer(
+                            renderer.framebuffers[frame.image_index].clone(),
+                        )
+                    },
+                    SubpassContents::Inline,
+                )
+                .context("can't begin render pass")?
+                .set_viewport(0, [target.viewport()])
+                .bind_pipeline_graphics(pipeline.clone());
+
+            for item in self.graph.draw_items() {
+                let draw = &renderer.draws[*item.item];
+                let rows = item.world.transpose();
+                let push_constants = vs::ty::PushConstants {
+                    mvp: (view_projection * item.world).into(),
+                    color: draw.color,
+                    model_rows: [rows.x.into(), rows.y.into(), rows.z.into()],
+                };
+                builder
+                    .push_constants(pipeline.layout().clone(), 0, push_constants)
+                    .bind_descriptor_sets(
+                        PipelineBindPoint::Graphics,
+                        pipeline.layout().clone(),
+                        0,
+                        draw.set.clone(),
+                    )
+                    .bind_vertex_buffers(0, draw.vertex_buffer.clone())
+                    .bind_index_buffer(draw.index_buffer.clone())
+                    .draw_indexed(draw.index_buffer.len() as u32, 1, 0, 0, 0)
+                    .context("can't draw")?;
+            }
+
+            builder.end_render_pass().context("can't end render pass")?;
+            Ok(())
+        })
+    }
+}
+
+fn window_size_dependent_setup(
+    target: &Target,
+    render_pass: Arc<RenderPass>,
+) -> Result<Vec<Arc<Framebuffer>>> {
+    target
+        .views()
+        .iter()
+        .map(|view| {
+            Framebuffer::new(
+                render_pass.clone(),
+                FramebufferCreateInfo {
+                    attachments: vec![view.clone()],
+                    ..Default::default()
+                },
+            )
+            .context("can't create framebuffer")
+        })
+        .collect()
+}
+
+/// Creates the buffers, textures and descriptor sets to draw every entity of `scene` with.
+/// Materials that share a texture share its image, and every entity shares the lights. Fails
+/// when a texture can't be read.
+fn upload_scene(
+    scene: &Scene,
+    gpu: &Gpu,
+    uploader: &mut Uploader,
+    pipeline: &GraphicsPipeline,
+    sampler: &Arc<Sampler>,
+) -> Result<Vec<Draw>> {
+    let memory_allocator = &*gpu.memory_allocator;
+    let layout = &pipeline.layout().set_layouts()[0];
+    let mut textures: HashMap<Option<PathBuf>, Arc<ImageView<ImmutableImage>>> = HashMap::new();
+
+    let lights = uploader.buffer_from_iter(
+        memory_allocator,
+        BufferUsage {
+            uniform_buffer: true,
+            ..BufferUsage::empty()
+        },
+        [fs::ty::Lights {
+            lights: std::array::from_fn(|i| {
+                scene.lights.get(i).map_or_else(Zeroable::zeroed, |light| {
+                    let (position, color) = light.shader_data();
+                    fs::ty::Light { position, color }
+                })
+            }),
+            count: scene.lights.len().min(MAX_LIGHTS) as u32,
+        }],
+    )?;
+    debug::set_buffer_name(&*lights, "lights");
+
+    scene
+        .entities
+        .iter()
+        .map(|entity| {
+            let material = scene.material(entity);
+            let texture_path = material.texture.as_deref().map(|path| scene.resolve(path));
+            let texture = match textures.get(&texture_path) {
+                Some(texture) => texture.clone(),
+                None => {
+                    let png_bytes = match &texture_path {
+                        Some(path) => std::fs::read(path).map_err(|e| {
+                            Error::Content(format!("can't read {}: {}", path.display(), e))
+                        })?,
+                        None => include_bytes!("../image.png").to_vec(),
+                    };
+                    let texture = load_texture(memory_allocator, uploader, &png_bytes)?;
+                    textures.insert(texture_path, texture.clone());
+                    texture
+                }
+            };
+
+            let mesh = entity.mesh.data();
+            let vertex_buffer = uploader.buffer_from_iter(
+                memory_allocator,
+                BufferUsage {
+                    vertex_buffer: true,
+                    ..BufferUsage::empty()
+                },
+                mesh.positions
+                    .iter()
+                    .zip(&mesh.tex_coords)
+                    .map(|(&position, &tex_coords)| Vertex {
+                        position,
+                        tex_coords,
+                    })
+                    .collect::<Vec<_>>(),
+            )?;
+            debug::set_buffer_name(&*vertex_buffer, &format!("{} vertex buffer", entity.name));
+
+            let index_buffer = uploader.buffer_from_iter(
+                memory_allocator,
+                BufferUsage {
+                    index_buffer: true,
+                    ..BufferUsage::empty()
+                },
+                mesh.indices,
+            )?;
+            debug::set_buffer_name(&*index_buffer, &format!("{} index buffer", entity.name));
+
+            let set = PersistentDescriptorSet::new(
+                &gpu.descriptor_set_allocator,
+                layout.clone(),
+                [
+                    WriteDescriptorSet::image_view_sampler(0, texture, sampler.clone()),
+                    WriteDescriptorSet::buffer(1, lights.clone()),
+                ],
+            )
+            .context("can't create descriptor set")?;
+
+            Ok(Draw {
+                vertex_buffer,
+                index_buffer,
+                set,
+                color: material.color,
+            })
+        })
+        .collect()
+}
+
+fn load_texture(
+    memory_allocator: &StandardMemoryAllocator,
+    uploader: &mut Uploader,
+    png_bytes: &[u8],
+) -> Result<Arc<ImageView<ImmutableImage>>> {
+    let decoder = png::Decoder::new(Cursor::new(png_bytes));
+    let mut reader = decoder
+        .read_info()
+        .map_err(|e| Error::Content(format!("can't read png info: {}", e)))?;
+    let info = reader.info();
+    let dimensions = ImageDimensions::Dim2d {
+        width: info.width,
+        height: info.height,
+        array_layers: 1,
+    };
+    let mut image_data = Vec::new();
+    image_data.resize((info.width * info.height * 4) as usize, 0);
+    reader
+        .next_frame(&mut image_data)
+        .map_err(|e| Error::Content(format!("can't decode png: {}", e)))?;
+
+    let image = ImmutableImage::from_iter(
+        memory_allocator,
+        image_data,
+        dimensions,
+        MipmapsCount::One,
+        Format::R8G8B8A8_SRGB,
+        uploader.builder(),
+    )
+    .context("can't create image")?;
+    debug::set_image_name(&*image, "texture");
+    ImageView::new_default(image).context("can't create image view")
+}
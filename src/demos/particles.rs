@@ -0,0 +1,744 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use bytemuck::{Pod, Zeroable};
+use cgmath::{Matrix4, Point3, Rad, Vector3};
+use egui::Slider;
+use egui_winit_vulkano::{Gui, GuiConfig};
+use vulkano::buffer::{BufferUsage, CpuBufferPool, DeviceLocalBuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, RenderPassBeginInfo, SubpassContents,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::image::SampleCount;
+use vulkano::impl_vertex;
+use vulkano::pipeline::graphics::color_blend::ColorBlendState;
+use vulkano::pipeline::graphics::input_assembly::{InputAssemblyState, PrimitiveTopology};
+use vulkano::pipeline::graphics::vertex_input::BuffersDefinition;
+use vulkano::pipeline::graphics::viewport::ViewportState;
+use vulkano::pipeline::{ComputePipeline, GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use winit::event::WindowEvent;
+use winit::event_loop::EventLoopWindowTarget;
+
+use crate::cli::Options;
+use crate::debug;
+use crate::error::{Context, Result};
+use crate::runner::{self, App, Frame, Gpu, Target};
+use crate::setup;
+
+// Must match the size of the `particles` array in the compute shader.
+const PARTICLE_COUNT: u32 = 65536;
+// Must match `local_size_x` in the compute shader.
+const WORKGROUP_SIZE: u32 = 256;
+// Must match the size of the `emitters` array in the compute shader.
+const MAX_EMITTERS: usize = 4;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
+struct Particle {
+    // xyz: position, w: age in seconds
+    position: [f32; 4],
+    // xyz: velocity, w: lifetime in seconds, the particle is dead once its age reaches it
+    velocity: [f32; 4],
+    color: [f32; 4],
+}
+
+impl_vertex!(Particle, position, velocity, color);
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
+struct EmitterData {
+    // xyz: position, w: particles spawned per second
+    position_rate: [f32; 4],
+    // xyz: direction, w: spread, 0 is a straight line and 1 is a full sphere
+    direction_spread: [f32; 4],
+    // x: speed, y: speed variance, z: lifetime, w: lifetime variance
+    speed_lifetime: [f32; 4],
+    color_start: [f32; 4],
+    color_end: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
+struct Emitters {
+    emitters: [EmitterData; MAX_EMITTERS],
+}
+
+struct Emitter {
+    enabled: bool,
+    position: [f32; 3],
+    rate: f32,
+    direction: [f32; 3],
+    spread: f32,
+    speed: f32,
+    speed_variance: f32,
+    lifetime: f32,
+    lifetime_variance: f32,
+    color_start: [f32; 4],
+    color_end: [f32; 4],
+}
+
+impl Emitter {
+    fn data(&self) -> EmitterData {
+        let [x, y, z] = self.position;
+        let [dx, dy, dz] = self.direction;
+        EmitterData {
+            position_rate: [x, y, z, self.rate],
+            direction_spread: [dx, dy, dz, self.spread],
+            speed_lifetime: [
+                self.speed,
+                self.speed_variance,
+                self.lifetime,
+                self.lifetime_variance,
+            ],
+            color_start: self.color_start,
+            color_end: self.color_end,
+        }
+    }
+}
+
+struct GuiState {
+    gravity: f32,
+    particle_size: f32,
+    camera_distance: f32,
+    emitters: Vec<Emitter>,
+}
+
+impl Default for GuiState {
+    fn default() -> Self {
+        Self {
+            gravity: -9.8,
+            particle_size: 0.08,
+            camera_distance: 20.0,
+            emitters: vec![
+                Emitter {
+                    enabled: true,
+                    position: [0.0, 0.0, 0.0],
+                    rate: 4000.0,
+                    direction: [0.0, 1.0, 0.0],
+                    spread: 0.2,
+                    speed: 12.0,
+                    speed_variance: 2.0,
+                    lifetime: 2.5,
+                    lifetime_variance: 0.5,
+                    color_start: [1.0, 0.8, 0.3, 1.0],
+                    color_end: [0.8, 0.1, 0.0, 0.0],
+                },
+                Emitter {
+                    enabled: true,
+                    position: [-6.0, 2.0, 0.0],
+                    rate: 2000.0,
+                    direction: [1.0, 0.5, 0.0],
+                    spread: 0.6,
+                    speed: 6.0,
+                    speed_variance: 1.0,
+                    lifetime: 3.0,
+                    lifetime_variance: 1.0,
+                    color_start: [0.3, 0.6, 1.0, 1.0],
+                    color_end: [0.1, 0.1, 0.6, 0.0],
+                },
+                Emitter {
+                    enabled: false,
+                    position: [6.0, 2.0, 0.0],
+                    rate: 2000.0,
+                    direction: [-1.0, 0.5, 0.0],
+                    spread: 1.0,
+                    speed: 4.0,
+                    speed_variance: 1.0,
+                    lifetime: 4.0,
+                    lifetime_variance: 1.0,
+                    color_start: [0.4, 1.0, 0.4, 1.0],
+                    color_end: [1.0, 1.0, 1.0, 0.0],
+                },
+            ],
+        }
+    }
+}
+
+// The buffers are filled with [`Particle`]s, which are also the vertex input, and [`Emitters`],
+// so only the generated push constants are used.
+#[allow(dead_code)]
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: "
+			#version 450
+
+        layout(local_size_x = 256, local_size_y = 1, local_size_z = 1) in;
+
+        struct Particle {
+            vec4 position;
+            vec4 velocity;
+            vec4 color;
+        };
+
+        struct Emitter {
+            vec4 position_rate;
+            vec4 direction_spread;
+            vec4 speed_lifetime;
+            vec4 color_start;
+            vec4 color_end;
+        };
+
+        layout(set = 0, binding = 0) buffer Particles {
+            Particle particles[65536];
+        };
+
+        layout(set = 0, binding = 1) uniform Emitters {
+            Emitter emitters[4];
+        };
+
+        layout (push_constant) uniform PushConstants {
+            vec4 gravity;
+            float delta_time;
+            uint seed;
+            uint emitter_count;
+        } push;
+
+        uint hash(uint x) {
+            x ^= x >> 16;
+            x *= 0x7feb352dU;
+            x ^= x >> 15;
+            x *= 0x846ca68bU;
+            x ^= x >> 16;
+            return x;
+        }
+
+        float random(inout uint state) {
+            state = hash(state);
+            return float(state) / 4294967295.0;
+        }
+
+        vec3 random_unit_vector(inout uint state) {
+            float z = random(state) * 2.0 - 1.0;
+            float a = random(state) * 6.2831853;
+            float r = sqrt(1.0 - z * z);
+            return vec3(r * cos(a), r * sin(a), z);
+        }
+
+        void main() {
+            uint index = gl_GlobalInvocationID.x;
+            if (index >= particles.length() || push.emitter_count == 0) {
+                return;
+            }
+
+            // Every emitter owns an interleaved slice of the particle buffer.
+            Emitter emitter = emitters[index % push.emitter_count];
+            Particle p = particles[index];
+            float dt = push.delta_time;
+
+            if (p.position.w >= p.velocity.w) {
+                uint state = hash(index ^ hash(push.seed));
+                float slots = float(particles.length() / push.emitter_count);
+                if (random(state) >= emitter.position_rate.w * dt / slots) {
+                    return;
+                }
+
+                vec3 direction = normalize(
+                    normalize(emitter.direction_spread.xyz)
+                    + emitter.direction_spread.w * random_unit_vector(state)
+                    + vec3(0.0, 1e-4, 0.0)
+                );
+                float speed = emitter.speed_lifetime.x
+                    + emitter.speed_lifetime.y * (random(state) * 2.0 - 1.0);
+                float lifetime = max(
+                    emitter.speed_lifetime.z
+                        + emitter.speed_lifetime.w * (random(state) * 2.0 - 1.0),
+                    0.05
+                );
+
+                p.position = vec4(emitter.position_rate.xyz, 0.0);
+                p.velocity = vec4(direction * speed, lifetime);
+                p.color = emitter.color_start;
+                particles[index] = p;
+                return;
+            }
+
+            p.velocity.xyz += push.gravity.xyz * dt;
+            p.position.xyz += p.velocity.xyz * dt;
+            p.position.w += dt;
+
+            float life = clamp(p.position.w / p.velocity.w, 0.0, 1.0);
+            p.color = mix(emitter.color_start, emitter.color_end, life);
+            particles[index] = p;
+        }",
+        types_meta: {
+            use bytemuck::{Pod, Zeroable};
+
+            #[derive(Clone, Copy, Zeroable, Pod)]
+        },
+    }
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
+			#version 450
+
+        // One instance per particle, the quad corners come from the vertex index.
+        layout(location = 0) in vec4 position;
+        layout(location = 1) in vec4 velocity;
+        layout(location = 2) in vec4 color;
+
+        layout(location = 0) out vec4 v_color;
+        layout(location = 1) out vec2 v_corner;
+
+        layout (push_constant) uniform PushConstants {
+            mat4 view_proj;
+            vec4 camera_right;
+            vec4 camera_up;
+            float particle_size;
+        } push;
+
+        void main() {
+            v_corner = vec2(gl_VertexIndex & 1, (gl_VertexIndex >> 1) & 1) * 2.0 - 1.0;
+            v_color = color;
+
+            if (position.w >= velocity.w) {
+                // Dead particles are pushed outside of the clip volume.
+                gl_Position = vec4(0.0, 0.0, 2.0, 1.0);
+                return;
+            }
+
+            vec3 world = position.xyz
+                + (push.camera_right.xyz * v_corner.x + push.camera_up.xyz * v_corner.y)
+                * push.particle_size;
+            gl_Position = push.view_proj * vec4(world, 1.0);
+        }",
+        types_meta: {
+            use bytemuck::{Pod, Zeroable};
+
+            #[derive(Clone, Copy, Zeroable, Pod)]
+        },
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        include: ["shaders"],
+        src: "
+			#version 450
+
+        layout(location = 0) in vec4 v_color;
+        layout(location = 1) in vec2 v_corner;
+
+        layout(location = 0) out vec4 f_color;
+
+        // The encoding of the target, see `OutputEncoding`.
+        layout(constant_id = 0) const uint output_encoding = 0;
+
+        #include <encoding.glsl>
+
+        void main() {
+            float d = dot(v_corner, v_corner);
+            if (d > 1.0) {
+                discard;
+            }
+
+            // Premultiplied for additive blending.
+            float alpha = v_color.a * (1.0 - d);
+            f_color = encode_output(vec4(v_color.rgb * alpha, alpha), output_encoding);
+        }"
+    }
+}
+
+pub fn run(options: Options) -> Result<()> {
+    let now = Instant::now();
+    let particles = Particles {
+        clear_color: options.config.clear_color,
+        state: GuiState::default(),
+        start_time: now,
+        last_frame_time: now,
+    };
+    runner::run(options, particles)
+}
+
+struct Particles {
+    clear_color: [f32; 4],
+    state: GuiState,
+    start_time: Instant,
+    last_frame_time: Instant,
+}
+
+struct Renderer {
+    particle_buffer: Arc<DeviceLocalBuffer<[Particle]>>,
+    emitter_buffer: CpuBufferPool<Emitters>,
+    compute_pipeline: Arc<ComputePipeline>,
+    render_pass: Arc<RenderPass>,
+    pipeline: Arc<GraphicsPipeline>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    gui: Option<Gui>,
+}
+
+impl App for Particles {
+    type Renderer = Renderer;
+
+    fn create(
+        &mut self,
+        gpu: &Gpu,
+        target: &Target,
+        event_loop: Option<&EventLoopWindowTarget<()>>,
+    ) -> Result<Renderer> {
+        let device = &gpu.device;
+
+        // The particles are shared between the compute and graphics queues.
+        let mut uploader = gpu.uploader()?;
+
+        // All particles start dead (age == lifetime == 0) and get spawned by the emitters.
+        let particle_buffer = uploader.buffer_from_iter(
+            &*gpu.memory_allocator,
+            BufferUsage {
+                storage_buffer: true,
+                vertex_buffer: true,
+                ..BufferUsage::empty()
+            },
+            (0..PARTICLE_COUNT).map(|_| Particle::default()),
+        )?;
+        debug::set_buffer_name(&*particle_buffer, "particle buffer");
+        uploader.submit_and_wait()?;
+
+        let emitter_buffer =
+            CpuBufferPool::<Emitters>::uniform_buffer(gpu.memory_allocator.clone());
+
+        let cs = cs::load(device.clone()).context("can't load compute shader")?;
+        let vs = vs::load(device.clone()).context("can't load vertex shader")?;
+        let fs = fs::load(device.clone()).context("can't load fragment shader")?;
+
+        let compute_pipeline = ComputePipeline::new(
+            device.clone(),
+            setup::entry_point(&cs, "main")?,
+            &(),
+            Some(gpu.pipeline_cache.cache()),
+            |_| {},
+        )
+        .context("can't create compute pipeline")?;
+        debug::set_name(&*compute_pipeline, "particle simulation pipeline");
+
+        let render_pass = vulkano::ordered_passes_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: target.format(),
+                    samples: SampleCount::Sample1,
+                }
+            },
+            passes: [
+                { color: [color], depth_stencil: {}, input: [] }, // Particles
+                { color: [color], depth_stencil: {}, input: [] } // Gui render pass
+            ]
+        )
+        .context("can't create render pass")?;
+        debug::set_name(&*render_pass, "render pass");
+
+        let subpass = Subpass::from(render_pass.clone(), 0).expect("can't create subpass");
+        let pipeline = GraphicsPipeline::start()
+            .vertex_input_state(BuffersDefinition::new().instance::<Particle>())
+            .vertex_shader(setup::entry_point(&vs, "main")?, ())
+            .input_assembly_state(
+                InputAssemblyState::new().topology(PrimitiveTopology::TriangleStrip),
+            )
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(
+                setup::entry_point(&fs, "main")?,
+                fs::SpecializationConstants {
+                    output_encoding: target.encoding() as u32,
+                },
+            )
+            .color_blend_state(
+                ColorBlendState::new(subpass.num_color_attachments()).blend_additive(),
+            )
+            .render_pass(subpass)
+            .build_with_cache(gpu.pipeline_cache.cache())
+            .build(device.clone())
+            .context("can't create graphics pipeline")?;
+        debug::set_name(&*pipeline, "particle pipeline");
+
+        let framebuffers = window_size_dependent_setup(target, render_pass.clone())?;
+
+        // Headless runs draw the particles with the default settings.
+        let gui = event_loop
+            .zip(target.surface())
+            .map(|(event_loop, surface)| {
+                Gui::new_with_subpass(
+                    event_loop,
+                    surface.clone(),
+                    gpu.queues.graphics.clone(),
+                    Subpass::from(render_pass.clone(), 1).expect("can't create subpass"),
+                    GuiConfig::default(),
+                )
+            });
+
+        Ok(Renderer {
+            particle_buffer,
+            emitter_buffer,
+            compute_pipeline,
+            render_pass,
+            pipeline,
+            framebuffers,
+            gui,
+        })
+    }
+
+    fn resize(&mut self, renderer: &mut Renderer, _gpu: &Gpu, target: &Target) -> Result<()> {
+        renderer.framebuffers = window_size_dependent_setup(target, renderer.render_pass.clone())?;
+        Ok(())
+    }
+
+    fn window_event(&mut self, renderer: &mut Renderer, event: &WindowEvent) {
+        if let Some(gui) = renderer.gui.as_mut() {
+            gui.update(event);
+        }
+    }
+
+    fn draw(
+        &mut self,
+        renderer: &mut Renderer,
+        gpu: &Gpu,
+        target: &Target,
+        frame: &mut Frame,
+    ) -> Result<()> {
+        let now = Instant::now();
+        // Clamp so a stall (e.g. dragging the window) doesn't make particles jump.
+        let delta_time = now
+            .duration_since(self.last_frame_time)
+            .as_secs_f32()
+            .min(0.1);
+        let time = now.duration_since(self.start_time).as_secs_f32();
+        self.last_frame_time = now;
+
+        if let Some(gui) = renderer.gui.as_mut() {
+            gui.immediate_ui(|gui| {
+                let ctx = gui.context();
+                egui::Window::new("Debug Window")
+                    .default_width(300.0)
+                    .show(&ctx, |ui| {
+                        ui.label(format!("particles: {PARTICLE_COUNT}"));
+                        ui.add(Slider::new(&mut self.state.gravity, -20.0..=20.0).text("gravity"));
+                        ui.add(
+                            Slider::new(&mut self.state.particle_size, 0.01..=0.5)
+                                .text("particle size"),
+                        );
+                        ui.add(
+                            Slider::new(&mut self.state.camera_distance, 5.0..=60.0)
+                                .text("camera distance"),
+                        );
+
+                        for (i, emitter) in self.state.emitters.iter_mut().enumerate() {
+                            ui.collapsing(format!("emitter {i}"), |ui| {
+                                ui.checkbox(&mut emitter.enabled, "enabled");
+                                for (axis, value) in
+                                    ["x", "y", "z"].iter().zip(&mut emitter.position)
+                                {
+                                    ui.add(
+                                        Slider::new(value, -10.0..=10.0)
+                                            .text(format!("position {axis}")),
+                                    );
+                                }
+                                for (axis, value) in
+                                    ["x", "y", "z"].iter().zip(&mut emitter.direction)
+                                {
+                                    ui.add(
+                                        Slider::new(value, -1.0..=1.0)
+                                            .text(format!("direction {axis}")),
+                                    );
+                                }
+                                ui.add(
+                                    Slider::new(&mut emitter.rate, 0.0..=20000.0)
+                                        .text("spawn rate"),
+                                );
+                                ui.add(Slider::new(&mut emitter.spread, 0.0..=1.0).text("spread"));
+                                ui.add(Slider::new(&mut emitter.speed, 0.0..=30.0).text("speed"));
+                                ui.add(
+                                    Slider::new(&mut emitter.speed_variance, 0.0..=10.0)
+                                        .text("speed variance"),
+                                );
+                                ui.add(
+                                    Slider::new(&mut emitter.lifetime, 0.1..=10.0).text("lifetime"),
+                                );
+                                ui.add(
+                                    Slider::new(&mut emitter.lifetime_variance, 0.0..=5.0)
+                                        .text("lifetime variance"),
+                                );
+                                ui.horizontal(|ui| {
+                                    ui.label("color over life");
+                                    ui.color_edit_button_rgba_unmultiplied(
+                                        &mut emitter.color_start,
+                                    );
+                                    ui.color_edit_button_rgba_unmultiplied(&mut emitter.color_end);
+                                });
+                            });
+                        }
+                    });
+            });
+        }
+
+        let mut emitters = Emitters::default();
+        let mut emitter_count = 0;
+        for emitter in self.state.emitters.iter().filter(|e| e.enabled) {
+            emitters.emitters[emitter_count] = emitter.data();
+            emitter_count += 1;
+        }
+
+        let compute_push_constants = cs::ty::PushConstants {
+            gravity: [0.0, self.state.gravity, 0.0, 0.0],
+            delta_time,
+            seed: frame.number as u32,
+            emitter_count: emitter_count as u32,
+        };
+
+        let [width, height] = target.dimensions();
+        let angle = time * 0.2;
+        let view = Matrix4::look_at_rh(
+            Point3::new(
+                self.state.camera_distance * angle.cos(),
+                self.state.camera_distance * 0.4,
+                self.state.camera_distance * angle.sin(),
+            ),
+            Point3::new(0.0, 3.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        );
+        let proj = cgmath::perspective(
+            Rad(std::f32::consts::FRAC_PI_3),
+            width as f32 / height as f32,
+            0.1,
+            200.0,
+        );
+        // cgmath follows the OpenGL clip space conventions, flip y and map z into 0..1.
+        let correction = Matrix4::new(
+            1.0, 0.0, 0.0, 0.0, //
+            0.0, -1.0, 0.0, 0.0, //
+            0.0, 0.0, 0.5, 0.0, //
+            0.0, 0.0, 0.5, 1.0, //
+        );
+
+        let push_constants = vs::ty::PushConstants {
+            view_proj: (correction * proj * view).into(),
+            camera_right: [view.x.x, view.y.x, view.z.x, 0.0],
+            camera_up: [view.x.y, view.y.y, view.z.y, 0.0],
+            particle_size: self.state.particle_size,
+        };
+
+        let compute_set = PersistentDescriptorSet::new(
+            &gpu.descriptor_set_allocator,
+            renderer.compute_pipeline.layout().set_layouts()[0].clone(),
+            [
+                WriteDescriptorSet::buffer(0, renderer.particle_buffer.clone()),
+                WriteDescriptorSet::buffer(
+                    1,
+                    renderer
+                        .emitter_buffer
+                        .from_data(emitters)
+                        .context("can't allocate emitter buffer")?,
+                ),
+            ],
+        )
+        .context("can't create descriptor set")?;
+
+        // The simulation runs on the compute queue before the frame, see `Frame::compute`.
+        let mut simulation = AutoCommandBufferBuilder::primary(
+            &gpu.command_buffer_allocator,
+            gpu.queues.compute.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .context("can't create command buffer builder")?;
+        debug::label(
+            &mut simulation,
+            "particle simulation",
+            |builder| -> Result<()> {
+                builder
+                    .bind_pipeline_compute(renderer.compute_pipeline.clone())
+                    .bind_descriptor_sets(
+                        PipelineBindPoint::Compute,
+                        renderer.compute_pipeline.layout().clone(),
+                        0,
+                        compute_set,
+                    )
+                    .push_constants(
+                        renderer.compute_pipeline.layout().clone(),
+                        0,
+                        compute_push_constants,
+                    )
+                    .dispatch([PARTICLE_COUNT / WORKGROUP_SIZE, 1, 1])
+                    .context("can't dispatch particle simulation")?;
+                Ok(())
+            },
+        )?;
+        frame.compute = Some(
+            simulation
+                .build()
+                .context("can't build simulation command buffer")?,
+        );
+
+        frame
+            .builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some(target.encoding().encode(self.clear_color).into())],
+                    ..RenderPassBeginInfo::framebuffer(
+                        renderer.framebuffers[frame.image_index].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .context("can't begin render pass")?;
+
+        debug::label(&mut frame.builder, "scene pass", |builder| -> Result<()> {
+            builder
+                .set_viewport(0, [target.viewport()])
+                .bind_pipeline_graphics(renderer.pipeline.clone())
+                .push_constants(renderer.pipeline.layout().clone(), 0, push_constants)
+                .bind_vertex_buffers(0, renderer.particle_buffer.clone())
+                .draw(4, PARTICLE_COUNT, 0, 0)
+                .context("can't draw")?;
+            Ok(())
+        })?;
+
+        // Move on to next subpass for gui
+        frame
+            .builder
+            .next_subpass(SubpassContents::SecondaryCommandBuffers)
+            .context("can't begin gui subpass")?;
+        // Draw gui on subpass
+        if let Some(gui) = renderer.gui.as_mut() {
+            let cb = gui.draw_on_subpass_image(target.dimensions());
+            debug::label(&mut frame.builder, "gui subpass", |builder| -> Result<()> {
+                builder
+                    .execute_commands(cb)
+                    .context("can't execute gui commands")?;
+                Ok(())
+            })?;
+        }
+
+        // Last end render pass
+        frame
+            .builder
+            .end_render_pass()
+            .context("can't end render pass")?;
+        Ok(())
+    }
+}
+
+fn window_size_dependent_setup(
+    target: &Target,
+    render_pass: Arc<RenderPass>,
+) -> Result<Vec<Arc<Framebuffer>>> {
+    target
+        .views()
+        .iter()
+        .map(|view| {
+            Framebuffer::new(
+                render_pass.clone(),
+                FramebufferCreateInfo {
+                    attachments: vec![view.clone()],
+                    ..Default::default()
+                },
+            )
+            .context("can't create framebuffer")
+        })
+        .collect()
+}
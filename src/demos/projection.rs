@@ -0,0 +1,341 @@
+use std::io::Cursor;
+use std::sync::Arc;
+
+use bytemuck::{Pod, Zeroable};
+use vulkano::buffer::{BufferUsage, DeviceLocalBuffer, TypedBufferAccess};
+use vulkano::command_buffer::{RenderPassBeginInfo, SubpassContents};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageDimensions, ImmutableImage, MipmapsCount};
+use vulkano::impl_vertex;
+use vulkano::pipeline::graphics::color_blend::ColorBlendState;
+use vulkano::pipeline::graphics::input_assembly::{InputAssemblyState, PrimitiveTopology};
+use vulkano::pipeline::graphics::vertex_input::BuffersDefinition;
+use vulkano::pipeline::graphics::viewport::ViewportState;
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo};
+use winit::event_loop::EventLoopWindowTarget;
+
+use crate::cli::Options;
+use crate::debug;
+use crate::error::{Context, Result};
+use crate::runner::{self, App, Frame, Gpu, Target};
+use crate::setup;
+use crate::upload::Uploader;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
+struct Vertex {
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+}
+
+impl_vertex!(Vertex, position, tex_coords);
+
+const VERTICES: [Vertex; 4] = [
+    Vertex {
+        position: [25.0, 25.0],
+        tex_coords: [0.0, 0.0],
+    },
+    Vertex {
+        position: [225.0, 25.0],
+        tex_coords: [1.0, 0.0],
+    },
+    Vertex {
+        position: [225.0, 225.0],
+        tex_coords: [1.0, 1.0],
+    },
+    Vertex {
+        position: [25.0, 225.0],
+        tex_coords: [0.0, 1.0],
+    },
+];
+
+const INDICES: [u16; 6] = [
+    0, 1, 2, //first triangle
+    2, 3, 0, //second triangle
+];
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
+			#version 450
+
+            layout(location = 0) in vec2 position;
+            layout(location = 1) in vec2 tex_coords;
+
+            layout(location = 0) out vec2 v_tex_coords;
+
+            layout (push_constant) uniform PushConstants {
+                mat4 proj;
+            } push;
+
+            void main() {
+                gl_Position = push.proj * vec4(position, 0.0, 1.0);
+                v_tex_coords = tex_coords;
+            }",
+        types_meta: {
+            use bytemuck::{Pod, Zeroable};
+
+            #[derive(Clone, Copy, Zeroable, Pod)]
+        },
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        include: ["shaders"],
+        src: "
+			#version 450
+
+            layout(location = 0) in vec2 tex_coords;
+
+            layout(location = 0) out vec4 f_color;
+            layout(set = 0, binding = 0) uniform sampler2D tex;
+
+            // The encoding of the target, see `OutputEncoding`.
+            layout(constant_id = 0) const uint output_encoding = 0;
+
+            #include <encoding.glsl>
+
+            void main() {
+                f_color = encode_output(texture(tex, tex_coords), output_encoding);
+            }",
+        types_meta: {
+            use bytemuck::{Pod, Zeroable};
+
+            #[derive(Clone, Copy, Zeroable, Pod)]
+        },
+    }
+}
+
+pub fn run(options: Options) -> Result<()> {
+    let projection = Projection {
+        clear_color: options.config.clear_color,
+    };
+    runner::run(options, projection)
+}
+
+struct Projection {
+    clear_color: [f32; 4],
+}
+
+struct Renderer {
+    vertex_buffer: Arc<DeviceLocalBuffer<[Vertex]>>,
+    index_buffer: Arc<DeviceLocalBuffer<[u16]>>,
+    render_pass: Arc<RenderPass>,
+    pipeline: Arc<GraphicsPipeline>,
+    set: Arc<PersistentDescriptorSet>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+}
+
+impl App for Projection {
+    type Renderer = Renderer;
+
+    fn create(
+        &mut self,
+        gpu: &Gpu,
+        target: &Target,
+        _event_loop: Option<&EventLoopWindowTarget<()>>,
+    ) -> Result<Renderer> {
+        let device = &gpu.device;
+
+        let mut uploader = gpu.uploader()?;
+        let vertex_buffer = uploader.buffer_from_iter(
+            &*gpu.memory_allocator,
+            BufferUsage {
+                vertex_buffer: true,
+                ..BufferUsage::empty()
+            },
+            VERTICES,
+        )?;
+        debug::set_buffer_name(&*vertex_buffer, "vertex buffer");
+
+        let index_buffer = uploader.buffer_from_iter(
+            &*gpu.memory_allocator,
+            BufferUsage {
+                index_buffer: true,
+                ..BufferUsage::empty()
+            },
+            INDICES,
+        )?;
+        debug::set_buffer_name(&*index_buffer, "index buffer");
+
+        let texture = load_texture(gpu, &mut uploader)?;
+        uploader.submit_and_wait()?;
+
+        let vs = vs::load(device.clone()).context("can't load vertex shader")?;
+        let fs = fs::load(device.clone()).context("can't load fragment shader")?;
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: target.format(),
+                    samples: 1,
+                }
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {}
+            }
+        )
+        .context("can't create render pass")?;
+        debug::set_name(&*render_pass, "render pass");
+
+        let subpass = Subpass::from(render_pass.clone(), 0).expect("can't create subpass");
+        let pipeline = GraphicsPipeline::start()
+            .vertex_input_state(BuffersDefinition::new().vertex::<Vertex>())
+            .vertex_shader(setup::entry_point(&vs, "main")?, ())
+            .input_assembly_state(
+                InputAssemblyState::new().topology(PrimitiveTopology::TriangleStrip),
+            )
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(
+                setup::entry_point(&fs, "main")?,
+                fs::SpecializationConstants {
+                    output_encoding: target.encoding() as u32,
+                },
+            )
+            .color_blend_state(ColorBlendState::new(subpass.num_color_attachments()).blend_alpha())
+            .render_pass(subpass)
+            .build_with_cache(gpu.pipeline_cache.cache())
+            .build(device.clone())
+            .context("can't create graphics pipeline")?;
+        debug::set_name(&*pipeline, "pipeline");
+
+        let sampler = Sampler::new(
+            device.clone(),
+            SamplerCreateInfo {
+                mag_filter: Filter::Linear,
+                min_filter: Filter::Linear,
+                address_mode: [SamplerAddressMode::Repeat; 3],
+                ..Default::default()
+            },
+        )
+        .context("can't create sampler")?;
+
+        let layout = pipeline.layout().set_layouts()[0].clone();
+        let set = PersistentDescriptorSet::new(
+            &gpu.descriptor_set_allocator,
+            layout,
+            [WriteDescriptorSet::image_view_sampler(0, texture, sampler)],
+        )
+        .context("can't create descriptor set")?;
+
+        let framebuffers = window_size_dependent_setup(target, render_pass.clone())?;
+
+        Ok(Renderer {
+            vertex_buffer,
+            index_buffer,
+            render_pass,
+            pipeline,
+            set,
+            framebuffers,
+        })
+    }
+
+    fn resize(&mut self, renderer: &mut Renderer, _gpu: &Gpu, target: &Target) -> Result<()> {
+        renderer.framebuffers = window_size_dependent_setup(target, renderer.render_pass.clone())?;
+        Ok(())
+    }
+
+    fn draw(
+        &mut self,
+        renderer: &mut Renderer,
+        _gpu: &Gpu,
+        target: &Target,
+        frame: &mut Frame,
+    ) -> Result<()> {
+        let [width, height] = target.dimensions();
+        let proj = cgmath::ortho(0.0, width as f32, 0.0, height as f32, -1.0, 1.0);
+
+        let push_constants = vs::ty::PushConstants { proj: proj.into() };
+
+        debug::label(&mut frame.builder, "scene pass", |builder| {
+            builder
+                .push_constants(renderer.pipeline.layout().clone(), 0, push_constants)
+                .begin_render_pass(
+                    RenderPassBeginInfo {
+                        clear_values: vec![Some(target.encoding().encode(self.clear_color).into())],
+                        ..RenderPassBeginInfo::framebuffer(
+                            renderer.framebuffers[frame.image_index].clone(),
+                        )
+                    },
+                    SubpassContents::Inline,
+                )
+                .context("can't begin render pass")?
+                .set_viewport(0, [target.viewport()])
+                .bind_pipeline_graphics(renderer.pipeline.clone())
+                .bind_descriptor_sets(
+                    PipelineBindPoint::Graphics,
+                    renderer.pipeline.layout().clone(),
+                    0,
+                    renderer.set.clone(),
+                )
+                .bind_vertex_buffers(0, renderer.vertex_buffer.clone())
+                .bind_index_buffer(renderer.index_buffer.clone())
+                .draw_indexed(renderer.index_buffer.len() as u32, 1, 0, 0, 0)
+                .context("can't draw")?
+                .end_render_pass()
+                .context("can't end render pass")?;
+            Ok(())
+        })
+    }
+}
+
+fn load_texture(gpu: &Gpu, uploader: &mut Uploader) -> Result<Arc<ImageView<ImmutableImage>>> {
+    let png_bytes = include_bytes!("../image.png").to_vec();
+    let cursor = Cursor::new(png_bytes);
+    let decoder = png::Decoder::new(cursor);
+    let mut reader = decoder.read_info().context("can't read png info")?;
+    let info = reader.info();
+    let dimensions = ImageDimensions::Dim2d {
+        width: info.width,
+        height: info.height,
+        array_layers: 1,
+    };
+    let mut image_data = Vec::new();
+    image_data.resize((info.width * info.height * 4) as usize, 0);
+    reader
+        .next_frame(&mut image_data)
+        .context("can't decode png")?;
+
+    let image = ImmutableImage::from_iter(
+        &*gpu.memory_allocator,
+        image_data,
+        dimensions,
+        MipmapsCount::One,
+        Format::R8G8B8A8_SRGB,
+        uploader.builder(),
+    )
+    .context("can't create image")?;
+    debug::set_image_name(&*image, "texture");
+    ImageView::new_default(image).context("can't create image view")
+}
+
+fn window_size_dependent_setup(
+    target: &Target,
+    render_pass: Arc<RenderPass>,
+) -> Result<Vec<Arc<Framebuffer>>> {
+    target
+        .views()
+        .iter()
+        .map(|view| {
+            Framebuffer::new(
+                render_pass.clone(),
+                FramebufferCreateInfo {
+                    attachments: vec![view.clone()],
+                    ..Default::default()
+                },
+            )
+            .context("can't create framebuffer")
+        })
+        .collect()
+}
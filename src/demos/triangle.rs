@@ -0,0 +1,326 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use bytemuck::{Pod, Zeroable};
+use vulkano::buffer::{BufferUsage, DeviceLocalBuffer, TypedBufferAccess};
+use vulkano::command_buffer::{RenderPassBeginInfo, SubpassContents};
+use vulkano::format::ClearValue;
+use vulkano::image::view::ImageView;
+use vulkano::image::{AttachmentImage, ImageViewAbstract, SampleCount};
+use vulkano::impl_vertex;
+use vulkano::memory::allocator::StandardMemoryAllocator;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::multisample::MultisampleState;
+use vulkano::pipeline::graphics::vertex_input::BuffersDefinition;
+use vulkano::pipeline::graphics::viewport::ViewportState;
+use vulkano::pipeline::{GraphicsPipeline, Pipeline};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use winit::event_loop::EventLoopWindowTarget;
+
+use crate::cli::Options;
+use crate::debug;
+use crate::error::{Context, Result};
+use crate::runner::{self, App, Frame, Gpu, Target};
+use crate::setup;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
+struct Vertex {
+    position: [f32; 2],
+    color: [f32; 3],
+}
+
+impl_vertex!(Vertex, position, color);
+
+const VERTICES: [Vertex; 3] = [
+    Vertex {
+        position: [0.0, -0.5],
+        color: [0.0, 1.0, 0.0],
+    },
+    Vertex {
+        position: [0.5, 0.5],
+        color: [0.0, 0.0, 1.0],
+    },
+    Vertex {
+        position: [-0.5, 0.5],
+        color: [1.0, 0.0, 0.0],
+    },
+];
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
+			#version 450
+
+            layout(location = 0) in vec2 position;
+            layout(location = 1) in vec3 color;
+
+            layout(location = 0) out vec3 v_color;
+
+            void main() {
+                v_color = color;
+                gl_Position = vec4(position, 0.0, 1.0);
+            }"
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        include: ["shaders"],
+        src: "
+			#version 450
+
+            layout(location = 0) in vec3 v_color;
+
+            layout(location = 0) out vec4 f_color;
+
+            layout (push_constant) uniform PushConstants {
+                float time;
+            } push;
+
+            // The encoding of the target, see `OutputEncoding`.
+            layout(constant_id = 0) const uint output_encoding = 0;
+
+            #include <encoding.glsl>
+
+            void main() {
+                vec3 col = 0.5 + 0.5*cos(push.time + v_color.xyx + vec3(0, 2, 4));
+
+                f_color = encode_output(vec4(col, 1.0), output_encoding);
+            }",
+        types_meta: {
+            use bytemuck::{Pod, Zeroable};
+
+            #[derive(Clone, Copy, Zeroable, Pod)]
+        },
+    }
+}
+
+pub fn run(options: Options) -> Result<()> {
+    let triangle = Triangle {
+        samples: options.sample_count(),
+        clear_color: options.config.clear_color,
+        start_time: Instant::now(),
+    };
+    runner::run(options, triangle)
+}
+
+struct Triangle {
+    samples: SampleCount,
+    clear_color: [f32; 4],
+    start_time: Instant,
+}
+
+struct Renderer {
+    vertex_buffer: Arc<DeviceLocalBuffer<[Vertex]>>,
+    render_pass: Arc<RenderPass>,
+    pipeline: Arc<GraphicsPipeline>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+}
+
+impl App for Triangle {
+    type Renderer = Renderer;
+
+    fn create(
+        &mut self,
+        gpu: &Gpu,
+        target: &Target,
+        _event_loop: Option<&EventLoopWindowTarget<()>>,
+    ) -> Result<Renderer> {
+        let device = &gpu.device;
+
+        let mut uploader = gpu.uploader()?;
+        let vertex_buffer = uploader.buffer_from_iter(
+            &*gpu.memory_allocator,
+            BufferUsage {
+                vertex_buffer: true,
+                ..BufferUsage::empty()
+            },
+            VERTICES,
+        )?;
+        debug::set_buffer_name(&*vertex_buffer, "vertex buffer");
+        uploader.submit_and_wait()?;
+
+        let vs = vs::load(device.clone()).context("can't load vertex shader")?;
+        let fs = fs::load(device.clone()).context("can't load fragment shader")?;
+
+        // With `--msaa` the triangle is drawn to a multisampled image that is resolved to the
+        // target image, which stays the first attachment either way.
+        let render_pass = if self.samples == SampleCount::Sample1 {
+            vulkano::single_pass_renderpass!(
+                device.clone(),
+                attachments: {
+                    color: {
+                        load: Clear,
+                        store: Store,
+                        format: target.format(),
+                        samples: 1,
+                    }
+                },
+                pass: {
+                    color: [color],
+                    depth_stencil: {}
+                }
+            )
+        } else {
+            vulkano::single_pass_renderpass!(
+                device.clone(),
+                attachments: {
+                    color: {
+                        load: DontCare,
+                        store: Store,
+                        format: target.format(),
+                        samples: 1,
+                    },
+                    multisampled: {
+                        load: Clear,
+                        store: DontCare,
+                        format: target.format(),
+                        samples: self.samples,
+                    }
+                },
+                pass: {
+                    color: [multisampled],
+                    color_resolve: [color],
+                    depth_stencil: {}
+                }
+            )
+        }
+        .context("can't create render pass")?;
+        debug::set_name(&*render_pass, "render pass");
+
+        let pipeline = GraphicsPipeline::start()
+            .render_pass(Subpass::from(render_pass.clone(), 0).expect("can't create subpass"))
+            .vertex_input_state(BuffersDefinition::new().vertex::<Vertex>())
+            .input_assembly_state(InputAssemblyState::new())
+            .vertex_shader(setup::entry_point(&vs, "main")?, ())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .multisample_state(MultisampleState {
+                rasterization_samples: self.samples,
+                ..Default::default()
+            })
+            .fragment_shader(
+                setup::entry_point(&fs, "main")?,
+                fs::SpecializationConstants {
+                    output_encoding: target.encoding() as u32,
+                },
+            )
+            .build_with_cache(gpu.pipeline_cache.cache())
+            .build(device.clone())
+            .context("can't create graphics pipeline")?;
+        debug::set_name(&*pipeline, "pipeline");
+
+        let framebuffers = window_size_dependent_setup(
+            target,
+            render_pass.clone(),
+            &gpu.memory_allocator,
+            self.samples,
+        )?;
+
+        Ok(Renderer {
+            vertex_buffer,
+            render_pass,
+            pipeline,
+            framebuffers,
+        })
+    }
+
+    fn resize(&mut self, renderer: &mut Renderer, gpu: &Gpu, target: &Target) -> Result<()> {
+        renderer.framebuffers = window_size_dependent_setup(
+            target,
+            renderer.render_pass.clone(),
+            &gpu.memory_allocator,
+            self.samples,
+        )?;
+        Ok(())
+    }
+
+    fn draw(
+        &mut self,
+        renderer: &mut Renderer,
+        _gpu: &Gpu,
+        target: &Target,
+        frame: &mut Frame,
+    ) -> Result<()> {
+        // Update per-frame variables.
+        let time = self.start_time.elapsed().as_secs_f32();
+        let push_constants = fs::ty::PushConstants { time };
+
+        // Only the multisampled image is cleared, the resolve overwrites the target image.
+        let clear_color = Some(ClearValue::from(target.encoding().encode(self.clear_color)));
+        let clear_values = if self.samples == SampleCount::Sample1 {
+            vec![clear_color]
+        } else {
+            vec![None, clear_color]
+        };
+
+        debug::label(&mut frame.builder, "scene pass", |builder| {
+            builder
+                .push_constants(renderer.pipeline.layout().clone(), 0, push_constants)
+                .begin_render_pass(
+                    RenderPassBeginInfo {
+                        clear_values,
+                        ..RenderPassBeginInfo::framebuffer(
+                            renderer.framebuffers[frame.image_index].clone(),
+                        )
+                    },
+                    SubpassContents::Inline,
+                )
+                .context("can't begin render pass")?
+                .set_viewport(0, [target.viewport()])
+                .bind_pipeline_graphics(renderer.pipeline.clone())
+                .bind_vertex_buffers(0, renderer.vertex_buffer.clone())
+                .draw(renderer.vertex_buffer.len() as u32, 1, 0, 0)
+                .context("can't draw")?
+                .end_render_pass()
+                .context("can't end render pass")?;
+            Ok(())
+        })
+    }
+}
+
+fn window_size_dependent_setup(
+    target: &Target,
+    render_pass: Arc<RenderPass>,
+    memory_allocator: &StandardMemoryAllocator,
+    samples: SampleCount,
+) -> Result<Vec<Arc<Framebuffer>>> {
+    // Every framebuffer shares the multisampled image. Each frame is submitted after the one
+    // before it, so they never draw to it at the same time.
+    let multisampled = if samples != SampleCount::Sample1 {
+        let image = AttachmentImage::transient_multisampled(
+            memory_allocator,
+            target.dimensions(),
+            samples,
+            target.format(),
+        )
+        .context("can't create multisampled image")?;
+        debug::set_image_name(&*image, "multisampled color");
+        Some(ImageView::new_default(image).context("can't create image view")?)
+    } else {
+        None
+    };
+
+    target
+        .views()
+        .iter()
+        .map(|view| {
+            let mut attachments = vec![view.clone()];
+            attachments.extend(
+                multisampled
+                    .clone()
+                    .map(|view| view as Arc<dyn ImageViewAbstract>),
+            );
+            Framebuffer::new(
+                render_pass.clone(),
+                FramebufferCreateInfo {
+                    attachments,
+                    ..Default::default()
+                },
+            )
+            .context("can't create framebuffer")
+        })
+        .collect()
+}
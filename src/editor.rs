@@ -0,0 +1,793 @@
+//! An egui editor for a [`Scene`]: a hierarchy of its entities, an inspector for the camera,
+//! entities, their materials and lights, and saving the result back to a scene file.
+//!
+//! The editor owns the scene and keeps a [`SceneGraph`] of its entities up to date. What
+//! [`SceneEditor::ui`] returns tells the renderer whether it only has to read the new values
+//! or upload the scene again.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use cgmath::{Matrix4, SquareMatrix};
+use egui::{DragValue, Ui};
+
+use crate::scene::{Camera, Entity, Light, Mesh, Projection, Scene, Transform};
+use crate::scene_graph::{DrawItem, NodeId, SceneGraph};
+
+/// What is shown in the inspector.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Selection {
+    Camera,
+    /// An index into [`Scene::entities`].
+    Entity(usize),
+    /// An index into [`Scene::lights`].
+    Light(usize),
+}
+
+/// How much of the scene an edit changed, from least to most.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Edit {
+    None,
+    /// Transforms, colors, lights or the camera, which are read every frame.
+    Properties,
+    /// Entities were added or removed, or a mesh or texture changed, so the scene has to be
+    /// uploaded again.
+    Structure,
+}
+
+pub struct SceneEditor {
+    scene: Scene,
+    graph: SceneGraph<usize>,
+    /// The node of each entity.
+    nodes: Vec<NodeId>,
+    selection: Option<Selection>,
+    /// The name being typed for an entity, which is only renamed once the field loses focus,
+    /// and why the last name entered was rejected.
+    name_edit: Option<(usize, String, Option<String>)>,
+    path: String,
+    /// The outcome of the last save.
+    status: Option<Result<String, String>>,
+    /// A mesh or material edit made while dragging, reported once the pointer is released so a
+    /// dragged value doesn't upload the scene on every step.
+    pending: bool,
+}
+
+impl SceneEditor {
+    /// Edits `scene`, which is saved to `path` unless another one is entered.
+    pub fn new(scene: Scene, path: PathBuf) -> Self {
+        let mut editor = Self {
+            scene,
+            graph: SceneGraph::new(),
+            nodes: Vec::new(),
+            selection: None,
+            name_edit: None,
+            path: path.display().to_string(),
+            status: None,
+            pending: false,
+        };
+        editor.rebuild();
+        editor
+    }
+
+    pub fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.scene.camera
+    }
+
+    pub fn selection(&self) -> Option<Selection> {
+        self.selection
+    }
+
+    pub fn select(&mut self, selection: Option<Selection>) {
+        self.selection = selection;
+    }
+
+    /// The entities to draw, with their world matrices.
+    pub fn draw_items(&mut self) -> impl Iterator<Item = DrawItem<'_, usize>> {
+        self.graph.update();
+        self.graph.draw_items()
+    }
+
+    /// The world matrix of `entity`.
+    pub fn world(&mut self, entity: usize) -> Matrix4<f32> {
+        self.graph.update();
+        self.graph
+            .world(self.nodes[entity])
+            .unwrap_or_else(Matrix4::identity)
+    }
+
+    /// The world matrix `entity`'s transform is relative to.
+    pub fn parent_world(&mut self, entity: usize) -> Matrix4<f32> {
+        self.graph.update();
+        self.graph
+            .parent(self.nodes[entity])
+            .and_then(|parent| self.graph.world(parent))
+            .unwrap_or_else(Matrix4::identity)
+    }
+
+    /// Moves `entity` to `transform`, relative to its parent.
+    pub fn set_transform(&mut self, entity: usize, transform: Transform) {
+        self.scene.entities[entity].transform = transform;
+        self.graph.set_local(self.nodes[entity], transform);
+    }
+
+    /// Shows the "Scene" and "Inspector" windows.
+    pub fn ui(&mut self, ctx: &egui::Context) -> Edit {
+        let mut actions = Edit::None;
+        let mut edit = Edit::None;
+
+        egui::Window::new("Scene")
+            .default_width(250.0)
+            .show(ctx, |ui| {
+                actions = self.actions_ui(ui);
+                ui.separator();
+
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        let selected = self.selection == Some(Selection::Camera);
+                        if ui.selectable_label(selected, "camera").clicked() {
+                            self.selection = Some(Selection::Camera);
+                        }
+                        for id in self.graph.roots().to_vec() {
+                            self.tree_ui(ui, id);
+                        }
+                        for (i, light) in self.scene.lights.iter().enumerate() {
+                            let name = match light {
+                                Light::Point { .. } => format!("point light {}", i),
+                                Light::Directional { .. } => format!("directional light {}", i),
+                            };
+                            let selected = self.selection == Some(Selection::Light(i));
+                            if ui.selectable_label(selected, name).clicked() {
+                                self.selection = Some(Selection::Light(i));
+                            }
+                        }
+                    });
+
+                ui.separator();
+                self.save_ui(ui);
+            });
+
+        egui::Window::new("Inspector")
+            .default_width(300.0)
+            .show(ctx, |ui| {
+                edit = edit.max(match self.selection {
+                    Some(Selection::Camera) => self.camera_ui(ui),
+                    Some(Selection::Entity(entity)) => self.entity_ui(ui, entity),
+                    Some(Selection::Light(light)) => self.light_ui(ui, light),
+                    None => {
+                        ui.label("nothing selected");
+                        Edit::None
+                    }
+                });
+            });
+
+        // Added and removed entities can't wait, the renderer draws entities by index.
+        if edit == Edit::Structure && ctx.is_using_pointer() {
+            self.pending = true;
+            edit = Edit::Properties;
+        } else if self.pending && !ctx.is_using_pointer() {
+            self.pending = false;
+            edit = Edit::Structure;
+        }
+        edit.max(actions)
+    }
+
+    /// The add, duplicate and remove buttons.
+    fn actions_ui(&mut self, ui: &mut Ui) -> Edit {
+        let selected = match self.selection {
+            Some(Selection::Entity(entity)) => Some(entity),
+            _ => None,
+        };
+        let mut edit = Edit::None;
+
+        ui.horizontal(|ui| {
+            if ui.button("add").clicked() {
+                // Under the selected entity, so building a hierarchy is a matter of selecting
+                // the parent first.
+                let entity = Entity {
+                    name: self.unique_name("entity"),
+                    material: None,
+                    parent: selected.map(|parent| self.scene.entities[parent].name.clone()),
+                    mesh: Mesh::Sprite {
+                        size: [100.0, 100.0],
+                    },
+                    transform: Transform::default(),
+                };
+                self.scene.entities.push(entity);
+                self.selection = Some(Selection::Entity(self.scene.entities.len() - 1));
+                edit = Edit::Structure;
+            }
+            if ui
+                .add_enabled(selected.is_some(), egui::Button::new("duplicate"))
+                .clicked()
+            {
+                self.duplicate(selected.unwrap());
+                edit = Edit::Structure;
+            }
+            if ui
+                .add_enabled(selected.is_some(), egui::Button::new("remove"))
+                .clicked()
+            {
+                self.remove(selected.unwrap());
+                edit = Edit::Structure;
+            }
+        });
+
+        if edit == Edit::Structure {
+            self.rebuild();
+        }
+        edit
+    }
+
+    fn tree_ui(&mut self, ui: &mut Ui, id: NodeId) {
+        let Some(&entity) = self.graph.item(id) else {
+            return;
+        };
+        let name = self.scene.entities[entity].name.clone();
+        let selected = self.selection == Some(Selection::Entity(entity));
+        let children = self.graph.children(id).to_vec();
+
+        let clicked = if children.is_empty() {
+            ui.selectable_label(selected, name).clicked()
+        } else {
+            let state = egui::collapsing_header::CollapsingState::load_with_default_open(
+                ui.ctx(),
+                ui.make_persistent_id(id),
+                true,
+            );
+            let (_, header, _) = state
+                .show_header(ui, |ui| ui.selectable_label(selected, name).clicked())
+                .body(|ui| {
+                    for child in children {
+                        self.tree_ui(ui, child);
+                    }
+                });
+            header.inner
+        };
+        if clicked {
+            self.selection = Some(Selection::Entity(entity));
+        }
+    }
+
+    fn save_ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.path);
+            if ui.button("save").clicked() {
+                let path = PathBuf::from(&self.path);
+                self.status = Some(
+                    self.scene
+                        .validate()
+                        .and_then(|()| self.scene.save(&path).map_err(|e| e.to_string()))
+                        .map(|()| format!("saved to {}", path.display())),
+                );
+            }
+        });
+        match &self.status {
+            Some(Ok(message)) => {
+                ui.label(message);
+            }
+            Some(Err(error)) => {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+            None => (),
+        }
+    }
+
+    fn camera_ui(&mut self, ui: &mut Ui) -> Edit {
+        let camera = &mut self.scene.camera;
+        let before = *camera;
+
+        let perspective = matches!(camera.projection, Projection::Perspective { .. });
+        egui::ComboBox::from_label("projection")
+            .selected_text(if perspective {
+                "perspective"
+            } else {
+                "orthographic"
+            })
+            .show_ui(ui, |ui| {
+                if ui.selectable_label(!perspective, "orthographic").clicked() && perspective {
+                    camera.projection = Projection::default();
+                }
+                if ui.selectable_label(perspective, "perspective").clicked() && !perspective {
+                    camera.projection = Projection::Perspective {
+                        fov_y: 60.0,
+                        near: 0.1,
+                        far: 1000.0,
+                    };
+                }
+            });
+        match &mut camera.projection {
+            Projection::Orthographic { near, far } => {
+                drag_row(ui, "near", near, 0.1);
+                drag_row(ui, "far", far, 0.1);
+            }
+            Projection::Perspective { fov_y, near, far } => {
+                ui.horizontal(|ui| {
+                    ui.add(DragValue::new(fov_y).clamp_range(1.0..=179.0));
+                    ui.label("fov y");
+                });
+                ui.horizontal(|ui| {
+                    ui.add(DragValue::new(near).speed(0.01).clamp_range(0.001..=*far));
+                    ui.label("near");
+                });
+                ui.horizontal(|ui| {
+                    ui.add(DragValue::new(far).clamp_range(*near..=f32::MAX));
+                    ui.label("far");
+                });
+            }
+        }
+        ui.separator();
+        transform_ui(ui, &mut camera.transform);
+
+        if *camera != before {
+            Edit::Properties
+        } else {
+            Edit::None
+        }
+    }
+
+    fn entity_ui(&mut self, ui: &mut Ui, entity: usize) -> Edit {
+        let mut edit = Edit::None;
+
+        if !matches!(self.name_edit, Some((edited, ..)) if edited == entity) {
+            self.name_edit = Some((entity, self.scene.entities[entity].name.clone(), None));
+        }
+        let (_, name, error) = self.name_edit.as_mut().unwrap();
+        let response = ui
+            .horizontal(|ui| {
+                let response = ui.text_edit_singleline(name);
+                ui.label("name");
+                response
+            })
+            .inner;
+        if let Some(error) = error {
+            ui.colored_label(egui::Color32::RED, error.as_str());
+        }
+        if response.lost_focus() {
+            let name = name.clone();
+            let result = self.rename(entity, &name);
+            // A rejected name goes back to the current one, with the reason under it.
+            self.name_edit = Some((
+                entity,
+                self.scene.entities[entity].name.clone(),
+                result.err(),
+            ));
+        }
+
+        // Only entities outside of this one's subtree can become its parent.
+        let subtree = self.subtree(entity);
+        let parent = self.scene.entities[entity].parent.clone();
+        let mut new_parent = parent.clone();
+        egui::ComboBox::from_label("parent")
+            .selected_text(parent.as_deref().unwrap_or("none"))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut new_parent, None, "none");
+                for (i, other) in self.scene.entities.iter().enumerate() {
+                    if !subtree.contains(&i) {
+                        ui.selectable_value(
+                            &mut new_parent,
+                            Some(other.name.clone()),
+                            other.name.as_str(),
+                        );
+                    }
+                }
+            });
+        if new_parent != parent {
+            let parent_node = new_parent
+                .as_deref()
+                .and_then(|name| self.scene.entities.iter().position(|e| e.name == name))
+                .map(|i| self.nodes[i]);
+            if self.graph.set_parent(self.nodes[entity], parent_node) {
+                self.scene.entities[entity].parent = new_parent;
+                edit = Edit::Properties;
+            }
+        }
+
+        ui.separator();
+        let mut transform = self.scene.entities[entity].transform;
+        transform_ui(ui, &mut transform);
+        if transform != self.scene.entities[entity].transform {
+            self.set_transform(entity, transform);
+            edit = edit.max(Edit::Properties);
+        }
+
+        ui.separator();
+        match &mut self.scene.entities[entity].mesh {
+            Mesh::Sprite { size } => {
+                let before = *size;
+                ui.horizontal(|ui| {
+                    ui.add(DragValue::new(&mut size[0]).clamp_range(1.0..=f32::MAX));
+                    ui.add(DragValue::new(&mut size[1]).clamp_range(1.0..=f32::MAX));
+                    ui.label("sprite size");
+                });
+                if *size != before {
+                    edit = Edit::Structure;
+                }
+            }
+            Mesh::Inline { indices, .. } => {
+                ui.label(format!("inline mesh, {} triangles", indices.len() / 3));
+            }
+        }
+
+        ui.separator();
+        edit.max(self.material_ui(ui, entity))
+    }
+
+    /// The material of `entity`. Materials are shared, so this changes every entity using it.
+    fn material_ui(&mut self, ui: &mut Ui, entity: usize) -> Edit {
+        let mut edit = Edit::None;
+
+        let material = self.scene.entities[entity].material.clone();
+        let mut new_material = material.clone();
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_label("material")
+                .selected_text(material.as_deref().unwrap_or("default"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut new_material, None, "default");
+                    for name in self.scene.materials.keys() {
+                        ui.selectable_value(&mut new_material, Some(name.clone()), name.as_str());
+                    }
+                });
+            if ui.button("new").clicked() {
+                let mut i = 1;
+                while self
+                    .scene
+                    .materials
+                    .contains_key(&format!("material {}", i))
+                {
+                    i += 1;
+                }
+                // Starting out as a copy of the current one.
+                let name = format!("material {}", i);
+                let copy = self.scene.material(&self.scene.entities[entity]);
+                self.scene.materials.insert(name.clone(), copy);
+                new_material = Some(name);
+            }
+        });
+        if new_material != material {
+            self.scene.entities[entity].material = new_material.clone();
+            edit = Edit::Structure;
+        }
+
+        let material = match &new_material {
+            Some(name) => self.scene.materials.get_mut(name),
+            None => None,
+        };
+        let Some(material) = material else {
+            return edit;
+        };
+        ui.horizontal(|ui| {
+            if ui
+                .color_edit_button_rgba_unmultiplied(&mut material.color)
+                .changed()
+            {
+                edit = edit.max(Edit::Properties);
+            }
+            ui.label("color");
+        });
+        ui.horizontal(|ui| {
+            let mut texture = material
+                .texture
+                .as_ref()
+                .map(|path| path.display().to_string())
+                .unwrap_or_default();
+            let response = ui.text_edit_singleline(&mut texture);
+            if response.changed() {
+                material.texture = Some(texture)
+                    .filter(|texture| !texture.is_empty())
+                    .map(PathBuf::from);
+            }
+            // The texture is only loaded once it's typed out.
+            if response.lost_focus() {
+                edit = Edit::Structure;
+            }
+            ui.label("texture");
+        });
+        edit
+    }
+
+    fn light_ui(&mut self, ui: &mut Ui, light: usize) -> Edit {
+        let light = &mut self.scene.lights[light];
+        let before = light.clone();
+
+        match light {
+            Light::Point {
+                position,
+                color,
+                radius,
+            } => {
+                vector_row(ui, "position", position, 1.0);
+                color_row(ui, color);
+                ui.horizontal(|ui| {
+                    ui.add(DragValue::new(radius).clamp_range(0.1..=f32::MAX));
+                    ui.label("radius");
+                });
+            }
+            Light::Directional { direction, color } => {
+                vector_row(ui, "direction", direction, 0.01);
+                color_row(ui, color);
+            }
+        }
+
+        if *light != before {
+            Edit::Properties
+        } else {
+            Edit::None
+        }
+    }
+
+    /// Renames `entity`, and its children's parent along with it. Parents are referred to by
+    /// name, so the name has to be one no other entity has.
+    fn rename(&mut self, entity: usize, name: &str) -> Result<(), String> {
+        if name.is_empty() {
+            return Err("the name can't be empty".to_owned());
+        }
+        let taken = self
+            .scene
+            .entities
+            .iter()
+            .enumerate()
+            .any(|(i, other)| i != entity && other.name == name);
+        if taken {
+            return Err(format!(
+                "{:?} is taken, {:?} isn't",
+                name,
+                self.unique_name(name)
+            ));
+        }
+
+        let children = self
+            .graph
+            .children(self.nodes[entity])
+            .iter()
+            .filter_map(|&child| self.graph.item(child).copied())
+            .collect::<Vec<_>>();
+        for child in children {
+            self.scene.entities[child].parent = Some(name.to_owned());
+        }
+        self.scene.entities[entity].name = name.to_owned();
+        self.graph.set_name(self.nodes[entity], name);
+        Ok(())
+    }
+
+    /// `entity` and every entity below it, parents first.
+    fn subtree(&self, entity: usize) -> Vec<usize> {
+        let mut entities = Vec::new();
+        let mut stack = vec![self.nodes[entity]];
+        while let Some(id) = stack.pop() {
+            entities.extend(self.graph.item(id));
+            stack.extend(self.graph.children(id));
+        }
+        entities
+    }
+
+    /// Copies `entity` and everything below it, next to the original.
+    fn duplicate(&mut self, entity: usize) {
+        let mut names = HashMap::new();
+        for i in self.subtree(entity) {
+            let mut copy = self.scene.entities[i].clone();
+            copy.name = self.unique_name(&copy.name);
+            names.insert(self.scene.entities[i].name.clone(), copy.name.clone());
+            // The copy of the top entity keeps its parent, the others move to the copies.
+            if i != entity {
+                copy.parent = copy.parent.map(|parent| names[&parent].clone());
+            }
+            self.scene.entities.push(copy);
+        }
+        let copy = self.scene.entities.len() - names.len();
+        self.selection = Some(Selection::Entity(copy));
+    }
+
+    /// Removes `entity` and everything below it.
+    fn remove(&mut self, entity: usize) {
+        let removed = self.subtree(entity);
+        let mut i = 0;
+        self.scene.entities.retain(|_| {
+            i += 1;
+            !removed.contains(&(i - 1))
+        });
+        self.selection = None;
+    }
+
+    /// `name`, or `name` with the lowest number after it that no entity is called yet.
+    fn unique_name(&self, name: &str) -> String {
+        let taken = |name: &str| self.scene.entities.iter().any(|e| e.name == name);
+        if !taken(name) {
+            return name.to_owned();
+        }
+        (2..)
+            .map(|i| format!("{} {}", name, i))
+            .find(|name| !taken(name))
+            .unwrap()
+    }
+
+    /// Builds the graph again after entities were added or removed.
+    fn rebuild(&mut self) {
+        // The entity being renamed may have moved or be gone.
+        self.name_edit = None;
+        self.graph = SceneGraph::from_scene(&self.scene);
+        let mut nodes = self
+            .graph
+            .depth_first()
+            .filter_map(|id| Some((*self.graph.item(id)?, id)))
+            .collect::<Vec<_>>();
+        nodes.sort_by_key(|&(entity, _)| entity);
+        self.nodes = nodes.into_iter().map(|(_, id)| id).collect();
+    }
+}
+
+fn transform_ui(ui: &mut Ui, transform: &mut Transform) {
+    vector_row(ui, "translation", &mut transform.translation, 1.0);
+    vector_row(ui, "rotation", &mut transform.rotation, 1.0);
+    // A scale of 0 can't be inverted, which the scene rejects.
+    ui.horizontal(|ui| {
+        for value in &mut transform.scale {
+            ui.add(DragValue::new(value).speed(0.01).clamp_range(0.01..=100.0));
+        }
+        ui.label("scale");
+    });
+}
+
+fn vector_row(ui: &mut Ui, label: &str, vector: &mut [f32; 3], speed: f32) {
+    ui.horizontal(|ui| {
+        for value in vector {
+            ui.add(DragValue::new(value).speed(speed));
+        }
+        ui.label(label);
+    });
+}
+
+fn drag_row(ui: &mut Ui, label: &str, value: &mut f32, speed: f32) {
+    ui.horizontal(|ui| {
+        ui.add(DragValue::new(value).speed(speed));
+        ui.label(label);
+    });
+}
+
+fn color_row(ui: &mut Ui, color: &mut [f32; 3]) {
+    ui.horizontal(|ui| {
+        ui.color_edit_button_rgb(color);
+        ui.label("color");
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor() -> SceneEditor {
+        let entity = |name: &str, parent: Option<&str>| Entity {
+            name: name.to_owned(),
+            material: None,
+            parent: parent.map(str::to_owned),
+            mesh: Mesh::Sprite { size: [1.0, 1.0] },
+            transform: Transform::default(),
+        };
+        let scene = Scene {
+            entities: vec![
+                entity("quad", None),
+                entity("badge", Some("quad")),
+                entity("label", Some("quad")),
+                entity("other", None),
+            ],
+            ..Scene::default()
+        };
+        SceneEditor::new(scene, PathBuf::from("scene.toml"))
+    }
+
+    /// `quad` with `badge` and `label` below it, `dot` below `badge`, and `other` on its own.
+    fn nested() -> SceneEditor {
+        let mut editor = editor();
+        let mut dot = editor.scene.entities[1].clone();
+        dot.name = "dot".to_owned();
+        dot.parent = Some("badge".to_owned());
+        editor.scene.entities.insert(2, dot);
+        editor.rebuild();
+        editor
+    }
+
+    fn names(editor: &SceneEditor) -> Vec<&str> {
+        editor
+            .scene()
+            .entities
+            .iter()
+            .map(|entity| entity.name.as_str())
+            .collect()
+    }
+
+    fn parents(editor: &SceneEditor) -> Vec<Option<&str>> {
+        editor
+            .scene()
+            .entities
+            .iter()
+            .map(|entity| entity.parent.as_deref())
+            .collect()
+    }
+
+    #[test]
+    fn rename_moves_the_children_along() {
+        let mut editor = editor();
+        editor.rename(0, "card").unwrap();
+        assert_eq!(editor.scene().entities[0].name, "card");
+        assert_eq!(parents(&editor), [None, Some("card"), Some("card"), None]);
+        assert_eq!(editor.graph.name(editor.nodes[0]), Some("card"));
+        assert_eq!(editor.scene().validate(), Ok(()));
+    }
+
+    #[test]
+    fn rename_rejects_taken_and_empty_names() {
+        let mut editor = editor();
+        let error = editor.rename(0, "other").unwrap_err();
+        assert!(error.contains("\"other 2\""), "{}", error);
+        editor.rename(0, "").unwrap_err();
+
+        assert_eq!(editor.scene().entities[0].name, "quad");
+        assert_eq!(parents(&editor), [None, Some("quad"), Some("quad"), None]);
+        // Keeping the same name is fine.
+        editor.rename(0, "quad").unwrap();
+    }
+
+    #[test]
+    fn duplicate_copies_the_whole_subtree() {
+        let mut editor = nested();
+        editor.duplicate(0);
+        editor.rebuild();
+
+        assert_eq!(
+            names(&editor),
+            ["quad", "badge", "dot", "label", "other", "quad 2", "label 2", "badge 2", "dot 2"]
+        );
+        // The copies hang off the copies, the originals are left alone.
+        assert_eq!(
+            parents(&editor),
+            [
+                None,
+                Some("quad"),
+                Some("badge"),
+                Some("quad"),
+                None,
+                None,
+                Some("quad 2"),
+                Some("quad 2"),
+                Some("badge 2"),
+            ]
+        );
+        assert_eq!(editor.selection(), Some(Selection::Entity(5)));
+        assert_eq!(editor.scene().validate(), Ok(()));
+    }
+
+    #[test]
+    fn duplicate_keeps_the_parent_of_the_top_entity() {
+        let mut editor = nested();
+        editor.duplicate(1);
+        editor.rebuild();
+
+        assert_eq!(names(&editor)[5..], ["badge 2", "dot 2"]);
+        assert_eq!(parents(&editor)[5..], [Some("quad"), Some("badge 2")]);
+        assert_eq!(editor.selection(), Some(Selection::Entity(5)));
+        assert_eq!(editor.scene().validate(), Ok(()));
+    }
+
+    #[test]
+    fn remove_takes_the_whole_subtree() {
+        let mut editor = nested();
+        editor.select(Some(Selection::Entity(1)));
+        editor.remove(1);
+        editor.rebuild();
+
+        assert_eq!(names(&editor), ["quad", "label", "other"]);
+        assert_eq!(parents(&editor), [None, Some("quad"), None]);
+        assert_eq!(editor.selection(), None);
+        assert_eq!(editor.scene().validate(), Ok(()));
+
+        editor.remove(0);
+        editor.rebuild();
+        assert_eq!(names(&editor), ["other"]);
+        assert_eq!(parents(&editor), [None]);
+        assert_eq!(editor.scene().validate(), Ok(()));
+    }
+}
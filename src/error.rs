@@ -0,0 +1,168 @@
+//! The crate's error type, and the policy the frame loops use to react to a failed frame.
+
+use std::fmt;
+
+use vulkano::device::DeviceCreationError;
+use vulkano::instance::InstanceCreationError;
+use vulkano::swapchain::{AcquireError, SwapchainCreationError};
+use vulkano::sync::FlushError;
+use vulkano::LoadingError;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+pub enum Error {
+    Library(LoadingError),
+    Instance(InstanceCreationError),
+    Surface(vulkano_win::CreationError),
+    Device(DeviceCreationError),
+    Swapchain(SwapchainCreationError),
+    Acquire(AcquireError),
+    Flush(FlushError),
+    /// Nothing on this machine supports what the renderer needs.
+    Unsupported(&'static str),
+    /// What the demo was given to draw can't be used, like a scene or a texture.
+    Content(String),
+    /// The validation layer reported this many errors.
+    Validation(u32),
+    /// A shader module has no entry point with this name.
+    MissingEntryPoint(&'static str),
+    /// Any other vulkano error, with a description of what was being done.
+    Vulkan {
+        context: &'static str,
+        source: Box<dyn std::error::Error>,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Library(e) => write!(f, "there's no Vulkan library: {}", e),
+            Self::Instance(e) => write!(f, "can't create instance: {}", e),
+            Self::Surface(e) => write!(f, "can't create surface: {}", e),
+            Self::Device(e) => write!(f, "can't create device: {}", e),
+            Self::Swapchain(e) => write!(f, "can't create swapchain: {}", e),
+            Self::Acquire(e) => write!(f, "can't acquire next image: {}", e),
+            Self::Flush(e) => write!(f, "can't flush future: {}", e),
+            Self::Unsupported(what) => write!(f, "{}", what),
+            Self::Content(what) => write!(f, "{}", what),
+            Self::Validation(errors) => write!(f, "validation reported {} error(s)", errors),
+            Self::MissingEntryPoint(name) => {
+                write!(f, "shader module has no entry point named `{}`", name)
+            }
+            Self::Vulkan { context, source } => write!(f, "{}: {}", context, source),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Library(e) => Some(e),
+            Self::Instance(e) => Some(e),
+            Self::Surface(e) => Some(e),
+            Self::Device(e) => Some(e),
+            Self::Swapchain(e) => Some(e),
+            Self::Acquire(e) => Some(e),
+            Self::Flush(e) => Some(e),
+            Self::Unsupported(_)
+            | Self::Content(_)
+            | Self::Validation(_)
+            | Self::MissingEntryPoint(_) => None,
+            Self::Vulkan { source, .. } => Some(source.as_ref()),
+        }
+    }
+}
+
+macro_rules! impl_from {
+    ($($variant:ident($ty:ty)),* $(,)?) => {
+        $(
+            impl From<$ty> for Error {
+                fn from(e: $ty) -> Self {
+                    Self::$variant(e)
+                }
+            }
+        )*
+    };
+}
+
+impl_from!(
+    Library(LoadingError),
+    Instance(InstanceCreationError),
+    Surface(vulkano_win::CreationError),
+    Device(DeviceCreationError),
+    Swapchain(SwapchainCreationError),
+    Acquire(AcquireError),
+    Flush(FlushError),
+);
+
+/// Attaches a description of what was being done to an error, like the `expect` messages it
+/// replaces.
+pub trait Context<T> {
+    fn context(self, context: &'static str) -> Result<T>;
+}
+
+impl<T, E: std::error::Error + 'static> Context<T> for std::result::Result<T, E> {
+    fn context(self, context: &'static str) -> Result<T> {
+        self.map_err(|e| Error::Vulkan {
+            context,
+            source: Box::new(e),
+        })
+    }
+}
+
+/// What the frame loop should do after a frame failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Recovery {
+    /// The swapchain no longer matches the surface, recreate it and try again next frame.
+    RecreateSwapchain,
+    /// The surface is gone, create a new one for the same window along with its swapchain.
+    RecreateSurface,
+    /// The device is gone, recreate it and everything that was created from it.
+    RecreateDevice,
+    /// Nothing needs fixing, drop this frame.
+    SkipFrame,
+    /// Rendering can't continue.
+    Fatal,
+}
+
+impl Error {
+    pub fn recovery(&self) -> Recovery {
+        match self {
+            Self::Swapchain(SwapchainCreationError::ImageExtentNotSupported { .. }) => {
+                Recovery::SkipFrame
+            }
+            Self::Acquire(AcquireError::OutOfDate)
+            | Self::Acquire(AcquireError::FullScreenExclusiveModeLost)
+            | Self::Flush(FlushError::OutOfDate)
+            | Self::Flush(FlushError::FullScreenExclusiveModeLost) => Recovery::RecreateSwapchain,
+            Self::Swapchain(SwapchainCreationError::SurfaceLost)
+            | Self::Acquire(AcquireError::SurfaceLost)
+            | Self::Flush(FlushError::SurfaceLost) => Recovery::RecreateSurface,
+            Self::Swapchain(SwapchainCreationError::DeviceLost)
+            | Self::Acquire(AcquireError::DeviceLost)
+            | Self::Flush(FlushError::DeviceLost) => Recovery::RecreateDevice,
+            Self::Acquire(AcquireError::Timeout) | Self::Flush(FlushError::Timeout) => {
+                Recovery::SkipFrame
+            }
+            // Anything else that went wrong while submitting only affects this frame.
+            Self::Flush(_) => Recovery::SkipFrame,
+            _ => Recovery::Fatal,
+        }
+    }
+}
+
+/// Logs an error that happened while rendering a frame and decides how to carry on.
+pub fn frame_error(error: impl Into<Error>) -> Recovery {
+    let error = error.into();
+    let recovery = error.recovery();
+    match recovery {
+        // This is mostly out of date swapchains, which happen on every resize.
+        Recovery::RecreateSwapchain => log::debug!("{}, recreating swapchain", error),
+        Recovery::RecreateSurface => log::warn!("{}, recreating surface", error),
+        Recovery::RecreateDevice => log::error!("{}, recreating device", error),
+        Recovery::SkipFrame => log::warn!("{}, skipping frame", error),
+        Recovery::Fatal => log::error!("{}, stopping", error),
+    }
+    recovery
+}
@@ -0,0 +1,87 @@
+//! Simulated device and surface loss, so the recovery paths can be exercised without waiting
+//! for a driver to actually lose the device.
+//!
+//! Faults are scheduled with an environment variable holding a comma separated list of
+//! `<fault>@<frame>`, for example `VULKAN_RUST_INJECT_FAULT=surface-lost@120,device-lost@300`.
+//! On those frames the demo reports the fault instead of drawing, as if the flush had failed.
+
+use vulkano::sync::FlushError;
+
+use crate::error::Error;
+
+pub const FAULT_ENV: &str = "VULKAN_RUST_INJECT_FAULT";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    DeviceLost,
+    SurfaceLost,
+}
+
+impl From<Fault> for Error {
+    fn from(fault: Fault) -> Self {
+        match fault {
+            Fault::DeviceLost => Error::Flush(FlushError::DeviceLost),
+            Fault::SurfaceLost => Error::Flush(FlushError::SurfaceLost),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct FaultInjector {
+    schedule: Vec<(u64, Fault)>,
+    frame: u64,
+}
+
+impl FaultInjector {
+    /// Injects each fault of `schedule` on its frame, the first frame being 1.
+    pub fn new(schedule: Vec<(u64, Fault)>) -> Self {
+        Self { schedule, frame: 0 }
+    }
+
+    /// Reads the schedule from [`FAULT_ENV`]. Entries that can't be parsed are reported and
+    /// skipped.
+    pub fn from_env() -> Self {
+        let Ok(value) = std::env::var(FAULT_ENV) else {
+            return Self::default();
+        };
+
+        let schedule = value
+            .split(',')
+            .filter(|entry| !entry.trim().is_empty())
+            .filter_map(|entry| {
+                let parsed = parse_entry(entry.trim());
+                if parsed.is_none() {
+                    log::warn!("ignoring {} entry {:?}", FAULT_ENV, entry);
+                }
+                parsed
+            })
+            .collect();
+
+        Self::new(schedule)
+    }
+
+    /// Advances to the next frame and returns the fault scheduled for it, if any.
+    pub fn next_frame(&mut self) -> Option<Fault> {
+        self.frame += 1;
+        let frame = self.frame;
+        let fault = self
+            .schedule
+            .iter()
+            .find(|(at, _)| *at == frame)
+            .map(|&(_, fault)| fault);
+        if let Some(fault) = fault {
+            log::warn!("injecting {:?} at frame {}", fault, frame);
+        }
+        fault
+    }
+}
+
+fn parse_entry(entry: &str) -> Option<(u64, Fault)> {
+    let (fault, frame) = entry.split_once('@')?;
+    let fault = match fault {
+        "device-lost" => Fault::DeviceLost,
+        "surface-lost" => Fault::SurfaceLost,
+        _ => return None,
+    };
+    Some((frame.parse().ok()?, fault))
+}
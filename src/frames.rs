@@ -0,0 +1,134 @@
+//! Several frames in flight, each with its own fence and resources, so the CPU records the next
+//! frame while the GPU is still executing the previous ones.
+//!
+//! A frame's resources are only handed out again once the fence of the last submission that
+//! used them has signaled, so they can be written without synchronizing with the GPU. The
+//! number of frames is read from [`FRAMES_IN_FLIGHT_ENV`], and defaults to
+//! [`DEFAULT_FRAMES_IN_FLIGHT`].
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use vulkano::device::Device;
+use vulkano::sync::{self, FenceSignalFuture, GpuFuture};
+
+use crate::error::Result;
+
+pub const FRAMES_IN_FLIGHT_ENV: &str = "VULKAN_RUST_FRAMES_IN_FLIGHT";
+pub const DEFAULT_FRAMES_IN_FLIGHT: usize = 2;
+
+/// More than this only adds latency.
+const MAX_FRAMES_IN_FLIGHT: usize = 8;
+
+/// The fence signaled when a frame's submission has finished executing.
+pub type FrameFence = FenceSignalFuture<Box<dyn GpuFuture>>;
+
+/// The number of frames in flight asked for in [`FRAMES_IN_FLIGHT_ENV`].
+pub fn frames_in_flight() -> usize {
+    let Ok(value) = std::env::var(FRAMES_IN_FLIGHT_ENV) else {
+        return DEFAULT_FRAMES_IN_FLIGHT;
+    };
+
+    match value.parse() {
+        Ok(count @ 1..=MAX_FRAMES_IN_FLIGHT) => count,
+        _ => {
+            log::warn!(
+                "{} must be between 1 and {}, using {}",
+                FRAMES_IN_FLIGHT_ENV,
+                MAX_FRAMES_IN_FLIGHT,
+                DEFAULT_FRAMES_IN_FLIGHT
+            );
+            DEFAULT_FRAMES_IN_FLIGHT
+        }
+    }
+}
+
+pub struct FramesInFlight<T> {
+    device: Arc<Device>,
+    frames: Vec<Frame<T>>,
+    current: usize,
+    /// The fence of the last submission, which the next one has to follow.
+    previous: Option<Arc<FrameFence>>,
+}
+
+struct Frame<T> {
+    fence: Option<Arc<FrameFence>>,
+    resources: T,
+}
+
+impl<T> FramesInFlight<T> {
+    /// Creates `count` frames, with the resources `create` returns for each index.
+    pub fn new(
+        device: Arc<Device>,
+        count: usize,
+        mut create: impl FnMut(usize) -> Result<T>,
+    ) -> Result<Self> {
+        let frames = (0..count)
+            .map(|i| {
+                Ok(Frame {
+                    fence: None,
+                    resources: create(i)?,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            device,
+            frames,
+            current: 0,
+            previous: None,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Moves on to the next frame, and waits until the GPU is done with its resources. Returns
+    /// how long that took, which is how far ahead of the GPU the CPU was.
+    pub fn next(&mut self) -> Result<Duration> {
+        self.current = (self.current + 1) % self.frames.len();
+
+        let start = Instant::now();
+        if let Some(fence) = self.frames[self.current].fence.take() {
+            fence.wait(None)?;
+        }
+        Ok(start.elapsed())
+    }
+
+    /// The index of the current frame, for per-frame resources kept elsewhere.
+    pub fn index(&self) -> usize {
+        self.current
+    }
+
+    /// The resources of the current frame.
+    pub fn current(&self) -> &T {
+        &self.frames[self.current].resources
+    }
+
+    pub fn current_mut(&mut self) -> &mut T {
+        &mut self.frames[self.current].resources
+    }
+
+    /// The resources of every frame, including the ones still in use by the GPU. Only replace
+    /// what they hold, the old objects are kept alive by the submissions using them.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.frames.iter_mut().map(|frame| &mut frame.resources)
+    }
+
+    /// What the current frame's submission has to be chained after.
+    pub fn previous_future(&mut self) -> Box<dyn GpuFuture> {
+        match self.previous.take() {
+            Some(fence) => fence.boxed(),
+            None => sync::now(self.device.clone()).boxed(),
+        }
+    }
+
+    /// Records the fence of the current frame's submission. When the submission failed, the next
+    /// frame starts a new chain instead.
+    pub fn submitted(&mut self, fence: Option<FrameFence>) {
+        let fence = fence.map(Arc::new);
+        self.frames[self.current].fence = fence.clone();
+        self.previous = fence;
+    }
+}
@@ -0,0 +1,813 @@
+//! Handles to translate, rotate and scale the entity selected in a [`SceneEditor`] with the
+//! mouse, drawn over the scene.
+//!
+//! Handles are picked by casting a ray from the camera through the cursor. Each handle changes
+//! one component of the entity's transform: translation handles follow the axes of the parent,
+//! rotation rings are the axes the Euler angles turn around, and scale handles are the entity's
+//! own axes. They keep the same size on screen however far the entity is. Every finished drag
+//! can be undone.
+
+use bytemuck::{Pod, Zeroable};
+use cgmath::{InnerSpace, Matrix4, Rad, SquareMatrix, Vector3, Vector4};
+use egui::DragValue;
+use vulkano::impl_vertex;
+
+use crate::editor::{SceneEditor, Selection};
+use crate::present::OutputEncoding;
+use crate::scene::Transform;
+
+/// How long a handle is, in pixels.
+const HANDLE_PIXELS: f32 = 100.0;
+
+/// How close to a handle the cursor has to be to pick it, in pixels.
+const PICK_PIXELS: f32 = 8.0;
+
+const THICKNESS_PIXELS: f32 = 3.0;
+
+const RING_SEGMENTS: usize = 48;
+
+const AXIS_COLORS: [[f32; 4]; 3] = [
+    [1.0, 0.0, 0.0, 1.0],
+    [0.0, 1.0, 0.0, 1.0],
+    [0.0, 0.0, 1.0, 1.0],
+];
+
+const ACTIVE_COLOR: [f32; 4] = [1.0, 1.0, 0.0, 1.0];
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
+pub struct OverlayVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 4],
+}
+
+impl_vertex!(OverlayVertex, position, color);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GizmoMode {
+    Translate,
+    Rotate,
+    Scale,
+}
+
+/// Rounding of dragged values, to multiples of the step of each mode.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Snap {
+    pub enabled: bool,
+    /// In the parent's units.
+    pub translation: f32,
+    /// In degrees.
+    pub rotation: f32,
+    pub scale: f32,
+}
+
+impl Default for Snap {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            translation: 10.0,
+            rotation: 15.0,
+            scale: 0.1,
+        }
+    }
+}
+
+/// A half line in world space.
+#[derive(Clone, Copy, Debug)]
+pub struct Ray {
+    pub origin: Vector3<f32>,
+    /// Of length 1.
+    pub direction: Vector3<f32>,
+}
+
+impl Ray {
+    /// The ray through pixel `cursor`, counted from the top left corner of a window
+    /// `dimensions` pixels big. `None` when `view_projection` can't be inverted.
+    pub fn from_cursor(
+        cursor: [f32; 2],
+        dimensions: [u32; 2],
+        view_projection: Matrix4<f32>,
+    ) -> Option<Self> {
+        let inverse = view_projection.invert()?;
+        let x = 2.0 * cursor[0] / dimensions[0] as f32 - 1.0;
+        let y = 2.0 * cursor[1] / dimensions[1] as f32 - 1.0;
+        // Any two depths give two points on the ray.
+        let unproject = |z: f32| {
+            let point = inverse * Vector4::new(x, y, z, 1.0);
+            point.truncate() / point.w
+        };
+        let near = unproject(0.0);
+        let far = unproject(0.5);
+
+        Some(Self {
+            origin: near,
+            direction: (far - near).normalize(),
+        })
+    }
+
+    pub fn at(&self, t: f32) -> Vector3<f32> {
+        self.origin + self.direction * t
+    }
+
+    /// Where the ray crosses the plane through `point` facing `normal`, `None` when it runs
+    /// along the plane.
+    pub fn intersect_plane(
+        &self,
+        point: Vector3<f32>,
+        normal: Vector3<f32>,
+    ) -> Option<Vector3<f32>> {
+        let denominator = normal.dot(self.direction);
+        if denominator.abs() < 1e-6 {
+            return None;
+        }
+        Some(self.at(normal.dot(point - self.origin) / denominator))
+    }
+
+    /// The point of the line through `point` along the unit vector `axis` that comes closest
+    /// to the ray, as a distance from `point`, and how close that is. `None` when the line is
+    /// parallel to the ray.
+    pub fn closest_on_line(&self, point: Vector3<f32>, axis: Vector3<f32>) -> Option<(f32, f32)> {
+        let b = self.direction.dot(axis);
+        let denominator = 1.0 - b * b;
+        if denominator < 1e-6 {
+            return None;
+        }
+        let w = self.origin - point;
+        let d = self.direction.dot(w);
+        let e = axis.dot(w);
+        let on_ray = (b * e - d) / denominator;
+        let on_line = (e - b * d) / denominator;
+        let distance = (self.at(on_ray) - (point + axis * on_line)).magnitude();
+        Some((on_line, distance))
+    }
+}
+
+/// Where the handles of an entity are.
+#[derive(Clone, Copy, Debug)]
+struct Handles {
+    center: Vector3<f32>,
+    /// Unit vectors.
+    axes: [Vector3<f32>; 3],
+    /// How long each axis was before it was normalized, to turn world distances back into
+    /// local ones.
+    units: [f32; 3],
+    /// World units per pixel at the center.
+    pixel: f32,
+    /// From the camera towards the center.
+    view: Vector3<f32>,
+}
+
+impl Handles {
+    fn length(&self) -> f32 {
+        HANDLE_PIXELS * self.pixel
+    }
+}
+
+/// Where a drag started.
+#[derive(Clone, Copy, Debug)]
+struct Drag {
+    entity: usize,
+    axis: usize,
+    handles: Handles,
+    start: Transform,
+    /// Along the axis for translation and scale, in the plane of the ring for rotation.
+    grab: Grab,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Grab {
+    Along(f32),
+    Around(Vector3<f32>),
+}
+
+pub struct Gizmo {
+    pub mode: GizmoMode,
+    pub snap: Snap,
+    hovered: Option<usize>,
+    drag: Option<Drag>,
+    /// Entities and the transforms they had before each drag, the last one on top.
+    undo: Vec<(usize, Transform)>,
+    redo: Vec<(usize, Transform)>,
+}
+
+impl Default for Gizmo {
+    fn default() -> Self {
+        Self {
+            mode: GizmoMode::Translate,
+            snap: Snap::default(),
+            hovered: None,
+            drag: None,
+            undo: Vec::new(),
+            redo: Vec::new(),
+        }
+    }
+}
+
+impl Gizmo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    /// Grabs the handle under `cursor`. Returns whether there was one, in which case the
+    /// press shouldn't go anywhere else.
+    pub fn press(
+        &mut self,
+        editor: &mut SceneEditor,
+        cursor: [f32; 2],
+        dimensions: [u32; 2],
+        view_projection: Matrix4<f32>,
+    ) -> bool {
+        let Some((entity, handles)) = self.handles(editor, dimensions, view_projection) else {
+            return false;
+        };
+        let Some(ray) = Ray::from_cursor(cursor, dimensions, view_projection) else {
+            return false;
+        };
+        let Some(axis) = self.pick(&handles, &ray) else {
+            return false;
+        };
+
+        let grab = match self.mode {
+            GizmoMode::Translate | GizmoMode::Scale => ray
+                .closest_on_line(handles.center, handles.axes[axis])
+                .map(|(along, _)| Grab::Along(along)),
+            GizmoMode::Rotate => ray
+                .intersect_plane(handles.center, handles.axes[axis])
+                .map(Grab::Around),
+        };
+        let Some(grab) = grab else {
+            return false;
+        };
+
+        self.drag = Some(Drag {
+            entity,
+            axis,
+            handles,
+            start: editor.scene().entities[entity].transform,
+            grab,
+        });
+        true
+    }
+
+    /// Drags the grabbed handle, or finds the one under the cursor to highlight it.
+    pub fn cursor_moved(
+        &mut self,
+        editor: &mut SceneEditor,
+        cursor: [f32; 2],
+        dimensions: [u32; 2],
+        view_projection: Matrix4<f32>,
+    ) {
+        let Some(ray) = Ray::from_cursor(cursor, dimensions, view_projection) else {
+            return;
+        };
+        let Some(drag) = self.drag else {
+            self.hovered = self
+                .handles(editor, dimensions, view_projection)
+                .and_then(|(_, handles)| self.pick(&handles, &ray));
+            return;
+        };
+        if drag.entity >= editor.scene().entities.len() {
+            self.drag = None;
+            return;
+        }
+
+        let Drag {
+            axis,
+            handles,
+            start,
+            ..
+        } = drag;
+        let mut transform = start;
+        match (self.mode, drag.grab) {
+            (GizmoMode::Translate, Grab::Along(grab)) => {
+                let Some((along, _)) = ray.closest_on_line(handles.center, handles.axes[axis])
+                else {
+                    return;
+                };
+                transform.translation[axis] = self.snapped(
+                    start.translation[axis] + (along - grab) / handles.units[axis],
+                    self.snap.translation,
+                );
+            }
+            (GizmoMode::Scale, Grab::Along(grab)) => {
+                let Some((along, _)) = ray.closest_on_line(handles.center, handles.axes[axis])
+                else {
+                    return;
+                };
+                if grab.abs() < 1e-6 {
+                    return;
+                }
+                let scale = self.snapped(start.scale[axis] * along / grab, self.snap.scale);
+                // The scene can't have a scale of 0.
+                transform.scale[axis] = scale.max(0.01);
+            }
+            (GizmoMode::Rotate, Grab::Around(grab)) => {
+                let normal = handles.axes[axis];
+                let Some(hit) = ray.intersect_plane(handles.center, normal) else {
+                    return;
+                };
+                let (from, to) = (grab - handles.center, hit - handles.center);
+                let angle = Rad(normal.dot(from.cross(to)).atan2(from.dot(to)));
+                let degrees = cgmath::Deg::from(angle).0;
+                transform.rotation[axis] =
+                    self.snapped(start.rotation[axis] + degrees, self.snap.rotation);
+            }
+            // The mode changed during the drag.
+            _ => return,
+        }
+        editor.set_transform(drag.entity, transform);
+    }
+
+    /// Lets go of the grabbed handle. Returns whether one was grabbed.
+    pub fn release(&mut self, editor: &SceneEditor) -> bool {
+        let Some(drag) = self.drag.take() else {
+            return false;
+        };
+        let moved = editor
+            .scene()
+            .entities
+            .get(drag.entity)
+            .map_or(false, |entity| entity.transform != drag.start);
+        if moved {
+            self.undo.push((drag.entity, drag.start));
+            self.redo.clear();
+        }
+        true
+    }
+
+    pub fn undo(&mut self, editor: &mut SceneEditor) {
+        if let Some(step) = self.undo.pop() {
+            self.redo.extend(Self::restore(editor, step));
+        }
+    }
+
+    pub fn redo(&mut self, editor: &mut SceneEditor) {
+        if let Some(step) = self.redo.pop() {
+            self.undo.extend(Self::restore(editor, step));
+        }
+    }
+
+    /// Forgets what can be undone, after entities were added or removed and the indices in
+    /// it may point at other entities.
+    pub fn clear_history(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.drag = None;
+    }
+
+    /// Puts an entity back to a transform and selects it. Returns the step that undoes this.
+    fn restore(
+        editor: &mut SceneEditor,
+        (entity, transform): (usize, Transform),
+    ) -> Option<(usize, Transform)> {
+        let current = editor.scene().entities.get(entity)?.transform;
+        editor.set_transform(entity, transform);
+        editor.select(Some(Selection::Entity(entity)));
+        Some((entity, current))
+    }
+
+    /// Shows the mode, snapping and undo in a "Gizmo" window.
+    pub fn ui(&mut self, ctx: &egui::Context, editor: &mut SceneEditor) {
+        egui::Window::new("Gizmo")
+            .default_width(250.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut self.mode, GizmoMode::Translate, "translate (w)");
+                    ui.selectable_value(&mut self.mode, GizmoMode::Rotate, "rotate (e)");
+                    ui.selectable_value(&mut self.mode, GizmoMode::Scale, "scale (r)");
+                });
+
+                ui.checkbox(&mut self.snap.enabled, "snap");
+                ui.add_enabled_ui(self.snap.enabled, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            DragValue::new(&mut self.snap.translation)
+                                .clamp_range(0.001..=f32::MAX),
+                        );
+                        ui.label("translation");
+                    });
+                    ui.horizontal(|ui| {
+                        ui.add(DragValue::new(&mut self.snap.rotation).clamp_range(0.1..=180.0));
+                        ui.label("rotation");
+                    });
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            DragValue::new(&mut self.snap.scale)
+                                .speed(0.01)
+                                .clamp_range(0.001..=f32::MAX),
+                        );
+                        ui.label("scale");
+                    });
+                });
+
+                ui.horizontal(|ui| {
+                    let undo = egui::Button::new(format!("undo ({})", self.undo.len()));
+                    if ui.add_enabled(!self.undo.is_empty(), undo).clicked() {
+                        self.undo(editor);
+                    }
+                    let redo = egui::Button::new(format!("redo ({})", self.redo.len()));
+                    if ui.add_enabled(!self.redo.is_empty(), redo).clicked() {
+                        self.redo(editor);
+                    }
+                });
+            });
+    }
+
+    /// The triangles of the handles of the selected entity, with colors already in `encoding`.
+    pub fn vertices(
+        &self,
+        editor: &mut SceneEditor,
+        dimensions: [u32; 2],
+        view_projection: Matrix4<f32>,
+        encoding: OutputEncoding,
+    ) -> Vec<OverlayVertex> {
+        let mut vertices = Vec::new();
+        let Some((_, handles)) = self.handles(editor, dimensions, view_projection) else {
+            return vertices;
+        };
+        let active = self.drag.map(|drag| drag.axis).or(self.hovered);
+        let length = handles.length();
+        let thickness = THICKNESS_PIXELS * handles.pixel;
+
+        for (axis, direction) in handles.axes.into_iter().enumerate() {
+            let color = encoding.encode(if active == Some(axis) {
+                ACTIVE_COLOR
+            } else {
+                AXIS_COLORS[axis]
+            });
+            let mut line = |from: Vector3<f32>, to: Vector3<f32>, width: f32| {
+                thick_line(&mut vertices, from, to, width, handles.view, color)
+            };
+            let center = handles.center;
+            let end = center + direction * length;
+
+            match self.mode {
+                GizmoMode::Translate => {
+                    line(center, end, thickness);
+                    // An arrow head, a line that narrows to a point.
+                    let tip = end + direction * length * 0.2;
+                    let steps = 6;
+                    for i in 0..steps {
+                        let from = end + (tip - end) * (i as f32 / steps as f32);
+                        let to = end + (tip - end) * ((i + 1) as f32 / steps as f32);
+                        let width = thickness * 4.0 * (1.0 - i as f32 / steps as f32);
+                        line(from, to, width);
+                    }
+                }
+                GizmoMode::Scale => {
+                    line(center, end, thickness);
+                    // A box at the end.
+                    line(end, end + direction * length * 0.15, length * 0.15);
+                }
+                GizmoMode::Rotate => {
+                    // A vector across the ring's plane to start from.
+                    let other = if direction.x.abs() < 0.9 {
+                        Vector3::unit_x()
+                    } else {
+                        Vector3::unit_y()
+                    };
+                    let u = direction.cross(other).normalize();
+                    let v = direction.cross(u);
+                    let point = |i: usize| {
+                        let angle = i as f32 / RING_SEGMENTS as f32 * std::f32::consts::TAU;
+                        center + (u * angle.cos() + v * angle.sin()) * length
+                    };
+                    for i in 0..RING_SEGMENTS {
+                        line(point(i), point(i + 1), thickness);
+                    }
+                }
+            }
+        }
+
+        vertices
+    }
+
+    /// The selected entity and its handles for the current mode.
+    fn handles(
+        &self,
+        editor: &mut SceneEditor,
+        dimensions: [u32; 2],
+        view_projection: Matrix4<f32>,
+    ) -> Option<(usize, Handles)> {
+        let Some(Selection::Entity(entity)) = editor.selection() else {
+            return None;
+        };
+        if entity >= editor.scene().entities.len() {
+            return None;
+        }
+        let world = editor.world(entity);
+        let parent = editor.parent_world(entity);
+        let transform = editor.scene().entities[entity].transform;
+        let center = world.w.truncate();
+
+        let axes = match self.mode {
+            GizmoMode::Translate => [parent.x, parent.y, parent.z].map(|axis| axis.truncate()),
+            // The Euler angles turn around Z, then the Y that leaves, then the X that leaves.
+            GizmoMode::Rotate => {
+                let z = Matrix4::from_angle_z(cgmath::Deg(transform.rotation[2]));
+                let y = z * Matrix4::from_angle_y(cgmath::Deg(transform.rotation[1]));
+                [
+                    (parent * y).x.truncate(),
+                    (parent * z).y.truncate(),
+                    parent.z.truncate(),
+                ]
+            }
+            GizmoMode::Scale => [world.x, world.y, world.z].map(|axis| axis.truncate()),
+        };
+        let units = axes.map(|axis| axis.magnitude());
+        if units.iter().any(|&unit| unit < 1e-6) {
+            return None;
+        }
+
+        // The world size of a pixel at the center, measured in the plane facing the camera.
+        let clip = view_projection * center.extend(1.0);
+        if clip.w.abs() < 1e-6 {
+            return None;
+        }
+        let screen = [
+            (clip.x / clip.w + 1.0) * 0.5 * dimensions[0] as f32,
+            (clip.y / clip.w + 1.0) * 0.5 * dimensions[1] as f32,
+        ];
+        let ray = Ray::from_cursor(screen, dimensions, view_projection)?;
+        let beside = Ray::from_cursor([screen[0] + 1.0, screen[1]], dimensions, view_projection)?;
+        let pixel = (beside.intersect_plane(center, ray.direction)? - center).magnitude();
+
+        Some((
+            entity,
+            Handles {
+                center,
+                axes: [0, 1, 2].map(|i| axes[i] / units[i]),
+                units,
+                pixel,
+                view: ray.direction,
+            },
+        ))
+    }
+
+    /// The handle `ray` points at, the closest one when it's near several.
+    fn pick(&self, handles: &Handles, ray: &Ray) -> Option<usize> {
+        let length = handles.length();
+        let tolerance = PICK_PIXELS * handles.pixel;
+
+        let distances = handles.axes.map(|axis| match self.mode {
+            GizmoMode::Translate | GizmoMode::Scale => ray
+                .closest_on_line(handles.center, axis)
+                .filter(|&(along, _)| along >= 0.0 && along <= length * 1.2)
+                .map(|(_, distance)| distance),
+            GizmoMode::Rotate => ray
+                .intersect_plane(handles.center, axis)
+                .map(|hit| ((hit - handles.center).magnitude() - length).abs()),
+        });
+
+        (0..3)
+            .filter_map(|axis| Some((axis, distances[axis]?)))
+            .filter(|&(_, distance)| distance <= tolerance)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(axis, _)| axis)
+    }
+
+    fn snapped(&self, value: f32, step: f32) -> f32 {
+        if self.snap.enabled {
+            (value / step).round() * step
+        } else {
+            value
+        }
+    }
+}
+
+/// Two triangles from `from` to `to`, `width` wide and facing the camera.
+fn thick_line(
+    vertices: &mut Vec<OverlayVertex>,
+    from: Vector3<f32>,
+    to: Vector3<f32>,
+    width: f32,
+    view: Vector3<f32>,
+    color: [f32; 4],
+) {
+    let side = (to - from).cross(view);
+    // Lines pointing at the camera have no width to show.
+    if side.magnitude2() < 1e-12 {
+        return;
+    }
+    let side = side.normalize() * width * 0.5;
+    let corners = [from - side, from + side, to + side, to - side];
+    for i in [0, 1, 2, 2, 3, 0] {
+        vertices.push(OverlayVertex {
+            position: corners[i].into(),
+            color,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::scene::{Entity, Mesh, Scene};
+
+    /// With an identity view projection, a pixel of a window this big is 0.01 units across and
+    /// every ray points along +Z.
+    const DIMENSIONS: [u32; 2] = [200, 200];
+
+    fn view_projection() -> Matrix4<f32> {
+        Matrix4::identity()
+    }
+
+    /// The cursor over the point of the z = 0 plane at `x`, `y`.
+    fn cursor(x: f32, y: f32) -> [f32; 2] {
+        [(x + 1.0) * 100.0, (y + 1.0) * 100.0]
+    }
+
+    /// A scene with one entity at the origin, selected.
+    fn editor() -> SceneEditor {
+        let scene = Scene {
+            entities: vec![Entity {
+                name: "quad".to_owned(),
+                material: None,
+                parent: None,
+                mesh: Mesh::Sprite { size: [1.0, 1.0] },
+                transform: Transform::default(),
+            }],
+            ..Scene::default()
+        };
+        let mut editor = SceneEditor::new(scene, PathBuf::from("scene.toml"));
+        editor.select(Some(Selection::Entity(0)));
+        editor
+    }
+
+    fn assert_near(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+    }
+
+    /// Drags the handle under `from` to `to`.
+    fn drag(gizmo: &mut Gizmo, editor: &mut SceneEditor, from: [f32; 2], to: [f32; 2]) {
+        assert!(gizmo.press(editor, from, DIMENSIONS, view_projection()));
+        gizmo.cursor_moved(editor, to, DIMENSIONS, view_projection());
+        assert!(gizmo.release(editor));
+    }
+
+    #[test]
+    fn rays_go_through_the_cursor() {
+        let ray = Ray::from_cursor(cursor(0.5, -0.25), DIMENSIONS, view_projection()).unwrap();
+        assert_near(ray.origin.x, 0.5);
+        assert_near(ray.origin.y, -0.25);
+        assert_near(ray.origin.z, 0.0);
+        assert_near(ray.direction.z, 1.0);
+
+        // A projection that flattens everything has no inverse to cast rays with.
+        assert!(Ray::from_cursor([0.0, 0.0], DIMENSIONS, Matrix4::from_scale(0.0)).is_none());
+    }
+
+    #[test]
+    fn rays_hit_planes_they_cross() {
+        let ray = Ray {
+            origin: Vector3::new(1.0, 2.0, 0.0),
+            direction: Vector3::unit_z(),
+        };
+        let hit = ray
+            .intersect_plane(Vector3::new(0.0, 0.0, 3.0), Vector3::unit_z())
+            .unwrap();
+        assert_eq!(hit, Vector3::new(1.0, 2.0, 3.0));
+        // Running along the plane never reaches it.
+        assert!(ray
+            .intersect_plane(Vector3::new(5.0, 0.0, 0.0), Vector3::unit_x())
+            .is_none());
+    }
+
+    #[test]
+    fn closest_on_line_measures_along_and_across() {
+        let ray = Ray {
+            origin: Vector3::new(2.0, 1.0, 0.0),
+            direction: Vector3::unit_z(),
+        };
+        let (along, distance) = ray
+            .closest_on_line(Vector3::new(0.0, 0.0, 5.0), Vector3::unit_x())
+            .unwrap();
+        assert_near(along, 2.0);
+        assert_near(distance, 1.0);
+        // A line along the ray has no closest point.
+        assert!(ray
+            .closest_on_line(Vector3::new(0.0, 0.0, 0.0), Vector3::unit_z())
+            .is_none());
+    }
+
+    #[test]
+    fn pick_finds_the_handle_under_the_ray() {
+        let mut editor = editor();
+        let gizmo = Gizmo::new();
+        let (entity, handles) = gizmo
+            .handles(&mut editor, DIMENSIONS, view_projection())
+            .unwrap();
+        assert_eq!(entity, 0);
+        assert_near(handles.pixel, 0.01);
+
+        let pick = |x, y| {
+            let ray = Ray::from_cursor(cursor(x, y), DIMENSIONS, view_projection()).unwrap();
+            gizmo.pick(&handles, &ray)
+        };
+        assert_eq!(pick(0.5, 0.0), Some(0));
+        assert_eq!(pick(0.0, 0.5), Some(1));
+        // Within a few pixels of the handle still counts.
+        assert_eq!(pick(0.5, 0.05), Some(0));
+        // Beside the handles, and behind the center where they don't reach.
+        assert_eq!(pick(0.5, 0.5), None);
+        assert_eq!(pick(-0.5, 0.0), None);
+    }
+
+    #[test]
+    fn press_only_grabs_handles() {
+        let mut editor = editor();
+        let mut gizmo = Gizmo::new();
+        assert!(!gizmo.press(&mut editor, cursor(0.5, 0.5), DIMENSIONS, view_projection()));
+        assert!(!gizmo.is_dragging());
+
+        // Nothing to grab without a selection.
+        editor.select(None);
+        assert!(!gizmo.press(&mut editor, cursor(0.5, 0.0), DIMENSIONS, view_projection()));
+
+        editor.select(Some(Selection::Entity(0)));
+        assert!(gizmo.press(&mut editor, cursor(0.5, 0.0), DIMENSIONS, view_projection()));
+        assert!(gizmo.is_dragging());
+    }
+
+    #[test]
+    fn dragging_a_handle_moves_along_its_axis() {
+        let mut editor = editor();
+        let mut gizmo = Gizmo::new();
+        drag(&mut gizmo, &mut editor, cursor(0.5, 0.0), cursor(0.7, 0.0));
+
+        let translation = editor.scene().entities[0].transform.translation;
+        assert_near(translation[0], 0.2);
+        assert_near(translation[1], 0.0);
+        assert_near(translation[2], 0.0);
+    }
+
+    #[test]
+    fn snapping_rounds_to_the_step() {
+        let mut gizmo = Gizmo::new();
+        assert_eq!(gizmo.snapped(0.37, 0.25), 0.37);
+
+        gizmo.snap.enabled = true;
+        assert_eq!(gizmo.snapped(0.37, 0.25), 0.25);
+        assert_eq!(gizmo.snapped(-0.4, 0.25), -0.5);
+        assert_eq!(gizmo.snapped(23.0, 15.0), 30.0);
+
+        let mut editor = editor();
+        gizmo.snap.translation = 0.25;
+        drag(&mut gizmo, &mut editor, cursor(0.5, 0.0), cursor(0.7, 0.0));
+        assert_eq!(editor.scene().entities[0].transform.translation[0], 0.25);
+    }
+
+    #[test]
+    fn undo_and_redo_round_trip() {
+        let mut editor = editor();
+        let mut gizmo = Gizmo::new();
+        drag(&mut gizmo, &mut editor, cursor(0.5, 0.0), cursor(0.7, 0.0));
+        let moved = editor.scene().entities[0].transform;
+
+        editor.select(None);
+        gizmo.undo(&mut editor);
+        assert_eq!(editor.scene().entities[0].transform, Transform::default());
+        // Undoing selects what changed.
+        assert_eq!(editor.selection(), Some(Selection::Entity(0)));
+
+        gizmo.redo(&mut editor);
+        assert_eq!(editor.scene().entities[0].transform, moved);
+        gizmo.undo(&mut editor);
+        assert_eq!(editor.scene().entities[0].transform, Transform::default());
+
+        // A new drag drops what could be redone.
+        drag(&mut gizmo, &mut editor, cursor(0.5, 0.0), cursor(0.6, 0.0));
+        assert!(gizmo.redo.is_empty());
+        assert_eq!(gizmo.undo.len(), 1);
+    }
+
+    #[test]
+    fn a_drag_that_doesnt_move_isnt_undone() {
+        let mut editor = editor();
+        let mut gizmo = Gizmo::new();
+        drag(&mut gizmo, &mut editor, cursor(0.5, 0.0), cursor(0.5, 0.0));
+        assert!(gizmo.undo.is_empty());
+    }
+
+    #[test]
+    fn steps_for_missing_entities_are_dropped() {
+        let mut editor = editor();
+        let mut gizmo = Gizmo::new();
+        gizmo.undo.push((3, Transform::default()));
+        gizmo.undo(&mut editor);
+        assert!(gizmo.undo.is_empty());
+        assert!(gizmo.redo.is_empty());
+
+        gizmo.redo.push((3, Transform::default()));
+        gizmo.redo(&mut editor);
+        assert!(gizmo.undo.is_empty());
+        assert!(gizmo.redo.is_empty());
+        assert_eq!(editor.scene().entities[0].transform, Transform::default());
+    }
+}
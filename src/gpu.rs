@@ -0,0 +1,274 @@
+//! Physical device selection.
+//!
+//! Every device that has what a demo needs gets a [`score`], and the highest one is used. A
+//! specific device can be forced with a [`GpuSelector`], from `--gpu <index|name>`, [`GPU_ENV`]
+//! or the config file, where the index is the one `--list-gpus` prints and a name matches any
+//! device whose name contains it, ignoring case.
+
+use std::sync::Arc;
+
+use vulkano::device::physical::{PhysicalDevice, PhysicalDeviceType};
+use vulkano::device::{DeviceExtensions, Features};
+use vulkano::instance::Instance;
+
+use crate::error::{Context, Error, Result};
+use crate::setup;
+
+pub const GPU_ENV: &str = "VULKAN_RUST_GPU";
+
+/// Which device was asked for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GpuSelector {
+    /// The position in the instance's device list.
+    Index(usize),
+    /// A lowercase substring of the device name.
+    Name(String),
+}
+
+impl GpuSelector {
+    pub fn parse(value: &str) -> Self {
+        match value.parse() {
+            Ok(index) => Self::Index(index),
+            Err(_) => Self::Name(value.to_lowercase()),
+        }
+    }
+
+    /// The device asked for with [`GPU_ENV`].
+    pub fn from_env() -> Option<Self> {
+        std::env::var(GPU_ENV)
+            .ok()
+            .filter(|value| !value.is_empty())
+            .map(|value| Self::parse(&value))
+    }
+
+    fn matches(&self, index: usize, physical_device: &PhysicalDevice) -> bool {
+        self.matches_device(index, &physical_device.properties().device_name)
+    }
+
+    /// Whether the device at `index` in the device list, called `device_name`, was asked for.
+    fn matches_device(&self, index: usize, device_name: &str) -> bool {
+        match self {
+            Self::Index(i) => *i == index,
+            Self::Name(name) => device_name.to_lowercase().contains(name.as_str()),
+        }
+    }
+}
+
+/// How much a device is preferred. Discrete GPUs come first, then devices with more dedicated
+/// queue families and more device-local memory.
+pub fn score(physical_device: &PhysicalDevice) -> u64 {
+    let dedicated_queues = setup::dedicated_transfer_family(physical_device).is_some() as u64
+        + setup::dedicated_compute_family(physical_device).is_some() as u64;
+    let device_local_gib = physical_device
+        .memory_properties()
+        .memory_heaps
+        .iter()
+        .filter(|heap| heap.flags.device_local)
+        .map(|heap| heap.size >> 30)
+        .sum::<u64>();
+
+    device_score(
+        physical_device.properties().device_type,
+        dedicated_queues,
+        device_local_gib,
+    )
+}
+
+/// The [`score`] of a device from what it's made of. Each criterion only breaks ties of the
+/// ones before it.
+fn device_score(
+    device_type: PhysicalDeviceType,
+    dedicated_queues: u64,
+    device_local_gib: u64,
+) -> u64 {
+    let device_type = match device_type {
+        PhysicalDeviceType::DiscreteGpu => 4,
+        PhysicalDeviceType::IntegratedGpu => 3,
+        PhysicalDeviceType::VirtualGpu => 2,
+        PhysicalDeviceType::Cpu => 1,
+        _ => 0,
+    };
+
+    device_type * 10_000 + dedicated_queues.min(9) * 1000 + device_local_gib.min(999)
+}
+
+/// Picks the device to use, among the ones with `device_extensions`, `features` and a queue
+/// family `queue_family` accepts, and the one `selector` matches when there is one. Returns it
+/// with the index `queue_family` returned.
+pub fn select(
+    instance: &Arc<Instance>,
+    selector: Option<&GpuSelector>,
+    device_extensions: &DeviceExtensions,
+    features: &Features,
+    queue_family: impl Fn(&PhysicalDevice) -> Option<u32>,
+) -> Result<(Arc<PhysicalDevice>, u32)> {
+    let mut best: Option<(u64, Arc<PhysicalDevice>, u32)> = None;
+
+    for (index, physical_device) in instance
+        .enumerate_physical_devices()
+        .context("can't enumerate physical devices")?
+        .enumerate()
+    {
+        let name = physical_device.properties().device_name.clone();
+        if let Some(selector) = selector {
+            if !selector.matches(index, &physical_device) {
+                continue;
+            }
+        }
+
+        if !physical_device
+            .supported_extensions()
+            .contains(device_extensions)
+        {
+            log::info!("{}: {} lacks required extensions", index, name);
+            continue;
+        }
+        if !physical_device.supported_features().contains(features) {
+            log::info!("{}: {} lacks required features", index, name);
+            continue;
+        }
+        let Some(queue_family_index) = queue_family(&physical_device) else {
+            log::info!("{}: {} has no suitable queue family", index, name);
+            continue;
+        };
+
+        let score = score(&physical_device);
+        log::debug!("{}: {} scores {}", index, name, score);
+        if best.as_ref().map_or(true, |(best, _, _)| score > *best) {
+            best = Some((score, physical_device, queue_family_index));
+        }
+    }
+
+    match (best, selector) {
+        (Some((_, physical_device, queue_family_index)), _) => {
+            Ok((physical_device, queue_family_index))
+        }
+        (None, Some(_)) => Err(Error::Unsupported(
+            "the gpu asked for doesn't exist or isn't suitable, see --list-gpus",
+        )),
+        (None, None) => Err(Error::Unsupported("no suitable physical device found")),
+    }
+}
+
+/// Prints every device with its properties, memory heaps, queue families and supported
+/// features.
+pub fn list(instance: &Arc<Instance>) -> Result<()> {
+    for (index, physical_device) in instance
+        .enumerate_physical_devices()
+        .context("can't enumerate physical devices")?
+        .enumerate()
+    {
+        let properties = physical_device.properties();
+        println!("{}: {}", index, properties.device_name);
+        println!("    type: {:?}", properties.device_type);
+        println!("    api version: {}", physical_device.api_version());
+        println!(
+            "    driver: {} {} (version {:#x})",
+            properties.driver_name.as_deref().unwrap_or("unknown"),
+            properties.driver_info.as_deref().unwrap_or(""),
+            properties.driver_version
+        );
+        println!("    score: {}", score(&physical_device));
+
+        for (i, heap) in physical_device
+            .memory_properties()
+            .memory_heaps
+            .iter()
+            .enumerate()
+        {
+            println!(
+                "    memory heap {}: {} MiB{}",
+                i,
+                heap.size >> 20,
+                if heap.flags.device_local {
+                    ", device local"
+                } else {
+                    ""
+                }
+            );
+        }
+
+        for (i, family) in physical_device.queue_family_properties().iter().enumerate() {
+            println!(
+                "    queue family {}: {} queues, {:?}",
+                i, family.queue_count, family.queue_flags
+            );
+        }
+
+        println!("    features: {:?}", physical_device.supported_features());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selector_parses_indices_and_names() {
+        assert_eq!(GpuSelector::parse("0"), GpuSelector::Index(0));
+        assert_eq!(GpuSelector::parse("12"), GpuSelector::Index(12));
+        assert_eq!(
+            GpuSelector::parse("GeForce"),
+            GpuSelector::Name("geforce".to_string())
+        );
+        // Anything that isn't a plain index is a name, even if it starts with digits.
+        assert_eq!(
+            GpuSelector::parse("1080 Ti"),
+            GpuSelector::Name("1080 ti".to_string())
+        );
+        assert_eq!(
+            GpuSelector::parse("-1"),
+            GpuSelector::Name("-1".to_string())
+        );
+    }
+
+    #[test]
+    fn selector_matches_index() {
+        let selector = GpuSelector::Index(1);
+        assert!(selector.matches_device(1, "llvmpipe"));
+        assert!(!selector.matches_device(0, "llvmpipe"));
+    }
+
+    #[test]
+    fn selector_matches_name_ignoring_case() {
+        let selector = GpuSelector::parse("RTX");
+        assert!(selector.matches_device(0, "NVIDIA GeForce RTX 3080"));
+        assert!(selector.matches_device(3, "nvidia geforce rtx 3080"));
+        assert!(!selector.matches_device(0, "AMD Radeon RX 6800"));
+    }
+
+    #[test]
+    fn discrete_gpus_score_highest() {
+        let discrete = device_score(PhysicalDeviceType::DiscreteGpu, 0, 2);
+        let integrated = device_score(PhysicalDeviceType::IntegratedGpu, 2, 64);
+        let virtual_gpu = device_score(PhysicalDeviceType::VirtualGpu, 2, 64);
+        let cpu = device_score(PhysicalDeviceType::Cpu, 2, 64);
+        let other = device_score(PhysicalDeviceType::Other, 2, 64);
+        assert!(discrete > integrated);
+        assert!(integrated > virtual_gpu);
+        assert!(virtual_gpu > cpu);
+        assert!(cpu > other);
+    }
+
+    #[test]
+    fn dedicated_queues_outrank_memory() {
+        let queues = device_score(PhysicalDeviceType::DiscreteGpu, 2, 0);
+        let memory = device_score(PhysicalDeviceType::DiscreteGpu, 1, 48);
+        assert!(queues > memory);
+    }
+
+    #[test]
+    fn memory_breaks_ties() {
+        assert!(
+            device_score(PhysicalDeviceType::DiscreteGpu, 2, 16)
+                > device_score(PhysicalDeviceType::DiscreteGpu, 2, 8)
+        );
+        // More memory than the cap can't make up for a missing queue family.
+        assert!(
+            device_score(PhysicalDeviceType::DiscreteGpu, 1, 0)
+                > device_score(PhysicalDeviceType::DiscreteGpu, 0, 100_000)
+        );
+    }
+}
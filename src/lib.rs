@@ -0,0 +1,22 @@
+pub mod capture;
+pub mod cli;
+pub mod config;
+pub mod debug;
+pub mod demos;
+pub mod editor;
+pub mod error;
+pub mod fault;
+pub mod frames;
+pub mod gizmo;
+pub mod gpu;
+pub mod pipeline_cache;
+pub mod present;
+pub mod profiler;
+pub mod reflect;
+pub mod runner;
+pub mod scene;
+pub mod scene_graph;
+pub mod setup;
+pub mod shader;
+pub mod stats;
+pub mod upload;
@@ -0,0 +1,3 @@
+fn main() {
+    vulkan_rust::cli::launch();
+}
@@ -0,0 +1,187 @@
+//! A `PipelineCache` persisted in the user's cache directory between runs.
+//!
+//! Every file starts with our own header describing the device and driver that produced the
+//! data. The driver validates its own header as well, but a mismatch there is reported as an
+//! error at best, so we never hand it data that came from another device or driver version.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use vulkano::device::{Device, Properties};
+use vulkano::pipeline::cache::PipelineCache;
+
+use crate::error::{Context, Result};
+
+const MAGIC: [u8; 4] = *b"VRPC";
+const HEADER_VERSION: u32 = 1;
+const HEADER_LEN: usize = 36;
+
+pub struct PersistentPipelineCache {
+    cache: Arc<PipelineCache>,
+    header: [u8; HEADER_LEN],
+    path: Option<PathBuf>,
+}
+
+impl PersistentPipelineCache {
+    /// Loads the cache stored for this device, or starts an empty one when there is none or it
+    /// was written by a different device or driver. Only fails when not even an empty cache can
+    /// be created.
+    pub fn load(device: &Arc<Device>) -> Result<Self> {
+        let properties = device.physical_device().properties();
+        let header = header(properties);
+        let path = dirs::cache_dir().map(|dir| dir.join("vulkan-rust").join(file_name(properties)));
+
+        let file = path
+            .as_ref()
+            .and_then(|path| Some((path, fs::read(path).ok()?)));
+        let data = file.as_ref().and_then(|(path, bytes)| {
+            let data = cache_data(bytes, &header);
+            if data.is_none() {
+                log::info!("pipeline cache {} is stale, ignoring it", path.display());
+            }
+            data
+        });
+
+        let cache = match data {
+            // Safety: the header guarantees the data was produced by this device and driver.
+            Some(data) => unsafe { PipelineCache::with_data(device.clone(), data) }.or_else(|e| {
+                log::warn!("can't load pipeline cache, starting an empty one: {}", e);
+                PipelineCache::empty(device.clone())
+            }),
+            None => PipelineCache::empty(device.clone()),
+        }
+        .context("can't create pipeline cache")?;
+
+        Ok(Self {
+            cache,
+            header,
+            path,
+        })
+    }
+
+    /// The cache to pass to every pipeline build.
+    pub fn cache(&self) -> Arc<PipelineCache> {
+        self.cache.clone()
+    }
+
+    /// Writes the cache back to disk. Failing to do so only costs startup time on the next run,
+    /// so errors are reported and otherwise ignored.
+    pub fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let data = match self.cache.get_data() {
+            Ok(data) => data,
+            Err(e) => {
+                log::warn!("can't get pipeline cache data: {}", e);
+                return;
+            }
+        };
+
+        let mut bytes = Vec::with_capacity(HEADER_LEN + data.len());
+        bytes.extend_from_slice(&self.header);
+        bytes.extend_from_slice(&data);
+
+        // Write to a temporary file first, so a crash mid-write never leaves a truncated cache.
+        let tmp_path = path.with_extension("tmp");
+        let result = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&tmp_path, &bytes))
+            .and_then(|_| fs::rename(&tmp_path, path));
+
+        if let Err(e) = result {
+            log::warn!("can't save pipeline cache to {}: {}", path.display(), e);
+        }
+    }
+}
+
+fn header(properties: &Properties) -> [u8; HEADER_LEN] {
+    encode_header(
+        properties.vendor_id,
+        properties.device_id,
+        properties.driver_version,
+        properties.pipeline_cache_uuid,
+    )
+}
+
+fn encode_header(
+    vendor_id: u32,
+    device_id: u32,
+    driver_version: u32,
+    pipeline_cache_uuid: [u8; 16],
+) -> [u8; HEADER_LEN] {
+    let mut header = [0; HEADER_LEN];
+    header[0..4].copy_from_slice(&MAGIC);
+    header[4..8].copy_from_slice(&HEADER_VERSION.to_le_bytes());
+    header[8..12].copy_from_slice(&vendor_id.to_le_bytes());
+    header[12..16].copy_from_slice(&device_id.to_le_bytes());
+    header[16..20].copy_from_slice(&driver_version.to_le_bytes());
+    header[20..36].copy_from_slice(&pipeline_cache_uuid);
+    header
+}
+
+/// The driver's data in the file `bytes`, when it starts with `header`.
+fn cache_data<'a>(bytes: &'a [u8], header: &[u8; HEADER_LEN]) -> Option<&'a [u8]> {
+    bytes.strip_prefix(header.as_slice())
+}
+
+fn file_name(properties: &Properties) -> String {
+    let uuid = properties
+        .device_uuid
+        .unwrap_or(properties.pipeline_cache_uuid)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+
+    format!("{}-{:08x}.bin", uuid, properties.driver_version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UUID: [u8; 16] = [7; 16];
+
+    fn file(header: [u8; HEADER_LEN], data: &[u8]) -> Vec<u8> {
+        [&header[..], data].concat()
+    }
+
+    #[test]
+    fn matching_headers_give_the_data() {
+        let header = encode_header(0x10de, 0x2204, 42, UUID);
+        let bytes = file(header, b"driver data");
+        assert_eq!(cache_data(&bytes, &header), Some(&b"driver data"[..]));
+        // A cache saved before any pipeline was built is just the header.
+        assert_eq!(cache_data(&header, &header), Some(&[][..]));
+    }
+
+    #[test]
+    fn truncated_files_are_stale() {
+        let header = encode_header(0x10de, 0x2204, 42, UUID);
+        assert_eq!(cache_data(&header[..HEADER_LEN - 1], &header), None);
+        assert_eq!(cache_data(&[], &header), None);
+    }
+
+    #[test]
+    fn other_devices_are_stale() {
+        let header = encode_header(0x10de, 0x2204, 42, UUID);
+        let others = [
+            encode_header(0x1002, 0x2204, 42, UUID),
+            encode_header(0x10de, 0x2206, 42, UUID),
+            encode_header(0x10de, 0x2204, 42, [8; 16]),
+        ];
+        for other in others {
+            assert_eq!(cache_data(&file(other, b"driver data"), &header), None);
+        }
+    }
+
+    #[test]
+    fn other_driver_versions_are_stale() {
+        let header = encode_header(0x10de, 0x2204, 42, UUID);
+        let bytes = file(encode_header(0x10de, 0x2204, 43, UUID), b"driver data");
+        assert_eq!(cache_data(&bytes, &header), None);
+    }
+}
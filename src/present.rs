@@ -0,0 +1,488 @@
+//! How frames are presented: the swapchain format and color space, the present mode, how many
+//! images the swapchain has, and an optional frame rate limit.
+//!
+//! The present mode and image count are checked against what the surface supports when the
+//! swapchain is created, and changing them means recreating it. The frame rate limit is applied
+//! on the CPU by [`FrameLimiter`], before a frame is recorded.
+//!
+//! The format is picked by [`select_surface_format`], which prefers an sRGB format so the
+//! hardware encodes the shaders' linear output. HDR is only used when asked for with
+//! [`DYNAMIC_RANGE_ENV`]. The fragment shaders writing to the swapchain encode their output as
+//! the [`OutputEncoding`] says, with `shaders/encoding.glsl`, so it looks the same whatever was
+//! picked.
+
+use std::time::{Duration, Instant};
+
+use egui::Slider;
+use vulkano::device::physical::PhysicalDevice;
+use vulkano::format::{Format, NumericType};
+use vulkano::swapchain::{ColorSpace, PresentMode, Surface};
+
+use crate::error::{Context, Result};
+
+pub const DYNAMIC_RANGE_ENV: &str = "VULKAN_RUST_DYNAMIC_RANGE";
+
+/// The present modes that can be picked, in the order they're listed.
+pub const PRESENT_MODES: [PresentMode; 4] = [
+    PresentMode::Fifo,
+    PresentMode::FifoRelaxed,
+    PresentMode::Mailbox,
+    PresentMode::Immediate,
+];
+
+/// The frame rates the limiter can be set to.
+pub const FPS_LIMIT_RANGE: std::ops::RangeInclusive<f32> = 10.0..=360.0;
+
+/// How many images past the minimum can be picked, when the surface has no maximum.
+const EXTRA_IMAGES: u32 = 4;
+
+/// The SDR formats preferred, in order. Every one of them encodes to sRGB in hardware.
+const SDR_FORMATS: [Format; 2] = [Format::B8G8R8A8_SRGB, Format::R8G8B8A8_SRGB];
+
+/// The formats used with the HDR10 color space.
+const HDR10_FORMATS: [Format; 2] = [
+    Format::A2B10G10R10_UNORM_PACK32,
+    Format::A2R10G10B10_UNORM_PACK32,
+];
+
+/// The name of a present mode on the command line and in the config file.
+pub fn present_mode_name(present_mode: PresentMode) -> &'static str {
+    match present_mode {
+        PresentMode::Fifo => "fifo",
+        PresentMode::FifoRelaxed => "fifo-relaxed",
+        PresentMode::Mailbox => "mailbox",
+        PresentMode::Immediate => "immediate",
+        _ => "unknown",
+    }
+}
+
+/// The present mode [`present_mode_name`] gives `name`, if it's one of [`PRESENT_MODES`].
+pub fn parse_present_mode(name: &str) -> Option<PresentMode> {
+    PRESENT_MODES
+        .into_iter()
+        .find(|&mode| present_mode_name(mode) == name)
+}
+
+/// What the swapchain is asked to be able to show.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DynamicRange {
+    #[default]
+    Sdr,
+    /// 10 bit PQ encoded Rec. 2020.
+    Hdr10,
+    /// Linear, extended sRGB in half floats.
+    ScRgb,
+}
+
+impl DynamicRange {
+    /// The dynamic range asked for in [`DYNAMIC_RANGE_ENV`], `sdr`, `hdr10` or `scrgb`.
+    pub fn from_env() -> Self {
+        match std::env::var(DYNAMIC_RANGE_ENV).as_deref() {
+            Err(_) | Ok("sdr") => Self::Sdr,
+            Ok("hdr10") => Self::Hdr10,
+            Ok("scrgb") => Self::ScRgb,
+            Ok(other) => {
+                log::warn!(
+                    "unknown {} {:?}, expected sdr, hdr10 or scrgb",
+                    DYNAMIC_RANGE_ENV,
+                    other
+                );
+                Self::Sdr
+            }
+        }
+    }
+}
+
+/// What the fragment shaders have to do to their linear output before writing it to the
+/// swapchain. Must match the `OUTPUT_*` constants in `shaders/encoding.glsl`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum OutputEncoding {
+    /// The format is sRGB, the hardware encodes.
+    Hardware = 0,
+    /// A UNORM format in the sRGB color space, the shader encodes to sRGB.
+    Srgb = 1,
+    /// HDR10, the shader converts to Rec. 2020 and encodes with the PQ curve.
+    Pq = 2,
+    /// scRGB, the output stays linear.
+    Linear = 3,
+}
+
+impl OutputEncoding {
+    /// Does on the CPU what the shaders do, for colors that are the same for a whole draw.
+    pub fn encode(self, linear: [f32; 4]) -> [f32; 4] {
+        let [r, g, b, a] = linear;
+        match self {
+            Self::Hardware | Self::Linear => linear,
+            Self::Srgb => {
+                let srgb = |c: f32| {
+                    if c < 0.0031308 {
+                        c * 12.92
+                    } else {
+                        1.055 * c.powf(1.0 / 2.4) - 0.055
+                    }
+                };
+                [srgb(r), srgb(g), srgb(b), a]
+            }
+            Self::Pq => {
+                // Where SDR white ends up in HDR10, in nits, like `SDR_WHITE_NITS`.
+                let scale = 203.0 / 10000.0;
+                let pq = |c: f32| {
+                    let p = (c * scale).clamp(0.0, 1.0).powf(0.1593017578125);
+                    ((0.8359375 + 18.8515625 * p) / (1.0 + 18.6875 * p)).powf(78.84375)
+                };
+                [
+                    pq(0.6274 * r + 0.3293 * g + 0.0433 * b),
+                    pq(0.0691 * r + 0.9195 * g + 0.0114 * b),
+                    pq(0.0164 * r + 0.0880 * g + 0.8956 * b),
+                    a,
+                ]
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SurfaceFormat {
+    pub format: Format,
+    pub color_space: ColorSpace,
+    pub encoding: OutputEncoding,
+}
+
+impl SurfaceFormat {
+    /// An image that is never presented, which is in the sRGB color space like an SDR window.
+    pub fn offscreen(format: Format) -> Self {
+        Self::new(format, ColorSpace::SrgbNonLinear)
+    }
+
+    fn new(format: Format, color_space: ColorSpace) -> Self {
+        let encoding = match color_space {
+            ColorSpace::Hdr10St2084 => OutputEncoding::Pq,
+            ColorSpace::ExtendedSrgbLinear => OutputEncoding::Linear,
+            _ if format.type_color() == Some(NumericType::SRGB) => OutputEncoding::Hardware,
+            _ => OutputEncoding::Srgb,
+        };
+
+        Self {
+            format,
+            color_space,
+            encoding,
+        }
+    }
+}
+
+/// Picks a format out of the ones the surface supports. `dynamic_range` is used when the
+/// surface supports it, otherwise an sRGB format, and only when there's none of those whatever
+/// comes first in the sRGB color space.
+pub fn select_surface_format(
+    formats: &[(Format, ColorSpace)],
+    dynamic_range: DynamicRange,
+) -> Option<SurfaceFormat> {
+    let find = |matches: &dyn Fn(Format, ColorSpace) -> bool| {
+        formats
+            .iter()
+            .find(|&&(format, color_space)| matches(format, color_space))
+            .map(|&(format, color_space)| SurfaceFormat::new(format, color_space))
+    };
+
+    let hdr = match dynamic_range {
+        DynamicRange::Sdr => None,
+        DynamicRange::Hdr10 => find(&|format, color_space| {
+            color_space == ColorSpace::Hdr10St2084 && HDR10_FORMATS.contains(&format)
+        }),
+        DynamicRange::ScRgb => find(&|format, color_space| {
+            color_space == ColorSpace::ExtendedSrgbLinear && format == Format::R16G16B16A16_SFLOAT
+        }),
+    };
+    if hdr.is_none() && dynamic_range != DynamicRange::Sdr {
+        log::warn!(
+            "{:?} isn't supported by the surface, using SDR",
+            dynamic_range
+        );
+    }
+
+    hdr.or_else(|| {
+        SDR_FORMATS.iter().find_map(|&sdr| {
+            find(&|format, color_space| format == sdr && color_space == ColorSpace::SrgbNonLinear)
+        })
+    })
+    .or_else(|| {
+        find(&|format, color_space| {
+            format.type_color() == Some(NumericType::SRGB)
+                && color_space == ColorSpace::SrgbNonLinear
+        })
+    })
+    .or_else(|| find(&|_, color_space| color_space == ColorSpace::SrgbNonLinear))
+}
+
+/// What the surface supports, which the settings are checked against.
+#[derive(Clone, Debug)]
+pub struct SurfaceSupport {
+    pub present_modes: Vec<PresentMode>,
+    pub min_image_count: u32,
+    pub max_image_count: Option<u32>,
+}
+
+impl SurfaceSupport {
+    pub fn query(physical_device: &PhysicalDevice, surface: &Surface) -> Result<Self> {
+        let capabilities = physical_device
+            .surface_capabilities(surface, Default::default())
+            .context("can't get surface capabilities")?;
+        let present_modes = physical_device
+            .surface_present_modes(surface)
+            .context("can't get surface present modes")?
+            .collect();
+
+        Ok(Self {
+            present_modes,
+            min_image_count: capabilities.min_image_count,
+            max_image_count: capabilities.max_image_count,
+        })
+    }
+
+    fn max_image_count(&self) -> u32 {
+        self.max_image_count
+            .unwrap_or(self.min_image_count + EXTRA_IMAGES)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PresentSettings {
+    pub present_mode: PresentMode,
+    /// The surface's minimum when `None`.
+    pub image_count: Option<u32>,
+    /// Frames per second, unlimited when `None`.
+    pub fps_limit: Option<f32>,
+}
+
+impl Default for PresentSettings {
+    fn default() -> Self {
+        Self {
+            present_mode: PresentMode::Fifo,
+            image_count: None,
+            fps_limit: None,
+        }
+    }
+}
+
+impl PresentSettings {
+    /// The present mode to create the swapchain with. FIFO, which every surface supports, when
+    /// the one asked for isn't.
+    pub fn present_mode(&self, support: &SurfaceSupport) -> PresentMode {
+        if support.present_modes.contains(&self.present_mode) {
+            self.present_mode
+        } else {
+            log::warn!(
+                "present mode {:?} isn't supported, using {:?}",
+                self.present_mode,
+                PresentMode::Fifo
+            );
+            PresentMode::Fifo
+        }
+    }
+
+    /// The image count to create the swapchain with, within what the surface supports.
+    pub fn image_count(&self, support: &SurfaceSupport) -> u32 {
+        self.image_count
+            .unwrap_or(support.min_image_count)
+            .clamp(support.min_image_count, support.max_image_count())
+    }
+
+    /// Whether going from `self` to `other` means recreating the swapchain.
+    pub fn needs_recreate(&self, other: &Self) -> bool {
+        self.present_mode != other.present_mode || self.image_count != other.image_count
+    }
+
+    /// Shows the settings in a window. Returns whether the swapchain has to be recreated for the
+    /// changes.
+    pub fn ui(&mut self, ctx: &egui::Context, support: &SurfaceSupport) -> bool {
+        let before = *self;
+
+        egui::Window::new("Presentation")
+            .default_width(300.0)
+            .show(ctx, |ui| {
+                egui::ComboBox::from_label("present mode")
+                    .selected_text(format!("{:?}", self.present_mode))
+                    .show_ui(ui, |ui| {
+                        for mode in PRESENT_MODES {
+                            ui.add_enabled_ui(support.present_modes.contains(&mode), |ui| {
+                                ui.selectable_value(
+                                    &mut self.present_mode,
+                                    mode,
+                                    format!("{:?}", mode),
+                                );
+                            });
+                        }
+                    });
+
+                let mut custom_images = self.image_count.is_some();
+                let mut image_count = self.image_count(support);
+                ui.checkbox(&mut custom_images, "set image count");
+                ui.add_enabled(
+                    custom_images,
+                    Slider::new(
+                        &mut image_count,
+                        support.min_image_count..=support.max_image_count(),
+                    )
+                    .text("images"),
+                );
+                self.image_count = custom_images.then_some(image_count);
+
+                let mut limit_fps = self.fps_limit.is_some();
+                let mut fps_limit = self.fps_limit.unwrap_or(60.0);
+                ui.checkbox(&mut limit_fps, "limit frame rate");
+                ui.add_enabled(
+                    limit_fps,
+                    Slider::new(&mut fps_limit, FPS_LIMIT_RANGE).text("fps"),
+                );
+                self.fps_limit = limit_fps.then_some(fps_limit);
+            });
+
+        before.needs_recreate(self)
+    }
+}
+
+/// Paces frames to a frame rate limit by sleeping before each one.
+#[derive(Debug, Default)]
+pub struct FrameLimiter {
+    /// When the next frame is due.
+    next: Option<Instant>,
+}
+
+impl FrameLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sleeps until the next frame is due at `fps_limit`, and returns how long that took. A
+    /// frame that is already late starts right away, and the ones after it aren't hurried to
+    /// catch up.
+    pub fn wait(&mut self, fps_limit: Option<f32>) -> Duration {
+        let Some(fps_limit) = fps_limit else {
+            self.next = None;
+            return Duration::ZERO;
+        };
+
+        let start = Instant::now();
+        let due = self.next.map_or(start, |next| next.max(start));
+        if due > start {
+            std::thread::sleep(due - start);
+        }
+        self.next = Some(due + Duration::from_secs_f32(1.0 / fps_limit));
+        start.elapsed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FPS: f32 = 50.0;
+    const INTERVAL: Duration = Duration::from_millis(20);
+    /// How much earlier than due a frame may start, for timer resolution.
+    const SLACK: Duration = Duration::from_millis(2);
+
+    #[test]
+    fn unlimited_frames_dont_wait() {
+        let mut limiter = FrameLimiter::new();
+        for _ in 0..3 {
+            assert_eq!(limiter.wait(None), Duration::ZERO);
+        }
+    }
+
+    #[test]
+    fn limited_frames_are_paced() {
+        let mut limiter = FrameLimiter::new();
+        // The first frame has nothing to be paced after.
+        assert!(limiter.wait(Some(FPS)) < INTERVAL / 2);
+
+        let start = Instant::now();
+        for _ in 0..3 {
+            limiter.wait(Some(FPS));
+        }
+        assert!(start.elapsed() + SLACK >= INTERVAL * 3);
+    }
+
+    #[test]
+    fn late_frames_start_right_away_without_catching_up() {
+        let mut limiter = FrameLimiter::new();
+        limiter.wait(Some(FPS));
+        std::thread::sleep(INTERVAL * 3);
+
+        assert!(limiter.wait(Some(FPS)) < INTERVAL / 2);
+        // The frames skipped aren't made up for, the next one is a whole interval later.
+        assert!(limiter.wait(Some(FPS)) + SLACK >= INTERVAL);
+    }
+
+    #[test]
+    fn removing_the_limit_resets_the_pacing() {
+        let mut limiter = FrameLimiter::new();
+        limiter.wait(Some(FPS));
+        limiter.wait(None);
+        assert!(limiter.wait(Some(FPS)) < INTERVAL / 2);
+    }
+
+    #[test]
+    fn surface_formats_are_picked_in_order_of_preference() {
+        use ColorSpace::{ExtendedSrgbLinear, Hdr10St2084, SrgbNonLinear};
+        use DynamicRange::{Hdr10, ScRgb, Sdr};
+        use Format::*;
+
+        let unorm = (B8G8R8A8_UNORM, SrgbNonLinear);
+        let srgb = (B8G8R8A8_SRGB, SrgbNonLinear);
+        let rgba_srgb = (R8G8B8A8_SRGB, SrgbNonLinear);
+        let hdr10 = (A2B10G10R10_UNORM_PACK32, Hdr10St2084);
+        let scrgb = (R16G16B16A16_SFLOAT, ExtendedSrgbLinear);
+
+        let cases = [
+            // sRGB formats win over UNORM ones, wherever they're listed.
+            (
+                vec![unorm, srgb],
+                Sdr,
+                Some((srgb, OutputEncoding::Hardware)),
+            ),
+            (
+                vec![rgba_srgb, srgb],
+                Sdr,
+                Some((srgb, OutputEncoding::Hardware)),
+            ),
+            (vec![unorm], Sdr, Some((unorm, OutputEncoding::Srgb))),
+            // HDR is only picked when asked for.
+            (
+                vec![hdr10, srgb],
+                Sdr,
+                Some((srgb, OutputEncoding::Hardware)),
+            ),
+            (
+                vec![unorm, hdr10, srgb],
+                Hdr10,
+                Some((hdr10, OutputEncoding::Pq)),
+            ),
+            (
+                vec![scrgb, srgb],
+                ScRgb,
+                Some((scrgb, OutputEncoding::Linear)),
+            ),
+            // And falls back to SDR when the surface doesn't have it.
+            (
+                vec![unorm, srgb],
+                Hdr10,
+                Some((srgb, OutputEncoding::Hardware)),
+            ),
+            (
+                vec![hdr10, unorm],
+                ScRgb,
+                Some((unorm, OutputEncoding::Srgb)),
+            ),
+            // A format in another color space is never picked for SDR.
+            (vec![hdr10, scrgb], Sdr, None),
+            (vec![], Sdr, None),
+        ];
+
+        for (formats, dynamic_range, expected) in cases {
+            let picked = select_surface_format(&formats, dynamic_range)
+                .map(|picked| ((picked.format, picked.color_space), picked.encoding));
+            assert_eq!(picked, expected, "{:?} from {:?}", dynamic_range, formats);
+        }
+    }
+}
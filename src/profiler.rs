@@ -0,0 +1,644 @@
+//! GPU timestamps around the passes of a frame, CPU timings for recording and submitting it,
+//! and egui panels showing them along with the frame's [counters](crate::stats).
+//!
+//! Timestamps and pipeline statistics can only be read once the GPU has executed the frame, so
+//! every frame in flight writes into its own query pools, which are read back when the frame
+//! comes around again and its fence has been waited for. Either kind of query is used whenever
+//! the device supports it, independently of the other.
+
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use egui::plot::{Legend, Line, Plot, PlotPoints};
+use vulkano::command_buffer::allocator::CommandBufferAllocator;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::device::Queue;
+use vulkano::query::{
+    QueryControlFlags, QueryPool, QueryPoolCreateInfo, QueryResultFlags, QueryType,
+};
+use vulkano::sync::PipelineStage;
+
+use crate::debug;
+use crate::error::{Context, Result};
+use crate::stats::{FrameCounters, PipelineStatistics};
+
+/// How many passes a frame can time. Passes past that are still labelled, but not timed.
+const MAX_PASSES: u32 = 16;
+
+/// How many pipeline statistics queries a frame can make.
+const MAX_STATISTICS: u32 = 4;
+
+/// Where the history is exported to as CSV, unless another path was set.
+pub const DEFAULT_EXPORT_PATH: &str = "frame-stats.csv";
+
+/// How many frames the graph and the averages cover.
+const HISTORY_LEN: usize = 240;
+
+/// How many of the most recent frames the breakdown averages over.
+const AVERAGE_LEN: usize = 60;
+
+/// The GPU time of one pass.
+#[derive(Clone, Copy, Debug)]
+pub struct PassTiming {
+    pub name: &'static str,
+    /// How many passes this one is nested in.
+    pub depth: usize,
+    pub time: Duration,
+}
+
+/// Where the time of one frame went.
+#[derive(Clone, Debug, Default)]
+pub struct FrameTimings {
+    /// Each pass's GPU time, in the order the passes were begun.
+    pub passes: Vec<PassTiming>,
+    /// From the first pass beginning to the last one ending. `None` without GPU timestamps.
+    pub gpu: Option<Duration>,
+    /// From the previous frame's submission to this one's, which is what frame pacing is about.
+    pub interval: Duration,
+    /// Waiting for the GPU to be done with the frame's resources before recording it.
+    pub wait: Duration,
+    /// Recording the frame's command buffers.
+    pub record: Duration,
+    /// Submitting and presenting the frame.
+    pub submit: Duration,
+    pub counters: FrameCounters,
+    /// Summed over the frame's statistics queries. `None` when the device doesn't support them.
+    pub statistics: Option<PipelineStatistics>,
+}
+
+impl FrameTimings {
+    const CSV_HEADER: &'static str = "gpu_ms,interval_ms,wait_ms,record_ms,submit_ms";
+
+    fn csv_row(&self) -> String {
+        format!(
+            "{},{},{},{},{}",
+            self.gpu
+                .map(millis)
+                .map_or(String::new(), |ms| ms.to_string()),
+            millis(self.interval),
+            millis(self.wait),
+            millis(self.record),
+            millis(self.submit)
+        )
+    }
+}
+
+pub struct Profiler {
+    /// `None` when the queue doesn't support timestamps.
+    gpu: Option<GpuTimer>,
+    /// The pipeline statistics queries of each frame in flight, `None` when the device doesn't
+    /// support them.
+    statistics: Option<Vec<StatisticsQueries>>,
+    /// The CPU side of each frame in flight, once it was submitted. Until then there is nothing
+    /// to read.
+    submitted: Vec<Option<FrameTimings>>,
+    /// The frame in flight being recorded, as given to [`Self::begin_frame`].
+    current: usize,
+    history: VecDeque<FrameTimings>,
+    last_submit: Option<Instant>,
+    export_path: PathBuf,
+    /// What the last CSV export did, shown under its button.
+    export_status: Option<String>,
+}
+
+struct GpuTimer {
+    /// Nanoseconds per timestamp tick.
+    period: f64,
+    /// The bits of a timestamp the queue actually writes.
+    mask: u64,
+    frames: Vec<TimestampQueries>,
+    /// How many passes are being recorded around the next one.
+    depth: Cell<usize>,
+}
+
+struct TimestampQueries {
+    pool: Arc<QueryPool>,
+    /// The name and depth of each pass, in the order of their queries.
+    passes: RefCell<Vec<(&'static str, usize)>>,
+}
+
+struct StatisticsQueries {
+    pool: Arc<QueryPool>,
+    used: Cell<u32>,
+}
+
+impl Profiler {
+    /// Times passes on `queue`, or only the CPU side when its family doesn't support timestamps.
+    /// Pipeline statistics are queried when the device was created with
+    /// `pipeline_statistics_query`. There is a set of queries for each of the
+    /// `frames_in_flight`, indexed like [`FramesInFlight`](crate::frames::FramesInFlight).
+    pub fn new(queue: &Arc<Queue>, frames_in_flight: usize) -> Result<Self> {
+        let device = queue.device();
+        let physical_device = device.physical_device();
+        let valid_bits = physical_device.queue_family_properties()
+            [queue.queue_family_index() as usize]
+            .timestamp_valid_bits;
+        let period = physical_device.properties().timestamp_period as f64;
+
+        let gpu = match valid_bits {
+            Some(bits) if period > 0.0 => {
+                let frames = (0..frames_in_flight)
+                    .map(|_| {
+                        let pool = QueryPool::new(
+                            device.clone(),
+                            QueryPoolCreateInfo {
+                                query_count: MAX_PASSES * 2,
+                                ..QueryPoolCreateInfo::query_type(QueryType::Timestamp)
+                            },
+                        )
+                        .context("can't create query pool")?;
+                        debug::set_name(&*pool, "timestamp queries");
+
+                        Ok(TimestampQueries {
+                            pool,
+                            passes: RefCell::new(Vec::new()),
+                        })
+                    })
+                    .collect::<Result<_>>()?;
+
+                Some(GpuTimer {
+                    period,
+                    mask: u64::MAX.checked_shr(64 - bits).unwrap_or(0),
+                    frames,
+                    depth: Cell::new(0),
+                })
+            }
+            _ => {
+                log::warn!("the queue doesn't support timestamps, only CPU timings are profiled");
+                None
+            }
+        };
+
+        let statistics = device
+            .enabled_features()
+            .pipeline_statistics_query
+            .then(|| {
+                (0..frames_in_flight)
+                    .map(|_| {
+                        let query_type = QueryType::PipelineStatistics(PipelineStatistics::FLAGS);
+                        let pool = QueryPool::new(
+                            device.clone(),
+                            QueryPoolCreateInfo {
+                                query_count: MAX_STATISTICS,
+                                ..QueryPoolCreateInfo::query_type(query_type)
+                            },
+                        )
+                        .context("can't create query pool")?;
+                        debug::set_name(&*pool, "pipeline statistics queries");
+
+                        Ok(StatisticsQueries {
+                            pool,
+                            used: Cell::new(0),
+                        })
+                    })
+                    .collect::<Result<_>>()
+            })
+            .transpose()?;
+
+        Ok(Self {
+            gpu,
+            statistics,
+            submitted: (0..frames_in_flight).map(|_| None).collect(),
+            current: 0,
+            history: VecDeque::with_capacity(HISTORY_LEN),
+            last_submit: None,
+            export_path: PathBuf::from(DEFAULT_EXPORT_PATH),
+            export_status: None,
+        })
+    }
+
+    /// Sets where the history is exported to, instead of [`DEFAULT_EXPORT_PATH`].
+    pub fn set_export_path(&mut self, path: PathBuf) {
+        self.export_path = path;
+    }
+
+    /// Starts the frame recorded in `builder` for the frame in flight `index`, which must be a
+    /// primary command buffer outside of a render pass. Reads back the frame that last used
+    /// these queries first. Its fence should have been waited for already, otherwise this waits
+    /// for the GPU.
+    pub fn begin_frame<L, A>(
+        &mut self,
+        builder: &mut AutoCommandBufferBuilder<L, A>,
+        index: usize,
+    ) -> Result<()>
+    where
+        A: CommandBufferAllocator,
+    {
+        self.current = index;
+        let timings = self.read_back()?;
+
+        if let Some(gpu) = &mut self.gpu {
+            let frame = &mut gpu.frames[index];
+            frame.passes.get_mut().clear();
+            gpu.depth.set(0);
+            // Safety: the frame that last wrote these queries has been waited for above, or was
+            // never submitted.
+            unsafe { builder.reset_query_pool(frame.pool.clone(), 0..MAX_PASSES * 2) }
+                .context("can't reset query pool")?;
+        }
+        if let Some(statistics) = &self.statistics {
+            let frame = &statistics[index];
+            frame.used.set(0);
+            // Safety: as above.
+            unsafe { builder.reset_query_pool(frame.pool.clone(), 0..MAX_STATISTICS) }
+                .context("can't reset query pool")?;
+        }
+
+        if let Some(timings) = timings {
+            self.push(timings);
+        }
+        Ok(())
+    }
+
+    /// Times the commands recorded by `record` on the GPU, and labels them like
+    /// [`debug::label`]. `builder` can be a secondary command buffer, as long as it's executed in
+    /// the frame begun last, and passes can be nested. Fails when `record` does, or when the
+    /// timestamps can't be written, which leaves the frame unusable.
+    pub fn pass<L, A, R>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<L, A>,
+        name: &'static str,
+        record: impl FnOnce(&mut AutoCommandBufferBuilder<L, A>) -> Result<R>,
+    ) -> Result<R>
+    where
+        A: CommandBufferAllocator,
+    {
+        let queries = self.gpu.as_ref().and_then(|gpu| {
+            let frame = &gpu.frames[self.current];
+            let mut passes = frame.passes.borrow_mut();
+            let index = passes.len() as u32;
+            if index == MAX_PASSES {
+                log::warn!("too many passes to time {}", name);
+                return None;
+            }
+            passes.push((name, gpu.depth.get()));
+            Some((frame.pool.clone(), index * 2))
+        });
+
+        // Safety: both queries were reset when the frame began, and are written once.
+        if let Some((pool, first)) = &queries {
+            unsafe { builder.write_timestamp(pool.clone(), *first, PipelineStage::TopOfPipe) }
+                .context("can't write timestamp")?;
+        }
+        if let Some(gpu) = &self.gpu {
+            gpu.depth.set(gpu.depth.get() + 1);
+        }
+        let result = debug::label(builder, name, record);
+        if let Some(gpu) = &self.gpu {
+            gpu.depth.set(gpu.depth.get() - 1);
+        }
+        let result = result?;
+        if let Some((pool, first)) = &queries {
+            unsafe {
+                builder.write_timestamp(pool.clone(), first + 1, PipelineStage::BottomOfPipe)
+            }
+            .context("can't write timestamp")?;
+        }
+
+        Ok(result)
+    }
+
+    /// Queries the pipeline statistics of the commands recorded by `record`, when the device
+    /// supports it. The query has to end in the subpass it began in, so a scope can't span
+    /// subpasses, and a secondary command buffer can only query its own commands. Fails like
+    /// [`Profiler::pass`].
+    pub fn statistics<L, A, R>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<L, A>,
+        record: impl FnOnce(&mut AutoCommandBufferBuilder<L, A>) -> Result<R>,
+    ) -> Result<R>
+    where
+        A: CommandBufferAllocator,
+    {
+        let query = self.statistics.as_ref().and_then(|statistics| {
+            let frame = &statistics[self.current];
+            let index = frame.used.get();
+            if index == MAX_STATISTICS {
+                log::warn!("too many pipeline statistics queries");
+                return None;
+            }
+            frame.used.set(index + 1);
+            Some((frame.pool.clone(), index))
+        });
+
+        if let Some((pool, index)) = &query {
+            // Safety: the query was reset when the frame began, and is begun once.
+            unsafe { builder.begin_query(pool.clone(), *index, QueryControlFlags::default()) }
+                .context("can't begin query")?;
+        }
+        let result = record(builder)?;
+        if let Some((pool, index)) = query {
+            builder.end_query(pool, index).context("can't end query")?;
+        }
+
+        Ok(result)
+    }
+
+    /// Ends the frame once it was submitted, with the CPU time spent waiting for its resources,
+    /// recording and submitting it, and what it recorded. A frame that failed before that is
+    /// never read back.
+    pub fn end_frame(
+        &mut self,
+        wait: Duration,
+        record: Duration,
+        submit: Duration,
+        counters: FrameCounters,
+    ) {
+        let now = Instant::now();
+        let interval = self
+            .last_submit
+            .replace(now)
+            .map_or(Duration::ZERO, |last| now - last);
+
+        let timings = FrameTimings {
+            interval,
+            wait,
+            record,
+            submit,
+            counters,
+            ..Default::default()
+        };
+
+        // Without any queries there is nothing to wait for.
+        if self.gpu.is_none() && self.statistics.is_none() {
+            self.push(timings);
+        } else {
+            self.submitted[self.current] = Some(timings);
+        }
+    }
+
+    /// The frames read back so far, oldest first.
+    pub fn history(&self) -> impl Iterator<Item = &FrameTimings> {
+        self.history.iter()
+    }
+
+    fn push(&mut self, timings: FrameTimings) {
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(timings);
+    }
+
+    /// Reads back the current frame's queries, if it was submitted.
+    fn read_back(&mut self) -> Result<Option<FrameTimings>> {
+        let Some(mut timings) = self.submitted[self.current].take() else {
+            return Ok(None);
+        };
+        if let Some(statistics) = &self.statistics {
+            timings.statistics = statistics[self.current].read_back()?;
+        }
+        if let Some(gpu) = &mut self.gpu {
+            gpu.read_back(self.current, &mut timings)?;
+        }
+        Ok(Some(timings))
+    }
+
+    /// Writes every frame in the history as a CSV row, oldest first, for comparing runs.
+    pub fn export_csv(&self, path: &Path) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(
+            file,
+            "{},{},{}",
+            FrameTimings::CSV_HEADER,
+            FrameCounters::CSV_HEADER,
+            PipelineStatistics::CSV_HEADER
+        )?;
+        for timings in &self.history {
+            // Frames without statistics leave their columns empty.
+            let statistics = timings
+                .statistics
+                .map_or_else(|| ",".repeat(PipelineStatistics::LEN - 1), |s| s.csv_row());
+            writeln!(
+                file,
+                "{},{},{}",
+                timings.csv_row(),
+                timings.counters.csv_row(),
+                statistics
+            )?;
+        }
+        file.flush()
+    }
+
+    /// Shows the last frame's counters and pipeline statistics, with a button exporting the
+    /// history to CSV.
+    pub fn counters_ui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Frame Counters")
+            .default_width(300.0)
+            .show(ctx, |ui| {
+                let latest = self.history.back();
+                egui::Grid::new("frame counters")
+                    .num_columns(2)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        let counters = latest.map(|t| t.counters).unwrap_or_default();
+                        let statistics = latest.and_then(|t| t.statistics);
+                        let statistics = statistics.iter().flat_map(|s| s.rows());
+                        for (name, value) in counters.rows().into_iter().chain(statistics) {
+                            ui.label(name);
+                            ui.label(value.to_string());
+                            ui.end_row();
+                        }
+                    });
+                if latest.map_or(false, |t| t.statistics.is_none()) {
+                    ui.label("pipeline statistics aren't supported");
+                }
+
+                if ui.button("Export CSV").clicked() {
+                    let path = &self.export_path;
+                    let status = match self.export_csv(path) {
+                        Ok(()) => {
+                            format!("wrote {} frames to {}", self.history.len(), path.display())
+                        }
+                        Err(e) => format!("can't write {}: {}", path.display(), e),
+                    };
+                    log::info!("{}", status);
+                    self.export_status = Some(status);
+                }
+                if let Some(status) = &self.export_status {
+                    ui.label(status);
+                }
+            });
+    }
+
+    /// Shows the per-pass breakdown, averaged over the last frames, and the frame-time graph.
+    pub fn ui(&self, ctx: &egui::Context) {
+        egui::Window::new("Profiler")
+            .default_width(300.0)
+            .show(ctx, |ui| {
+                if self.gpu.is_none() {
+                    ui.label("GPU timestamps aren't supported");
+                }
+
+                let recent: Vec<_> = self.history.iter().rev().take(AVERAGE_LEN).collect();
+                let average = |time: &dyn Fn(&FrameTimings) -> Option<Duration>| {
+                    let times: Vec<_> = recent.iter().filter_map(|t| time(t)).collect();
+                    (!times.is_empty())
+                        .then(|| millis(times.iter().sum::<Duration>()) / times.len() as f64)
+                };
+
+                egui::Grid::new("profiler breakdown")
+                    .num_columns(2)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        let mut row = |name: &str, ms: Option<f64>| {
+                            if let Some(ms) = ms {
+                                ui.label(name);
+                                ui.label(format!("{:.3} ms", ms));
+                                ui.end_row();
+                            }
+                        };
+
+                        row("GPU frame", average(&|t| t.gpu));
+                        if let Some(latest) = recent.first() {
+                            for pass in &latest.passes {
+                                let indent = "  ".repeat(pass.depth + 1);
+                                row(
+                                    &format!("{}{}", indent, pass.name),
+                                    average(&|t| {
+                                        let same = |p: &&PassTiming| p.name == pass.name;
+                                        t.passes.iter().find(same).map(|p| p.time)
+                                    }),
+                                );
+                            }
+                        }
+                        row("frame interval", average(&|t| Some(t.interval)));
+                        row("  jitter", jitter(&recent));
+                        row("CPU waiting for frame", average(&|t| Some(t.wait)));
+                        row("CPU recording", average(&|t| Some(t.record)));
+                        row("CPU submission", average(&|t| Some(t.submit)));
+                    });
+
+                let line = |name: &str, time: &dyn Fn(&FrameTimings) -> Option<Duration>| {
+                    let points: Vec<[f64; 2]> = self
+                        .history
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(i, t)| Some([i as f64, millis(time(t)?)]))
+                        .collect();
+                    Line::new(PlotPoints::from(points)).name(name)
+                };
+
+                Plot::new("frame times")
+                    .height(150.0)
+                    .include_y(0.0)
+                    .legend(Legend::default())
+                    .show(ui, |plot_ui| {
+                        plot_ui.line(line("frame interval", &|t| Some(t.interval)));
+                        plot_ui.line(line("GPU frame", &|t| t.gpu));
+                        plot_ui.line(line("CPU waiting", &|t| Some(t.wait)));
+                        plot_ui.line(line("CPU recording", &|t| Some(t.record)));
+                        plot_ui.line(line("CPU submission", &|t| Some(t.submit)));
+                    });
+            });
+    }
+}
+
+impl StatisticsQueries {
+    /// The statistics of every query used, summed, or `None` when they can't be read.
+    fn read_back(&self) -> Result<Option<PipelineStatistics>> {
+        let used = self.used.get();
+        let mut results = vec![0u64; used as usize * PipelineStatistics::LEN];
+        if used > 0 {
+            // `used` never goes past the pool's size, but a frame without statistics is better
+            // than a panic if it did.
+            let Some(queries) = self.pool.queries_range(0..used) else {
+                log::warn!("can't get pipeline statistics queries 0..{}", used);
+                return Ok(None);
+            };
+            queries
+                .get_results(
+                    &mut results,
+                    QueryResultFlags {
+                        wait: true,
+                        ..Default::default()
+                    },
+                )
+                .context("can't get pipeline statistics")?;
+        }
+
+        let mut statistics = PipelineStatistics::default();
+        for query in results.chunks_exact(PipelineStatistics::LEN) {
+            statistics += PipelineStatistics::from_results(query);
+        }
+        Ok(Some(statistics))
+    }
+}
+
+impl GpuTimer {
+    /// Reads back the timestamps of the frame in flight `index` into `timings`.
+    fn read_back(&mut self, index: usize, timings: &mut FrameTimings) -> Result<()> {
+        let (mask, period) = (self.mask, self.period);
+        let frame = &mut self.frames[index];
+        let passes = frame.passes.get_mut();
+        let count = passes.len() as u32 * 2;
+        if count == 0 {
+            return Ok(());
+        }
+
+        let mut timestamps = vec![0u64; count as usize];
+        // Like the statistics, the frame goes without timestamps rather than panicking.
+        let Some(queries) = frame.pool.queries_range(0..count) else {
+            log::warn!("can't get timestamp queries 0..{}", count);
+            return Ok(());
+        };
+        queries
+            .get_results(
+                &mut timestamps,
+                QueryResultFlags {
+                    wait: true,
+                    ..Default::default()
+                },
+            )
+            .context("can't get timestamps")?;
+
+        // Timestamps wrap around at the number of valid bits.
+        let elapsed = |begin: u64, end: u64| {
+            let ticks = end.wrapping_sub(begin) & mask;
+            Duration::from_nanos((ticks as f64 * period).round() as u64)
+        };
+        let passes = passes
+            .iter()
+            .zip(timestamps.chunks_exact(2))
+            .map(|(&(name, depth), t)| PassTiming {
+                name,
+                depth,
+                time: elapsed(t[0], t[1]),
+            })
+            .collect();
+        let begin = timestamps.iter().step_by(2).min().copied().unwrap_or(0);
+        let end = timestamps
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .max()
+            .copied()
+            .unwrap_or(0);
+
+        timings.passes = passes;
+        timings.gpu = Some(elapsed(begin, end));
+        Ok(())
+    }
+}
+
+/// The standard deviation of the frame intervals, in milliseconds. Steady pacing keeps it close
+/// to zero even when the frames themselves are slow.
+fn jitter(frames: &[&FrameTimings]) -> Option<f64> {
+    if frames.len() < 2 {
+        return None;
+    }
+
+    let intervals: Vec<_> = frames.iter().map(|t| millis(t.interval)).collect();
+    let mean = intervals.iter().sum::<f64>() / intervals.len() as f64;
+    let variance =
+        intervals.iter().map(|ms| (ms - mean).powi(2)).sum::<f64>() / intervals.len() as f64;
+    Some(variance.sqrt())
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
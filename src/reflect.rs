@@ -0,0 +1,394 @@
+//! Checks runtime-loaded shaders against the Rust side before a pipeline is built from them.
+//!
+//! Vertex layouts are declared twice, once in GLSL and once with `impl_vertex!`, and descriptor
+//! bindings are only assumed by the code that writes the sets. Shaders compiled into the binary
+//! are checked by `vulkano_shaders` at build time, but a GLSL file edited while a demo is running
+//! is not, so these functions compare the SPIR-V reflection data of an entry point with what the
+//! renderer provides and say exactly which location or binding disagrees.
+
+use std::fmt;
+
+use vulkano::descriptor_set::layout::DescriptorType;
+use vulkano::format::{Format, NumericType};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::shader::{
+    DescriptorRequirements, EntryPoint, ShaderInterface, ShaderInterfaceEntryType, ShaderScalarType,
+};
+
+#[derive(Debug)]
+pub enum ReflectError {
+    /// An input has no name, usually because the SPIR-V was built without debug info, so it
+    /// can't be matched to a field of the vertex type.
+    UnnamedVertexInput { location: u32 },
+    /// The shader reads an input the vertex type has no field for.
+    MissingVertexMember {
+        location: u32,
+        name: String,
+        vertex: &'static str,
+    },
+    /// The field exists, but its format doesn't fit the shader type at that location.
+    VertexMemberMismatch {
+        location: u32,
+        name: String,
+        shader: String,
+        rust: String,
+    },
+    /// The shader uses a descriptor the renderer doesn't bind.
+    UnexpectedDescriptor {
+        set: u32,
+        binding: u32,
+        types: Vec<DescriptorType>,
+    },
+    /// The renderer binds a different kind of descriptor than the shader declares.
+    DescriptorTypeMismatch {
+        set: u32,
+        binding: u32,
+        expected: DescriptorType,
+        found: Vec<DescriptorType>,
+    },
+}
+
+impl fmt::Display for ReflectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnnamedVertexInput { location } => write!(
+                f,
+                "vertex input at location {} has no name, compile the shader with debug info",
+                location
+            ),
+            Self::MissingVertexMember {
+                location,
+                name,
+                vertex,
+            } => write!(
+                f,
+                "vertex input `{}` at location {} is not a field of `{}`",
+                name, location, vertex
+            ),
+            Self::VertexMemberMismatch {
+                location,
+                name,
+                shader,
+                rust,
+            } => write!(
+                f,
+                "vertex input `{}` at location {} is {} in the shader but {} in Rust",
+                name, location, shader, rust
+            ),
+            Self::UnexpectedDescriptor {
+                set,
+                binding,
+                types,
+            } => write!(
+                f,
+                "shader uses a {:?} at set {}, binding {}, but nothing is bound there",
+                types, set, binding
+            ),
+            Self::DescriptorTypeMismatch {
+                set,
+                binding,
+                expected,
+                found,
+            } => write!(
+                f,
+                "set {}, binding {} is bound as {:?}, but the shader declares {:?}",
+                set, binding, expected, found
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReflectError {}
+
+/// A descriptor the renderer writes into its sets.
+#[derive(Clone, Copy, Debug)]
+pub struct ExpectedDescriptor {
+    pub set: u32,
+    pub binding: u32,
+    pub ty: DescriptorType,
+}
+
+/// Checks that every input of a vertex shader has a field of the same name and a compatible
+/// format in `V`, the same way `BuffersDefinition::vertex::<V>()` matches them.
+pub fn check_vertex_input<V: Vertex>(entry_point: &EntryPoint) -> Result<(), ReflectError> {
+    check_vertex_interface::<V>(entry_point.input_interface())
+}
+
+fn check_vertex_interface<V: Vertex>(inputs: &ShaderInterface) -> Result<(), ReflectError> {
+    for element in inputs.elements() {
+        let location = element.location;
+        let name = element
+            .name
+            .as_ref()
+            .ok_or(ReflectError::UnnamedVertexInput { location })?;
+
+        let member = V::member(name).ok_or_else(|| ReflectError::MissingVertexMember {
+            location,
+            name: name.to_string(),
+            vertex: std::any::type_name::<V>(),
+        })?;
+
+        if !format_matches(member.format, member.num_elements, &element.ty) {
+            return Err(ReflectError::VertexMemberMismatch {
+                location,
+                name: name.to_string(),
+                shader: describe_shader_type(&element.ty),
+                rust: describe_format(member.format, member.num_elements),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that every descriptor the entry point uses is one of `expected`, with the same type.
+/// Descriptors the renderer binds but the shader doesn't use are fine, the compiler strips
+/// unused bindings.
+pub fn check_descriptors(
+    entry_point: &EntryPoint,
+    expected: &[ExpectedDescriptor],
+) -> Result<(), ReflectError> {
+    check_descriptor_requirements(entry_point.descriptor_requirements(), expected)
+}
+
+fn check_descriptor_requirements<'a>(
+    requirements: impl IntoIterator<Item = ((u32, u32), &'a DescriptorRequirements)>,
+    expected: &[ExpectedDescriptor],
+) -> Result<(), ReflectError> {
+    for ((set, binding), requirements) in requirements {
+        let types = &requirements.descriptor_types;
+        let expected = expected
+            .iter()
+            .find(|e| e.set == set && e.binding == binding)
+            .ok_or_else(|| ReflectError::UnexpectedDescriptor {
+                set,
+                binding,
+                types: types.clone(),
+            })?;
+
+        if !types.contains(&expected.ty) {
+            return Err(ReflectError::DescriptorTypeMismatch {
+                set,
+                binding,
+                expected: expected.ty,
+                found: types.clone(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+fn format_matches(format: Format, num_elements: u32, ty: &ShaderInterfaceEntryType) -> bool {
+    let components = format.components();
+    let num_components = components.iter().filter(|&&bits| bits != 0).count() as u32;
+    let is_64bit = components[0] == 64;
+
+    // Normalized and scaled formats are read as floats by the shader.
+    let scalar = match format.type_color() {
+        Some(NumericType::SINT) => ShaderScalarType::Sint,
+        Some(NumericType::UINT) => ShaderScalarType::Uint,
+        Some(_) => ShaderScalarType::Float,
+        None => return false,
+    };
+
+    scalar == ty.base_type
+        && num_components == ty.num_components
+        && num_elements == ty.num_elements
+        && is_64bit == ty.is_64bit
+}
+
+fn describe_shader_type(ty: &ShaderInterfaceEntryType) -> String {
+    let prefix = match (ty.base_type, ty.is_64bit) {
+        (ShaderScalarType::Float, false) => "",
+        (ShaderScalarType::Float, true) => "d",
+        (ShaderScalarType::Sint, _) => "i",
+        (ShaderScalarType::Uint, _) => "u",
+    };
+    let base = match (ty.num_components, ty.base_type, ty.is_64bit) {
+        (1, ShaderScalarType::Float, false) => "float".to_owned(),
+        (1, ShaderScalarType::Float, true) => "double".to_owned(),
+        (1, ShaderScalarType::Sint, _) => "int".to_owned(),
+        (1, ShaderScalarType::Uint, _) => "uint".to_owned(),
+        (n, _, _) => format!("{}vec{}", prefix, n),
+    };
+
+    if ty.num_elements > 1 {
+        format!("{}[{}]", base, ty.num_elements)
+    } else {
+        base
+    }
+}
+
+fn describe_format(format: Format, num_elements: u32) -> String {
+    if num_elements > 1 {
+        format!("{:?}[{}]", format, num_elements)
+    } else {
+        format!("{:?}", format)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytemuck::{Pod, Zeroable};
+    use shaderc::ShaderKind;
+    use vulkano::impl_vertex;
+    use vulkano::shader::reflect::{self, EntryPointInfo};
+    use vulkano::shader::spirv::Spirv;
+
+    use super::*;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
+    struct TestVertex {
+        position: [f32; 3],
+        color: [f32; 4],
+    }
+
+    impl_vertex!(TestVertex, position, color);
+
+    const DESCRIPTORS: [ExpectedDescriptor; 2] = [
+        ExpectedDescriptor {
+            set: 0,
+            binding: 0,
+            ty: DescriptorType::CombinedImageSampler,
+        },
+        ExpectedDescriptor {
+            set: 0,
+            binding: 1,
+            ty: DescriptorType::UniformBuffer,
+        },
+    ];
+
+    /// The reflection data of `main` in `source`, compiled with the names kept.
+    fn reflect(kind: ShaderKind, source: &str) -> EntryPointInfo {
+        let compiler = shaderc::Compiler::new().unwrap();
+        let mut options = shaderc::CompileOptions::new().unwrap();
+        options.set_generate_debug_info();
+        let artifact = compiler
+            .compile_into_spirv(source, kind, "test.glsl", "main", Some(&options))
+            .unwrap();
+        let spirv = Spirv::new(artifact.as_binary()).unwrap();
+        reflect::entry_points(&spirv)
+            .find(|(name, _, _)| name == "main")
+            .map(|(_, _, info)| info)
+            .unwrap()
+    }
+
+    fn check_vertex(source: &str) -> Result<(), ReflectError> {
+        let info = reflect(ShaderKind::Vertex, source);
+        check_vertex_interface::<TestVertex>(&info.input_interface)
+    }
+
+    fn check_fragment(expected: &[ExpectedDescriptor]) -> Result<(), ReflectError> {
+        let info = reflect(
+            ShaderKind::Fragment,
+            "
+            #version 450
+            layout(set = 0, binding = 0) uniform sampler2D tex;
+            layout(set = 0, binding = 1) uniform Data { vec4 color; } data;
+            layout(location = 0) out vec4 f_color;
+            void main() {
+                f_color = texture(tex, vec2(0.5)) * data.color;
+            }",
+        );
+        let requirements = info
+            .descriptor_requirements
+            .iter()
+            .map(|(&key, requirements)| (key, requirements));
+        check_descriptor_requirements(requirements, expected)
+    }
+
+    #[test]
+    fn matching_vertex_inputs_pass() {
+        let result = check_vertex(
+            "
+            #version 450
+            layout(location = 0) in vec3 position;
+            layout(location = 1) in vec4 color;
+            void main() {
+                gl_Position = vec4(position, 1.0) * color.a;
+            }",
+        );
+        assert!(result.is_ok(), "{}", result.unwrap_err());
+    }
+
+    #[test]
+    fn missing_vertex_members_are_named() {
+        let result = check_vertex(
+            "
+            #version 450
+            layout(location = 0) in vec3 position;
+            layout(location = 1) in vec2 uv;
+            void main() {
+                gl_Position = vec4(position, uv.x);
+            }",
+        );
+        match result {
+            Err(ReflectError::MissingVertexMember { location, name, .. }) => {
+                assert_eq!((location, name.as_str()), (1, "uv"));
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn mismatched_vertex_formats_are_reported() {
+        let result = check_vertex(
+            "
+            #version 450
+            layout(location = 0) in vec2 position;
+            void main() {
+                gl_Position = vec4(position, 0.0, 1.0);
+            }",
+        );
+        match result {
+            Err(ReflectError::VertexMemberMismatch {
+                location,
+                name,
+                shader,
+                ..
+            }) => {
+                assert_eq!((location, name.as_str()), (0, "position"));
+                assert_eq!(shader, "vec2");
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn matching_descriptors_pass() {
+        let result = check_fragment(&DESCRIPTORS);
+        assert!(result.is_ok(), "{}", result.unwrap_err());
+    }
+
+    #[test]
+    fn descriptors_at_other_bindings_are_unexpected() {
+        let mut expected = DESCRIPTORS;
+        expected[1].binding = 2;
+        match check_fragment(&expected) {
+            Err(ReflectError::UnexpectedDescriptor { set, binding, .. }) => {
+                assert_eq!((set, binding), (0, 1));
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn descriptors_of_another_type_are_reported() {
+        let mut expected = DESCRIPTORS;
+        expected[1].ty = DescriptorType::StorageBuffer;
+        match check_fragment(&expected) {
+            Err(ReflectError::DescriptorTypeMismatch {
+                set,
+                binding,
+                expected,
+                ..
+            }) => {
+                assert_eq!((set, binding), (0, 1));
+                assert_eq!(expected, DescriptorType::StorageBuffer);
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+}
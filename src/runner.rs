@@ -0,0 +1,734 @@
+//! The frame loop the windowed demos share.
+//!
+//! A demo describes what it draws as an [`App`], and [`run`] does everything else: it creates
+//! the instance, device and swapchain with [`crate::setup`], acquires, submits and presents each
+//! frame, recreates the swapchain when the window or the present settings change, and handles
+//! `--frames`, `--output` and strict validation runs. A failed frame goes through
+//! [`frame_error`] to decide how to carry on: a lost surface or device is created again, along
+//! with everything made from it. [`crate::fault`] simulates those losses.
+//!
+//! With `--headless`, which `--output` implies, the frames are drawn to an offscreen image
+//! instead, see [`run_headless`].
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer,
+};
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::device::{Device, DeviceExtensions, DeviceOwned};
+use vulkano::format::Format;
+use vulkano::image::{ImageAccess, ImageUsage, ImageViewAbstract};
+use vulkano::instance::Instance;
+use vulkano::memory::allocator::StandardMemoryAllocator;
+use vulkano::pipeline::graphics::viewport::Viewport;
+use vulkano::swapchain::{
+    acquire_next_image, Surface, Swapchain, SwapchainCreateInfo, SwapchainPresentInfo,
+};
+use vulkano::sync::GpuFuture;
+use vulkano_win::VkSurfaceBuild;
+use winit::event::{Event, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop, EventLoopWindowTarget};
+
+use crate::capture::FrameCapture;
+use crate::cli::Options;
+use crate::debug::Validation;
+use crate::error::{frame_error, Context, Error, Recovery, Result};
+use crate::fault::FaultInjector;
+use crate::frames::{self, FramesInFlight};
+use crate::gpu;
+use crate::pipeline_cache::PersistentPipelineCache;
+use crate::present::{
+    DynamicRange, FrameLimiter, OutputEncoding, PresentSettings, SurfaceFormat, SurfaceSupport,
+};
+use crate::setup::{self, Queues};
+use crate::upload::Uploader;
+
+/// A demo drawn by [`run`].
+pub trait App {
+    /// Everything the app created from the device.
+    type Renderer: 'static;
+
+    /// Creates the renderer for frames drawn to `target`. `event_loop` is the one of the
+    /// window, for the apps that draw a GUI, and `None` when running headless.
+    ///
+    /// It's called again with a new device when the device was lost, so everything uploaded has
+    /// to be kept on the CPU as well.
+    fn create(
+        &mut self,
+        gpu: &Gpu,
+        target: &Target,
+        event_loop: Option<&EventLoopWindowTarget<()>>,
+    ) -> Result<Self::Renderer>;
+
+    /// Rebuilds what depends on the images of `target`, after they were recreated.
+    fn resize(&mut self, renderer: &mut Self::Renderer, gpu: &Gpu, target: &Target) -> Result<()>;
+
+    /// Handles an event of the window. Does nothing by default.
+    fn window_event(&mut self, _renderer: &mut Self::Renderer, _event: &WindowEvent) {}
+
+    /// Records a frame into `frame.builder`.
+    fn draw(
+        &mut self,
+        renderer: &mut Self::Renderer,
+        gpu: &Gpu,
+        target: &Target,
+        frame: &mut Frame,
+    ) -> Result<()>;
+
+    /// Called once the frame the last [`Self::draw`] recorded was submitted, with the CPU time
+    /// spent waiting for its frame in flight, recording it and submitting it. Not called for a
+    /// frame that failed. Does nothing by default.
+    fn submitted(&mut self, _renderer: &mut Self::Renderer, _timings: SubmitTimings) {}
+}
+
+/// The CPU side of a submitted frame, see [`App::submitted`].
+#[derive(Clone, Copy, Debug)]
+pub struct SubmitTimings {
+    pub wait: Duration,
+    pub record: Duration,
+    pub submit: Duration,
+}
+
+/// The device, its queues and the allocators an app creates its objects with.
+pub struct Gpu {
+    pub device: Arc<Device>,
+    pub queues: Queues,
+    pub memory_allocator: Arc<StandardMemoryAllocator>,
+    pub descriptor_set_allocator: StandardDescriptorSetAllocator,
+    pub command_buffer_allocator: StandardCommandBufferAllocator,
+    pub pipeline_cache: PersistentPipelineCache,
+}
+
+impl Gpu {
+    /// Creates the device for the GPU picked with `--gpu`, which has to be able to present to
+    /// `surface` when there is one.
+    fn new(instance: &Arc<Instance>, surface: Option<&Surface>, options: &Options) -> Result<Self> {
+        let device_extensions = DeviceExtensions {
+            khr_swapchain: surface.is_some(),
+            ..DeviceExtensions::empty()
+        };
+        let (physical_device, queue_family_index) = setup::select_physical_device(
+            instance,
+            options.gpu.as_ref(),
+            surface,
+            &device_extensions,
+        )?;
+        log::info!(
+            "physical device: {}",
+            physical_device.properties().device_name
+        );
+        let (device, queues) =
+            setup::create_device(physical_device, queue_family_index, device_extensions)?;
+
+        Ok(Self {
+            memory_allocator: Arc::new(StandardMemoryAllocator::new_default(device.clone())),
+            descriptor_set_allocator: StandardDescriptorSetAllocator::new(device.clone()),
+            command_buffer_allocator: StandardCommandBufferAllocator::new(
+                device.clone(),
+                Default::default(),
+            ),
+            pipeline_cache: PersistentPipelineCache::load(&device)?,
+            device,
+            queues,
+        })
+    }
+
+    /// Records uploads for every queue of the device, see [`Uploader::for_queues`].
+    pub fn uploader(&self) -> Result<Uploader> {
+        Uploader::for_queues(&self.command_buffer_allocator, &self.queues)
+    }
+}
+
+/// The images frames are drawn to: the swapchain of the window, or a single offscreen image.
+pub struct Target {
+    /// `None` when running headless.
+    surface: Option<Arc<Surface>>,
+    swapchain: Option<Arc<Swapchain>>,
+    /// What the surface supports, as of the last time the swapchain was created.
+    support: Option<SurfaceSupport>,
+    surface_format: SurfaceFormat,
+    views: Vec<Arc<dyn ImageViewAbstract>>,
+}
+
+impl Target {
+    /// Creates the swapchain of `surface`, in the format [`setup::select_surface_format`] picks.
+    fn new(gpu: &Gpu, surface: Arc<Surface>, options: &Options) -> Result<Self> {
+        let surface_format =
+            setup::select_surface_format(&gpu.device, &surface, DynamicRange::from_env())?;
+        let image_usage = ImageUsage {
+            color_attachment: true,
+            ..ImageUsage::empty()
+        };
+        let (swapchain, images) = setup::create_swapchain(
+            &gpu.device,
+            &surface,
+            image_usage,
+            surface_format,
+            &options.present,
+        )?;
+        let support = SurfaceSupport::query(gpu.device.physical_device(), &surface)?;
+
+        Ok(Self {
+            surface: Some(surface),
+            swapchain: Some(swapchain),
+            support: Some(support),
+            surface_format,
+            views: setup::swapchain_views(images)?,
+        })
+    }
+
+    /// Creates the image a headless run draws to, at `--resolution`.
+    fn offscreen(gpu: &Gpu, options: &Options) -> Result<Self> {
+        let dimensions = options.resolution.unwrap_or(setup::OFFSCREEN_RESOLUTION);
+        let view = setup::create_offscreen_image(&gpu.memory_allocator, dimensions)?;
+
+        Ok(Self {
+            surface: None,
+            swapchain: None,
+            support: None,
+            surface_format: SurfaceFormat::offscreen(setup::OFFSCREEN_FORMAT),
+            views: vec![view],
+        })
+    }
+
+    /// Recreates the swapchain at `dimensions` with `present`, along with the views of its
+    /// images. The offscreen image never changes.
+    fn recreate(&mut self, dimensions: [u32; 2], present: &PresentSettings) -> Result<()> {
+        let (Some(old), Some(surface)) = (&self.swapchain, &self.surface) else {
+            return Ok(());
+        };
+        let support = SurfaceSupport::query(old.device().physical_device(), surface)?;
+        let (swapchain, images) = old.recreate(SwapchainCreateInfo {
+            image_extent: dimensions,
+            min_image_count: present.image_count(&support),
+            present_mode: present.present_mode(&support),
+            ..old.create_info()
+        })?;
+        self.swapchain = Some(swapchain);
+        self.support = Some(support);
+        self.views = setup::swapchain_views(images)?;
+        Ok(())
+    }
+
+    pub fn format(&self) -> Format {
+        self.surface_format.format
+    }
+
+    /// How the shaders have to encode their colors for this target.
+    pub fn encoding(&self) -> OutputEncoding {
+        self.surface_format.encoding
+    }
+
+    pub fn dimensions(&self) -> [u32; 2] {
+        self.views[0].image().dimensions().width_height()
+    }
+
+    /// A view of every image, in the order the frames' `image_index` refers to them.
+    pub fn views(&self) -> &[Arc<dyn ImageViewAbstract>] {
+        &self.views
+    }
+
+    /// A viewport covering the whole image.
+    pub fn viewport(&self) -> Viewport {
+        let [width, height] = self.dimensions();
+        Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [width as f32, height as f32],
+            depth_range: 0.0..1.0,
+        }
+    }
+
+    /// The surface of the window, `None` when running headless.
+    pub fn surface(&self) -> Option<&Arc<Surface>> {
+        self.surface.as_ref()
+    }
+
+    /// What the surface supports, `None` when running headless.
+    pub fn support(&self) -> Option<&SurfaceSupport> {
+        self.support.as_ref()
+    }
+}
+
+/// A frame being recorded.
+pub struct Frame {
+    /// The image drawn to, an index into [`Target::views`].
+    pub image_index: usize,
+    /// How many frames were drawn before this one.
+    pub number: u64,
+    /// Which frame in flight this is. Its fence was waited for, so what the app keeps for each
+    /// frame in flight, [`frames::frames_in_flight`] of them, is free to reuse at this index.
+    pub in_flight: usize,
+    /// The present settings, starting from `--present-mode` and the like. When the app changes
+    /// them, the swapchain is recreated with them before the next frame.
+    pub present: PresentSettings,
+    /// The command buffer submitted to the graphics queue.
+    pub builder: AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    /// Work for the compute queue that `builder` depends on. It's submitted first, after the
+    /// previous frame, and the graphics work waits on it with a semaphore.
+    pub compute: Option<PrimaryAutoCommandBuffer>,
+}
+
+/// Runs `app` in a window, until the window is closed or the frames asked for with `--frames`
+/// are drawn. Only returns early, when setting up fails. With `--headless` it's
+/// [`run_headless`] instead.
+pub fn run<A: App + 'static>(options: Options, mut app: A) -> Result<()> {
+    if options.headless {
+        return run_headless(options, app);
+    }
+
+    let instance = setup::create_instance()?;
+    if options.list_gpus {
+        return gpu::list(&instance);
+    }
+    let mut validation = Validation::new(&instance);
+
+    let event_loop = EventLoop::new();
+    let surface = options
+        .window_builder()
+        .build_vk_surface(&event_loop, instance.clone())?;
+
+    let gpu = Gpu::new(&instance, Some(&*surface), &options)?;
+    let target = Target::new(&gpu, surface, &options)?;
+    let renderer = app.create(&gpu, &target, Some(&event_loop))?;
+    let faults = FaultInjector::from_env();
+    let mut runner = Runner::new(options, app, gpu, target, renderer, faults)?;
+
+    event_loop.run(move |event, window_target, control_flow| match event {
+        Event::WindowEvent { event, .. } => {
+            runner.app.window_event(&mut runner.renderer, &event);
+            match event {
+                WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                WindowEvent::Resized(_) | WindowEvent::ScaleFactorChanged { .. } => {
+                    runner.recreate_target = true
+                }
+                _ => (),
+            }
+        }
+        Event::RedrawEventsCleared => {
+            if validation.frame() {
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
+
+            let recovered = runner
+                .draw()
+                .or_else(|e| runner.recover(e, &instance, Some(window_target)));
+            if let Err(e) = recovered {
+                log::error!("{}, stopping", e);
+                runner.failed = true;
+                *control_flow = ControlFlow::Exit;
+            }
+            if runner.finished {
+                *control_flow = ControlFlow::Exit;
+            }
+        }
+        Event::LoopDestroyed => {
+            runner.gpu.pipeline_cache.save();
+            validation.finish();
+            if runner.failed {
+                std::process::exit(1);
+            }
+        }
+        _ => (),
+    })
+}
+
+/// Draws `options.frames` frames of `app` to an offscreen image, without opening a window.
+/// Fails when a frame does, or when the validation layer reported an error.
+pub fn run_headless<A: App>(options: Options, app: A) -> Result<()> {
+    run_headless_with(options, app, FaultInjector::from_env())
+}
+
+fn run_headless_with<A: App>(options: Options, mut app: A, faults: FaultInjector) -> Result<()> {
+    let instance = setup::create_instance()?;
+    if options.list_gpus {
+        return gpu::list(&instance);
+    }
+    let mut validation = Validation::new(&instance);
+
+    let gpu = Gpu::new(&instance, None, &options)?;
+    let target = Target::offscreen(&gpu, &options)?;
+    let renderer = app.create(&gpu, &target, None)?;
+    let mut runner = Runner::new(options, app, gpu, target, renderer, faults)?;
+
+    while !runner.finished && !validation.frame() {
+        if let Err(e) = runner.draw() {
+            runner.recover(e, &instance, None)?;
+        }
+    }
+
+    runner.gpu.pipeline_cache.save();
+    // Waits for the frames still in flight, so nothing is reported after the check.
+    drop(runner);
+    validation.check()
+}
+
+struct Runner<A: App> {
+    options: Options,
+    app: A,
+    gpu: Gpu,
+    target: Target,
+    renderer: A::Renderer,
+    frames: FramesInFlight<()>,
+    limiter: FrameLimiter,
+    faults: FaultInjector,
+    recreate_target: bool,
+    /// How many frames were drawn.
+    frame: u64,
+    /// Whether the last frame asked for was drawn.
+    finished: bool,
+    /// Whether rendering stopped on an error.
+    failed: bool,
+}
+
+impl<A: App> Runner<A> {
+    fn new(
+        options: Options,
+        app: A,
+        gpu: Gpu,
+        target: Target,
+        renderer: A::Renderer,
+        faults: FaultInjector,
+    ) -> Result<Self> {
+        let frames =
+            FramesInFlight::new(gpu.device.clone(), frames::frames_in_flight(), |_| Ok(()))?;
+
+        Ok(Self {
+            options,
+            app,
+            gpu,
+            target,
+            renderer,
+            frames,
+            limiter: FrameLimiter::new(),
+            faults,
+            recreate_target: false,
+            frame: 0,
+            finished: false,
+            failed: false,
+        })
+    }
+
+    /// Carries on after a frame failed with `error`, as [`frame_error`] decides. Returns the
+    /// error when it's fatal, or the one that stopped the recovery.
+    fn recover(
+        &mut self,
+        error: Error,
+        instance: &Arc<Instance>,
+        event_loop: Option<&EventLoopWindowTarget<()>>,
+    ) -> Result<()> {
+        if error.recovery() == Recovery::Fatal {
+            return Err(error);
+        }
+        match frame_error(error) {
+            Recovery::RecreateSwapchain => self.recreate_target = true,
+            Recovery::SkipFrame | Recovery::Fatal => (),
+            Recovery::RecreateSurface => {
+                let surface = self.new_surface(instance)?;
+                self.target = self.new_target(surface)?;
+                self.app
+                    .resize(&mut self.renderer, &self.gpu, &self.target)?;
+            }
+            Recovery::RecreateDevice => {
+                // The old swapchain lives on in the old renderer until it's replaced, and a
+                // surface can't have two, so the new one gets a new surface as well.
+                let surface = self.new_surface(instance)?;
+                self.gpu = Gpu::new(instance, surface.as_deref(), &self.options)?;
+                self.target = self.new_target(surface)?;
+                self.renderer = self.app.create(&self.gpu, &self.target, event_loop)?;
+                self.frames = FramesInFlight::new(
+                    self.gpu.device.clone(),
+                    frames::frames_in_flight(),
+                    |_| Ok(()),
+                )?;
+            }
+        }
+        log::debug!("recovered");
+        Ok(())
+    }
+
+    /// A new surface for the window, `None` when running headless.
+    fn new_surface(&self, instance: &Arc<Instance>) -> Result<Option<Arc<Surface>>> {
+        self.target
+            .surface()
+            .map(|surface| setup::recreate_surface(surface, instance))
+            .transpose()
+    }
+
+    /// The target for `surface`, or a new offscreen image without one.
+    fn new_target(&self, surface: Option<Arc<Surface>>) -> Result<Target> {
+        match surface {
+            Some(surface) => Target::new(&self.gpu, surface, &self.options),
+            None => Target::offscreen(&self.gpu, &self.options),
+        }
+    }
+
+    fn draw(&mut self) -> Result<()> {
+        let window_dimensions = self
+            .target
+            .surface
+            .as_ref()
+            .map(|surface| <[u32; 2]>::from(setup::window(surface).inner_size()));
+        if let Some(dimensions) = window_dimensions {
+            if dimensions.contains(&0) {
+                return Ok(());
+            }
+            self.limiter.wait(self.options.present.fps_limit);
+        }
+
+        if let Some(fault) = self.faults.next_frame() {
+            return Err(fault.into());
+        }
+        let wait = self.frames.next()?;
+
+        if let (true, Some(dimensions)) = (self.recreate_target, window_dimensions) {
+            self.target.recreate(dimensions, &self.options.present)?;
+            self.app
+                .resize(&mut self.renderer, &self.gpu, &self.target)?;
+            self.recreate_target = false;
+        }
+
+        let (image_index, acquire_future) = match self.target.swapchain.clone() {
+            Some(swapchain) => {
+                let (image_index, suboptimal, acquire_future) =
+                    acquire_next_image(swapchain, None)?;
+                if suboptimal {
+                    self.recreate_target = true;
+                }
+                (image_index as usize, Some(acquire_future))
+            }
+            // Every frame draws to the one offscreen image, each after the one before it.
+            None => (0, None),
+        };
+
+        let record_start = Instant::now();
+        let builder = AutoCommandBufferBuilder::primary(
+            &self.gpu.command_buffer_allocator,
+            self.gpu.queues.graphics.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .context("can't create command buffer builder")?;
+        let mut frame = Frame {
+            image_index,
+            number: self.frame,
+            in_flight: self.frames.index(),
+            present: self.options.present,
+            builder,
+            compute: None,
+        };
+        self.app
+            .draw(&mut self.renderer, &self.gpu, &self.target, &mut frame)?;
+        let (before, after) = (self.options.present, frame.present);
+        // Only the frame limit applies without a new swapchain.
+        if (after.present_mode, after.image_count) != (before.present_mode, before.image_count) {
+            self.recreate_target = true;
+        }
+        self.options.present = after;
+
+        let capture = self
+            .options
+            .output_for(self.frame)
+            .map(|path| {
+                FrameCapture::record(
+                    &mut frame.builder,
+                    &*self.gpu.memory_allocator,
+                    self.target.views[frame.image_index].image(),
+                    path,
+                )
+            })
+            .transpose()?;
+        let command_buffer = frame
+            .builder
+            .build()
+            .context("can't build command buffer")?;
+        let record = record_start.elapsed();
+
+        let submit_start = Instant::now();
+        let previous = self.frames.previous_future();
+        let previous = match frame.compute {
+            Some(compute) => {
+                // The compute queue doesn't follow the graphics queue by itself, and the previous
+                // frame may still be reading what the compute work writes. The semaphore makes it
+                // wait. There's nothing to wait for before the first frame.
+                let previous = match previous.queue() {
+                    Some(_) => previous.then_signal_semaphore().boxed(),
+                    None => previous,
+                };
+                previous
+                    .then_execute(self.gpu.queues.compute.clone(), compute)
+                    .context("can't execute compute command buffer")?
+                    .then_signal_semaphore()
+                    .boxed()
+            }
+            None => previous,
+        };
+
+        let previous = match acquire_future {
+            Some(acquire_future) => previous.join(acquire_future).boxed(),
+            None => previous,
+        };
+
+        let queue = self.gpu.queues.graphics.clone();
+        let future = previous
+            .then_execute(queue.clone(), command_buffer)
+            .context("can't execute command buffer")?;
+        let future = match &self.target.swapchain {
+            Some(swapchain) => future
+                .then_swapchain_present(
+                    queue,
+                    SwapchainPresentInfo::swapchain_image_index(
+                        swapchain.clone(),
+                        image_index as u32,
+                    ),
+                )
+                .boxed(),
+            None => future.boxed(),
+        }
+        .then_signal_fence_and_flush();
+        let future = match future {
+            Ok(future) => future,
+            Err(e) => {
+                self.frames.submitted(None);
+                return Err(e.into());
+            }
+        };
+        let timings = SubmitTimings {
+            wait,
+            record,
+            submit: submit_start.elapsed(),
+        };
+        self.app.submitted(&mut self.renderer, timings);
+
+        if let Some(capture) = capture {
+            future.wait(None)?;
+            capture.save()?;
+        }
+        self.frames.submitted(Some(future));
+        self.finished = self.options.is_last_frame(self.frame);
+        self.frame += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer, DeviceLocalBuffer};
+    use vulkano::command_buffer::CopyBufferInfo;
+
+    use super::*;
+    use crate::fault::Fault;
+
+    const DATA: [u32; 5] = [1, 2, 3, 5, 8];
+
+    /// A renderer [`Retained`] created, and where its frames copy what it uploaded back to.
+    struct Created {
+        device: Arc<Device>,
+        readback: Arc<CpuAccessibleBuffer<[u32]>>,
+    }
+
+    /// Keeps [`DATA`] on the CPU and uploads it for every renderer it creates, like the demos do
+    /// with their meshes and textures.
+    struct Retained {
+        created: Rc<RefCell<Vec<Created>>>,
+    }
+
+    struct RetainedRenderer {
+        buffer: Arc<DeviceLocalBuffer<[u32]>>,
+        readback: Arc<CpuAccessibleBuffer<[u32]>>,
+    }
+
+    impl App for Retained {
+        type Renderer = RetainedRenderer;
+
+        fn create(
+            &mut self,
+            gpu: &Gpu,
+            _target: &Target,
+            _event_loop: Option<&EventLoopWindowTarget<()>>,
+        ) -> Result<RetainedRenderer> {
+            let mut uploader = gpu.uploader()?;
+            let buffer = uploader.buffer_from_iter(
+                &*gpu.memory_allocator,
+                BufferUsage {
+                    transfer_src: true,
+                    ..BufferUsage::empty()
+                },
+                DATA,
+            )?;
+            uploader.submit_and_wait()?;
+
+            let readback = CpuAccessibleBuffer::from_iter(
+                &*gpu.memory_allocator,
+                BufferUsage {
+                    transfer_dst: true,
+                    ..BufferUsage::empty()
+                },
+                true,
+                DATA.map(|_| 0),
+            )
+            .context("can't create readback buffer")?;
+            self.created.borrow_mut().push(Created {
+                device: gpu.device.clone(),
+                readback: readback.clone(),
+            });
+
+            Ok(RetainedRenderer { buffer, readback })
+        }
+
+        fn resize(
+            &mut self,
+            _renderer: &mut RetainedRenderer,
+            _gpu: &Gpu,
+            _target: &Target,
+        ) -> Result<()> {
+            Ok(())
+        }
+
+        fn draw(
+            &mut self,
+            renderer: &mut RetainedRenderer,
+            _gpu: &Gpu,
+            _target: &Target,
+            frame: &mut Frame,
+        ) -> Result<()> {
+            frame
+                .builder
+                .copy_buffer(CopyBufferInfo::buffers(
+                    renderer.buffer.clone(),
+                    renderer.readback.clone(),
+                ))
+                .context("can't copy buffer")?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn lost_device_is_recreated_from_retained_data() {
+        let created = Rc::new(RefCell::new(Vec::new()));
+        let app = Retained {
+            created: created.clone(),
+        };
+        let options = Options {
+            headless: true,
+            frames: Some(6),
+            ..Default::default()
+        };
+        let faults = FaultInjector::new(vec![(2, Fault::SurfaceLost), (4, Fault::DeviceLost)]);
+        run_headless_with(options, app, faults).unwrap();
+
+        let created = created.borrow();
+        // A lost surface only recreates the target, a lost device the renderer as well.
+        assert_eq!(created.len(), 2);
+        assert!(!Arc::ptr_eq(&created[0].device, &created[1].device));
+        for renderer in created.iter() {
+            assert_eq!(*renderer.readback.read().unwrap(), DATA);
+        }
+    }
+}
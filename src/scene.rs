@@ -0,0 +1,641 @@
+//! Scenes described in a TOML or RON file, so test scenes can be put together without changing
+//! a demo.
+//!
+//! A scene has a camera, lights, named materials and a list of entities, each a mesh drawn
+//! with a material at a transform. Entities are shaded with the lights by
+//! `shaders/lighting.glsl`, a scene without lights is drawn unlit. An entity with a `parent` is placed relative to it, see
+//! [`crate::scene_graph`]. Relative paths in the file are relative to the file itself.
+//! A [`SceneFile`] reloads the scene whenever the file changes.
+//!
+//! ```toml
+//! [camera]
+//! projection = { type = "orthographic" }
+//! transform = { translation = [-100.0, 0.0, 0.0] }
+//!
+//! [materials.checker]
+//! texture = "checker.png"
+//! color = [1.0, 0.5, 0.5, 1.0]
+//!
+//! [[entities]]
+//! name = "quad"
+//! mesh = { type = "sprite", size = [200.0, 200.0] }
+//! material = "checker"
+//! transform = { translation = [-175.0, 25.0, 0.0] }
+//!
+//! [[entities]]
+//! name = "badge"
+//! parent = "quad"
+//! mesh = { type = "sprite", size = [50.0, 50.0] }
+//! transform = { translation = [150.0, 150.0, 0.0], rotation = [0.0, 0.0, 45.0] }
+//!
+//! [[lights]]
+//! type = "point"
+//! position = [0.0, 100.0, 50.0]
+//! radius = 300.0
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use cgmath::{Deg, Matrix4, SquareMatrix, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{self, ConfigError};
+
+/// How many lights a scene can have, like `MAX_LIGHTS` in `shaders/lighting.glsl`.
+pub const MAX_LIGHTS: usize = 8;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scene {
+    pub camera: Camera,
+    pub lights: Vec<Light>,
+    pub materials: BTreeMap<String, Material>,
+    pub entities: Vec<Entity>,
+    /// The directory relative paths are resolved against.
+    #[serde(skip)]
+    pub base_dir: PathBuf,
+}
+
+/// Translation, then rotation, then scale, like most editors show them.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Transform {
+    pub translation: [f32; 3],
+    /// Euler angles in degrees, applied around X, then Y, then Z.
+    pub rotation: [f32; 3],
+    pub scale: [f32; 3],
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Camera {
+    pub projection: Projection,
+    /// Where the camera is. The view matrix is its inverse.
+    pub transform: Transform,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Projection {
+    /// One unit per pixel, with the origin in the bottom left corner of the window.
+    Orthographic {
+        #[serde(default = "default_ortho_near")]
+        near: f32,
+        #[serde(default = "default_ortho_far")]
+        far: f32,
+    },
+    Perspective {
+        /// The vertical field of view, in degrees.
+        fov_y: f32,
+        near: f32,
+        far: f32,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Light {
+    Point {
+        position: [f32; 3],
+        #[serde(default = "white")]
+        color: [f32; 3],
+        /// How far the light reaches.
+        radius: f32,
+    },
+    Directional {
+        /// The direction the light travels in.
+        direction: [f32; 3],
+        #[serde(default = "white")]
+        color: [f32; 3],
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Material {
+    /// A PNG, the demo's built-in image when `None`.
+    pub texture: Option<PathBuf>,
+    /// Multiplied with the texture, in linear RGBA.
+    pub color: [f32; 4],
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Entity {
+    pub name: String,
+    /// A key of [`Scene::materials`], the default material when `None`.
+    #[serde(default)]
+    pub material: Option<String>,
+    /// The name of the entity `transform` is relative to, the world when `None`.
+    #[serde(default)]
+    pub parent: Option<String>,
+    // The TOML serializer can't write a plain value after a table, so the tables come last.
+    pub mesh: Mesh,
+    /// Relative to the parent.
+    #[serde(default)]
+    pub transform: Transform,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Mesh {
+    /// A rectangle from the origin to `size` in the XY plane, with the whole texture on it.
+    Sprite { size: [f32; 2] },
+    /// Triangles given in the file. `tex_coords` has one entry per position.
+    Inline {
+        positions: Vec<[f32; 3]>,
+        tex_coords: Vec<[f32; 2]>,
+        indices: Vec<u16>,
+    },
+}
+
+/// The vertices and triangle list indices of a [`Mesh`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MeshData {
+    pub positions: Vec<[f32; 3]>,
+    pub tex_coords: Vec<[f32; 2]>,
+    pub indices: Vec<u16>,
+}
+
+fn default_ortho_near() -> f32 {
+    -1.0
+}
+
+fn default_ortho_far() -> f32 {
+    1.0
+}
+
+fn white() -> [f32; 3] {
+    [1.0; 3]
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: [0.0; 3],
+            rotation: [0.0; 3],
+            scale: [1.0; 3],
+        }
+    }
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Self::Orthographic {
+            near: default_ortho_near(),
+            far: default_ortho_far(),
+        }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            texture: None,
+            color: [1.0; 4],
+        }
+    }
+}
+
+impl Transform {
+    pub fn matrix(&self) -> Matrix4<f32> {
+        let [x, y, z] = self.rotation;
+        let [sx, sy, sz] = self.scale;
+        Matrix4::from_translation(Vector3::from(self.translation))
+            * Matrix4::from_angle_z(Deg(z))
+            * Matrix4::from_angle_y(Deg(y))
+            * Matrix4::from_angle_x(Deg(x))
+            * Matrix4::from_nonuniform_scale(sx, sy, sz)
+    }
+}
+
+impl Camera {
+    pub fn view(&self) -> Matrix4<f32> {
+        self.transform
+            .matrix()
+            .invert()
+            .unwrap_or_else(Matrix4::identity)
+    }
+
+    /// The projection for a window `dimensions` pixels big.
+    pub fn projection(&self, dimensions: [u32; 2]) -> Matrix4<f32> {
+        let [width, height] = dimensions.map(|d| d as f32);
+        match self.projection {
+            Projection::Orthographic { near, far } => {
+                cgmath::ortho(0.0, width, 0.0, height, near, far)
+            }
+            Projection::Perspective { fov_y, near, far } => {
+                cgmath::perspective(Deg(fov_y), width / height, near, far)
+            }
+        }
+    }
+}
+
+impl Light {
+    /// The light as `shaders/lighting.glsl` takes it: the position of a point light with w = 1
+    /// or the direction of a directional one with w = 0, and the color with the radius of a
+    /// point light in w.
+    pub fn shader_data(&self) -> ([f32; 4], [f32; 4]) {
+        match *self {
+            Self::Point {
+                position: [x, y, z],
+                color: [r, g, b],
+                radius,
+            } => ([x, y, z, 1.0], [r, g, b, radius]),
+            Self::Directional {
+                direction: [x, y, z],
+                color: [r, g, b],
+            } => ([x, y, z, 0.0], [r, g, b, 0.0]),
+        }
+    }
+}
+
+impl Mesh {
+    pub fn data(&self) -> MeshData {
+        match self {
+            Self::Sprite { size: [w, h] } => MeshData {
+                positions: vec![
+                    [0.0, 0.0, 0.0],
+                    [*w, 0.0, 0.0],
+                    [*w, *h, 0.0],
+                    [0.0, *h, 0.0],
+                ],
+                tex_coords: vec![[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]],
+                indices: vec![0, 1, 2, 2, 3, 0],
+            },
+            Self::Inline {
+                positions,
+                tex_coords,
+                indices,
+            } => MeshData {
+                positions: positions.clone(),
+                tex_coords: tex_coords.clone(),
+                indices: indices.clone(),
+            },
+        }
+    }
+}
+
+impl Scene {
+    /// Reads and validates the scene in `path`.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let mut scene: Self = config::read(path)?;
+        scene.validate().map_err(|e| ConfigError::new(path, e))?;
+        scene.base_dir = path.parent().unwrap_or(Path::new("")).to_owned();
+        Ok(scene)
+    }
+
+    /// Parses a scene built into a demo, with paths relative to the working directory.
+    pub fn builtin(format: config::Format, text: &str) -> Result<Self, String> {
+        let scene: Self = format.parse(text)?;
+        scene.validate()?;
+        Ok(scene)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        config::write(path, self)
+    }
+
+    /// `path` from the file, relative to the scene file.
+    pub fn resolve(&self, path: &Path) -> PathBuf {
+        self.base_dir.join(path)
+    }
+
+    /// The material `entity` is drawn with.
+    pub fn material(&self, entity: &Entity) -> Material {
+        entity
+            .material
+            .as_ref()
+            .and_then(|name| self.materials.get(name))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// The entity called `name`.
+    pub fn entity(&self, name: &str) -> Option<&Entity> {
+        self.entities.iter().find(|entity| entity.name == name)
+    }
+
+    /// Checks what the types don't: references, ranges and mesh sizes.
+    pub fn validate(&self) -> Result<(), String> {
+        if let Projection::Perspective { fov_y, near, far } = self.camera.projection {
+            if !(fov_y > 0.0 && fov_y < 180.0) {
+                return Err(format!("camera fov_y = {} isn't between 0 and 180", fov_y));
+            }
+            if !(near > 0.0 && far > near) {
+                return Err(format!(
+                    "camera near = {} and far = {} have to be 0 < near < far",
+                    near, far
+                ));
+            }
+        }
+
+        if self.lights.len() > MAX_LIGHTS {
+            return Err(format!(
+                "{} lights are more than the {} that can be shaded with",
+                self.lights.len(),
+                MAX_LIGHTS
+            ));
+        }
+        for light in &self.lights {
+            match light {
+                Light::Point { radius, .. } => {
+                    if *radius <= 0.0 {
+                        return Err(format!("point light radius = {} isn't positive", radius));
+                    }
+                }
+                Light::Directional { direction, .. } => {
+                    if direction.iter().all(|&d| d == 0.0) {
+                        return Err("directional light has no direction".to_owned());
+                    }
+                }
+            }
+        }
+
+        for (name, material) in &self.materials {
+            if material.color.iter().any(|c| !(0.0..=1.0).contains(c)) {
+                return Err(format!(
+                    "material {:?} color has a channel outside 0 to 1",
+                    name
+                ));
+            }
+        }
+
+        for entity in &self.entities {
+            if let Some(material) = &entity.material {
+                if !self.materials.contains_key(material) {
+                    return Err(format!(
+                        "entity {:?} uses material {:?}, which isn't defined",
+                        entity.name, material
+                    ));
+                }
+            }
+            if let Some(parent) = &entity.parent {
+                match self.entities.iter().filter(|e| &e.name == parent).count() {
+                    0 => {
+                        return Err(format!(
+                            "entity {:?} has parent {:?}, which isn't defined",
+                            entity.name, parent
+                        ))
+                    }
+                    1 => (),
+                    _ => {
+                        return Err(format!(
+                            "entity {:?} has parent {:?}, which more than one entity is called",
+                            entity.name, parent
+                        ))
+                    }
+                }
+
+                // A chain longer than the scene has entities has to go around a loop.
+                let mut ancestor = Some(entity);
+                for _ in 0..=self.entities.len() {
+                    ancestor = ancestor
+                        .and_then(|e| e.parent.as_deref())
+                        .and_then(|name| self.entity(name));
+                }
+                if ancestor.is_some() {
+                    return Err(format!("entity {:?} is its own ancestor", entity.name));
+                }
+            }
+            if entity.transform.scale.contains(&0.0) {
+                return Err(format!("entity {:?} has a scale of 0", entity.name));
+            }
+
+            match &entity.mesh {
+                Mesh::Sprite { size: [w, h] } => {
+                    if *w <= 0.0 || *h <= 0.0 {
+                        return Err(format!("entity {:?} sprite is empty", entity.name));
+                    }
+                }
+                Mesh::Inline {
+                    positions,
+                    tex_coords,
+                    indices,
+                } => {
+                    if tex_coords.len() != positions.len() {
+                        return Err(format!(
+                            "entity {:?} has {} positions but {} tex_coords",
+                            entity.name,
+                            positions.len(),
+                            tex_coords.len()
+                        ));
+                    }
+                    if indices.is_empty() || indices.len() % 3 != 0 {
+                        return Err(format!(
+                            "entity {:?} has {} indices, which isn't whole triangles",
+                            entity.name,
+                            indices.len()
+                        ));
+                    }
+                    if let Some(index) = indices.iter().find(|&&i| i as usize >= positions.len()) {
+                        return Err(format!(
+                            "entity {:?} index {} is past its {} positions",
+                            entity.name,
+                            index,
+                            positions.len()
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A scene file, polled for changes.
+pub struct SceneFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    polled: bool,
+}
+
+impl SceneFile {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            modified: None,
+            polled: false,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Loads the scene again when the file changed since the last call, which the first call
+    /// always counts as.
+    pub fn poll(&mut self) -> Option<Result<Scene, ConfigError>> {
+        // A file that is being rewritten may briefly not exist, keep the last known time.
+        let modified = self.path.metadata().and_then(|m| m.modified()).ok();
+        if self.polled && (modified.is_none() || modified == self.modified) {
+            return None;
+        }
+        self.polled = true;
+        self.modified = modified;
+        Some(Scene::load(&self.path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(name: &str, parent: Option<&str>) -> Entity {
+        Entity {
+            name: name.to_owned(),
+            material: None,
+            parent: parent.map(str::to_owned),
+            mesh: Mesh::Sprite { size: [1.0, 1.0] },
+            transform: Transform::default(),
+        }
+    }
+
+    fn scene(entities: Vec<Entity>) -> Scene {
+        Scene {
+            entities,
+            ..Scene::default()
+        }
+    }
+
+    fn invalid(change: impl FnOnce(&mut Scene)) -> String {
+        let mut scene = scene(vec![entity("a", None)]);
+        change(&mut scene);
+        scene.validate().unwrap_err()
+    }
+
+    #[test]
+    fn documented_scene_is_valid() {
+        let text = include_str!("scene.rs")
+            .split("//! ```toml\n")
+            .nth(1)
+            .and_then(|rest| rest.split("//! ```").next())
+            .expect("the module docs have an example")
+            .lines()
+            .map(|line| line.trim_start_matches("//!").trim_start())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let scene = Scene::builtin(config::Format::Toml, &text).unwrap();
+        assert_eq!(scene.entities.len(), 2);
+        assert_eq!(scene.lights.len(), 1);
+    }
+
+    #[test]
+    fn builtin_scenes_are_valid() {
+        for text in [
+            include_str!("../scenes/mvp.toml"),
+            include_str!("../scenes/egui.toml"),
+        ] {
+            Scene::builtin(config::Format::Toml, text).unwrap();
+        }
+    }
+
+    #[test]
+    fn camera_ranges_are_checked() {
+        let perspective = |fov_y, near, far| {
+            invalid(|s| s.camera.projection = Projection::Perspective { fov_y, near, far })
+        };
+        assert!(perspective(0.0, 0.1, 100.0).contains("fov_y"));
+        assert!(perspective(180.0, 0.1, 100.0).contains("fov_y"));
+        assert!(perspective(60.0, 0.0, 100.0).contains("near"));
+        assert!(perspective(60.0, 10.0, 1.0).contains("near"));
+
+        let mut valid = scene(Vec::new());
+        valid.camera.projection = Projection::Perspective {
+            fov_y: 60.0,
+            near: 0.1,
+            far: 100.0,
+        };
+        assert_eq!(valid.validate(), Ok(()));
+    }
+
+    #[test]
+    fn lights_are_checked() {
+        let point = |radius| Light::Point {
+            position: [0.0; 3],
+            color: white(),
+            radius,
+        };
+        assert!(invalid(|s| s.lights.push(point(0.0))).contains("radius"));
+        assert!(invalid(|s| s.lights.push(Light::Directional {
+            direction: [0.0; 3],
+            color: white(),
+        }))
+        .contains("direction"));
+        assert!(invalid(|s| s.lights = vec![point(1.0); MAX_LIGHTS + 1]).contains("lights"));
+
+        let mut valid = scene(Vec::new());
+        valid.lights = vec![point(1.0); MAX_LIGHTS];
+        assert_eq!(valid.validate(), Ok(()));
+    }
+
+    #[test]
+    fn materials_are_checked() {
+        let error = invalid(|s| {
+            s.materials.insert(
+                "red".to_owned(),
+                Material {
+                    texture: None,
+                    color: [1.5, 0.0, 0.0, 1.0],
+                },
+            );
+        });
+        assert!(error.contains("\"red\""), "{}", error);
+        assert!(
+            invalid(|s| s.entities[0].material = Some("missing".to_owned())).contains("missing")
+        );
+    }
+
+    #[test]
+    fn parents_are_checked() {
+        let missing = scene(vec![entity("a", Some("b"))]).validate().unwrap_err();
+        assert!(missing.contains("isn't defined"), "{}", missing);
+
+        let ambiguous = scene(vec![
+            entity("a", Some("b")),
+            entity("b", None),
+            entity("b", None),
+        ])
+        .validate()
+        .unwrap_err();
+        assert!(ambiguous.contains("more than one"), "{}", ambiguous);
+
+        let cycle = scene(vec![entity("a", Some("b")), entity("b", Some("a"))])
+            .validate()
+            .unwrap_err();
+        assert!(cycle.contains("own ancestor"), "{}", cycle);
+        let own_parent = scene(vec![entity("a", Some("a"))]).validate().unwrap_err();
+        assert!(own_parent.contains("own ancestor"), "{}", own_parent);
+
+        let chain = scene(vec![
+            entity("c", Some("b")),
+            entity("b", Some("a")),
+            entity("a", None),
+        ]);
+        assert_eq!(chain.validate(), Ok(()));
+    }
+
+    #[test]
+    fn meshes_are_checked() {
+        let inline = |positions: usize, tex_coords: usize, indices: Vec<u16>| {
+            invalid(|s| {
+                s.entities[0].mesh = Mesh::Inline {
+                    positions: vec![[0.0; 3]; positions],
+                    tex_coords: vec![[0.0; 2]; tex_coords],
+                    indices,
+                }
+            })
+        };
+        assert!(inline(3, 2, vec![0, 1, 2]).contains("tex_coords"));
+        assert!(inline(3, 3, Vec::new()).contains("whole triangles"));
+        assert!(inline(3, 3, vec![0, 1]).contains("whole triangles"));
+        assert!(inline(3, 3, vec![0, 1, 3]).contains("past"));
+        assert!(
+            invalid(|s| s.entities[0].mesh = Mesh::Sprite { size: [0.0, 1.0] }).contains("empty")
+        );
+        assert!(invalid(|s| s.entities[0].transform.scale[2] = 0.0).contains("scale"));
+    }
+}
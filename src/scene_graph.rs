@@ -0,0 +1,495 @@
+//! A hierarchy of nodes, each with a transform relative to its parent.
+//!
+//! Nodes live in one `Vec` and are referred to by [`NodeId`], which stops matching once its
+//! node is removed. Every node caches its world matrix. Changing a transform or a parent only
+//! marks the node dirty, and [`SceneGraph::update`] recomputes the dirty nodes and everything
+//! below them in one pass, so a frame where nothing moved costs a single flag check.
+
+use cgmath::{Matrix4, SquareMatrix};
+
+use crate::scene::{Scene, Transform};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId {
+    index: u32,
+    generation: u32,
+}
+
+#[derive(Clone, Debug)]
+struct Node<T> {
+    name: String,
+    local: Transform,
+    world: Matrix4<f32>,
+    dirty: bool,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    item: Option<T>,
+}
+
+#[derive(Clone, Debug)]
+struct Slot<T> {
+    generation: u32,
+    node: Option<Node<T>>,
+}
+
+/// A node to draw, with the world matrix it's drawn at.
+#[derive(Clone, Copy, Debug)]
+pub struct DrawItem<'a, T> {
+    pub node: NodeId,
+    pub world: Matrix4<f32>,
+    pub item: &'a T,
+}
+
+/// Nodes carrying an optional `T`, which is what a renderer draws for them.
+#[derive(Clone, Debug)]
+pub struct SceneGraph<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+    roots: Vec<NodeId>,
+    dirty: bool,
+}
+
+impl<T> Default for SceneGraph<T> {
+    fn default() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+            roots: Vec::new(),
+            dirty: false,
+        }
+    }
+}
+
+impl SceneGraph<usize> {
+    /// A node for each entity of `scene`, carrying its index in [`Scene::entities`]. Entities
+    /// whose parent isn't in the scene become roots, [`Scene::validate`] rejects those.
+    pub fn from_scene(scene: &Scene) -> Self {
+        let mut graph = Self::new();
+        let ids = scene
+            .entities
+            .iter()
+            .enumerate()
+            .map(|(i, entity)| graph.insert(None, &entity.name, entity.transform, Some(i)))
+            .collect::<Vec<_>>();
+
+        for (entity, &id) in scene.entities.iter().zip(&ids) {
+            let parent = entity.parent.as_ref().and_then(|parent| {
+                scene
+                    .entities
+                    .iter()
+                    .position(|other| &other.name == parent)
+            });
+            if let Some(parent) = parent {
+                graph.set_parent(id, Some(ids[parent]));
+            }
+        }
+
+        graph
+    }
+}
+
+impl<T> SceneGraph<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How many nodes there are.
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.node(id).is_some()
+    }
+
+    /// Adds a node under `parent`, or as a root when `None`.
+    pub fn insert(
+        &mut self,
+        parent: Option<NodeId>,
+        name: &str,
+        local: Transform,
+        item: Option<T>,
+    ) -> NodeId {
+        let node = Node {
+            name: name.to_owned(),
+            local,
+            world: Matrix4::identity(),
+            dirty: true,
+            parent: None,
+            children: Vec::new(),
+            item,
+        };
+
+        let id = match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.node = Some(node);
+                NodeId {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    node: Some(node),
+                });
+                NodeId {
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        };
+
+        self.roots.push(id);
+        self.dirty = true;
+        if parent.is_some() {
+            self.set_parent(id, parent);
+        }
+        id
+    }
+
+    /// Removes `id` and everything below it.
+    pub fn remove(&mut self, id: NodeId) {
+        if !self.contains(id) {
+            return;
+        }
+        self.detach(id);
+
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            let slot = &mut self.slots[id.index as usize];
+            if let Some(node) = slot.node.take() {
+                stack.extend(node.children);
+            }
+            slot.generation = slot.generation.wrapping_add(1);
+            self.free.push(id.index);
+        }
+    }
+
+    /// Moves `id` under `parent`, or to the roots when `None`. Its local transform is kept, so
+    /// it moves along with its new parent. Returns false and changes nothing when `parent` is
+    /// `id` or below it.
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) -> bool {
+        if !self.contains(id) {
+            return false;
+        }
+        if let Some(parent) = parent {
+            if !self.contains(parent) || self.ancestors(parent).any(|a| a == id) {
+                return false;
+            }
+        }
+
+        self.detach(id);
+        match parent {
+            Some(parent) => self.node_mut(parent).unwrap().children.push(id),
+            None => self.roots.push(id),
+        }
+        let node = self.node_mut(id).unwrap();
+        node.parent = parent;
+        node.dirty = true;
+        self.dirty = true;
+        true
+    }
+
+    /// Takes `id` out of its parent's children or the roots.
+    fn detach(&mut self, id: NodeId) {
+        let siblings = match self.node(id).and_then(|node| node.parent) {
+            Some(parent) => &mut self.node_mut(parent).unwrap().children,
+            None => &mut self.roots,
+        };
+        siblings.retain(|&sibling| sibling != id);
+    }
+
+    /// `id` and its parents up to a root.
+    pub fn ancestors(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        std::iter::successors(Some(id).filter(|&id| self.contains(id)), |&id| {
+            self.node(id).and_then(|node| node.parent)
+        })
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.node(id)?.parent
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        self.node(id).map_or(&[], |node| &node.children)
+    }
+
+    pub fn name(&self, id: NodeId) -> Option<&str> {
+        self.node(id).map(|node| node.name.as_str())
+    }
+
+    pub fn set_name(&mut self, id: NodeId, name: &str) {
+        if let Some(node) = self.node_mut(id) {
+            node.name = name.to_owned();
+        }
+    }
+
+    pub fn local(&self, id: NodeId) -> Option<&Transform> {
+        self.node(id).map(|node| &node.local)
+    }
+
+    pub fn set_local(&mut self, id: NodeId, local: Transform) {
+        if let Some(node) = self.node_mut(id) {
+            if node.local != local {
+                node.local = local;
+                node.dirty = true;
+                self.dirty = true;
+            }
+        }
+    }
+
+    /// The world matrix as of the last [`Self::update`].
+    pub fn world(&self, id: NodeId) -> Option<Matrix4<f32>> {
+        self.node(id).map(|node| node.world)
+    }
+
+    pub fn item(&self, id: NodeId) -> Option<&T> {
+        self.node(id)?.item.as_ref()
+    }
+
+    pub fn item_mut(&mut self, id: NodeId) -> Option<&mut T> {
+        self.node_mut(id)?.item.as_mut()
+    }
+
+    /// Every node, parents before their children.
+    pub fn depth_first(&self) -> impl Iterator<Item = NodeId> + '_ {
+        let mut stack = self.roots.iter().rev().copied().collect::<Vec<_>>();
+        std::iter::from_fn(move || {
+            let id = stack.pop()?;
+            stack.extend(self.children(id).iter().rev());
+            Some(id)
+        })
+    }
+
+    /// Recomputes the world matrix of every dirty node and of the nodes below it.
+    pub fn update(&mut self) {
+        if !self.dirty {
+            return;
+        }
+        self.dirty = false;
+
+        // Each entry carries the parent's world matrix and whether it changed.
+        let mut stack = self
+            .roots
+            .iter()
+            .map(|&id| (id, Matrix4::identity(), false))
+            .collect::<Vec<_>>();
+        while let Some((id, parent_world, parent_changed)) = stack.pop() {
+            let node = self.node_mut(id).unwrap();
+            let changed = parent_changed || node.dirty;
+            if changed {
+                node.world = parent_world * node.local.matrix();
+                node.dirty = false;
+            }
+            let world = node.world;
+            stack.extend(node.children.iter().map(|&child| (child, world, changed)));
+        }
+    }
+
+    /// The nodes that carry an item, in the order they were inserted. Call [`Self::update`]
+    /// first, the world matrices are the cached ones.
+    pub fn draw_items(&self) -> impl Iterator<Item = DrawItem<'_, T>> {
+        debug_assert!(!self.dirty, "scene graph drawn without an update");
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            let node = slot.node.as_ref()?;
+            Some(DrawItem {
+                node: NodeId {
+                    index: index as u32,
+                    generation: slot.generation,
+                },
+                world: node.world,
+                item: node.item.as_ref()?,
+            })
+        })
+    }
+
+    fn node(&self, id: NodeId) -> Option<&Node<T>> {
+        let slot = self.slots.get(id.index as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        slot.node.as_ref()
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> Option<&mut Node<T>> {
+        let slot = self.slots.get_mut(id.index as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        slot.node.as_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use super::*;
+    use crate::scene::{Entity, Mesh};
+
+    fn at(x: f32) -> Transform {
+        Transform {
+            translation: [x, 0.0, 0.0],
+            ..Transform::default()
+        }
+    }
+
+    fn translation(x: f32) -> Matrix4<f32> {
+        Matrix4::from_translation(Vector3::new(x, 0.0, 0.0))
+    }
+
+    #[test]
+    fn set_parent_rejects_cycles() {
+        let mut graph = SceneGraph::<()>::new();
+        let a = graph.insert(None, "a", at(1.0), None);
+        let b = graph.insert(Some(a), "b", at(1.0), None);
+        let c = graph.insert(Some(b), "c", at(1.0), None);
+
+        assert!(!graph.set_parent(a, Some(a)));
+        assert!(!graph.set_parent(a, Some(b)));
+        assert!(!graph.set_parent(a, Some(c)));
+        // Nothing changed.
+        assert_eq!(graph.roots(), [a]);
+        assert_eq!(graph.parent(a), None);
+        assert_eq!(graph.children(a), [b]);
+        assert_eq!(graph.ancestors(c).collect::<Vec<_>>(), [c, b, a]);
+
+        assert!(graph.set_parent(c, Some(a)));
+        assert_eq!(graph.children(a), [b, c]);
+        assert!(graph.children(b).is_empty());
+        assert!(graph.set_parent(b, None));
+        assert_eq!(graph.roots(), [a, b]);
+    }
+
+    #[test]
+    fn remove_takes_the_subtree_and_invalidates_its_ids() {
+        let mut graph = SceneGraph::new();
+        let a = graph.insert(None, "a", at(1.0), Some(0));
+        let b = graph.insert(Some(a), "b", at(1.0), Some(1));
+        let c = graph.insert(Some(b), "c", at(1.0), Some(2));
+        let d = graph.insert(Some(a), "d", at(1.0), Some(3));
+
+        graph.remove(b);
+        assert_eq!(graph.len(), 2);
+        assert!(!graph.contains(b));
+        assert!(!graph.contains(c));
+        assert_eq!(graph.children(a), [d]);
+        assert_eq!(graph.item(c), None);
+        assert!(!graph.set_parent(c, None));
+
+        // The freed slots are reused, but the old ids still don't match.
+        let e = graph.insert(None, "e", at(1.0), Some(4));
+        let f = graph.insert(None, "f", at(1.0), Some(5));
+        assert!([b.index, c.index].contains(&e.index));
+        assert!([b.index, c.index].contains(&f.index));
+        assert!(!graph.contains(b) && !graph.contains(c));
+        assert_eq!(graph.name(e), Some("e"));
+        graph.set_name(b, "stale");
+        assert_eq!(graph.name(e), Some("e"));
+        assert_eq!(graph.name(f), Some("f"));
+
+        // Removing a stale id does nothing.
+        graph.remove(b);
+        assert_eq!(graph.len(), 4);
+    }
+
+    #[test]
+    fn update_propagates_changes_down() {
+        let mut graph = SceneGraph::<()>::new();
+        let a = graph.insert(None, "a", at(1.0), None);
+        let b = graph.insert(Some(a), "b", at(2.0), None);
+        let c = graph.insert(Some(b), "c", at(4.0), None);
+        let other = graph.insert(None, "other", at(8.0), None);
+        graph.update();
+        assert_eq!(graph.world(c), Some(translation(7.0)));
+        assert_eq!(graph.world(other), Some(translation(8.0)));
+
+        // Only the changed node and the ones below it are recomputed, so a stale matrix
+        // elsewhere stays.
+        graph.node_mut(other).unwrap().world = Matrix4::identity();
+        graph.set_local(a, at(3.0));
+        assert!(graph.node(a).unwrap().dirty);
+        assert!(!graph.node(b).unwrap().dirty);
+        graph.update();
+        assert_eq!(graph.world(a), Some(translation(3.0)));
+        assert_eq!(graph.world(b), Some(translation(5.0)));
+        assert_eq!(graph.world(c), Some(translation(9.0)));
+        assert_eq!(graph.world(other), Some(Matrix4::identity()));
+        assert!(graph.depth_first().all(|id| !graph.node(id).unwrap().dirty));
+
+        // A node moved to another parent follows it.
+        graph.set_parent(c, Some(a));
+        graph.update();
+        assert_eq!(graph.world(c), Some(translation(7.0)));
+
+        // Nothing dirty, nothing recomputed.
+        graph.node_mut(c).unwrap().world = Matrix4::identity();
+        graph.set_local(b, at(2.0));
+        graph.update();
+        assert_eq!(graph.world(c), Some(Matrix4::identity()));
+    }
+
+    #[test]
+    fn from_scene_builds_the_hierarchy() {
+        let entity = |name: &str, parent: Option<&str>, x| Entity {
+            name: name.to_owned(),
+            material: None,
+            parent: parent.map(str::to_owned),
+            mesh: Mesh::Sprite { size: [1.0, 1.0] },
+            transform: at(x),
+        };
+        // Children can come before their parents in the file.
+        let scene = Scene {
+            entities: vec![
+                entity("badge", Some("quad"), 2.0),
+                entity("quad", None, 1.0),
+                entity("label", Some("badge"), 4.0),
+                entity("background", None, 8.0),
+            ],
+            ..Scene::default()
+        };
+
+        let mut graph = SceneGraph::from_scene(&scene);
+        graph.update();
+        assert_eq!(graph.len(), 4);
+        let roots = graph.roots().to_vec();
+        assert_eq!(
+            roots.iter().map(|&id| graph.name(id)).collect::<Vec<_>>(),
+            [Some("quad"), Some("background")]
+        );
+
+        let draws = graph.draw_items().collect::<Vec<_>>();
+        assert_eq!(
+            draws.iter().map(|draw| *draw.item).collect::<Vec<_>>(),
+            [0, 1, 2, 3]
+        );
+        let worlds = draws.iter().map(|draw| draw.world).collect::<Vec<_>>();
+        assert_eq!(
+            worlds,
+            [
+                translation(3.0),
+                translation(1.0),
+                translation(7.0),
+                translation(8.0)
+            ]
+        );
+        let label = draws[2].node;
+        assert_eq!(
+            graph
+                .ancestors(label)
+                .map(|id| graph.item(id).copied())
+                .collect::<Vec<_>>(),
+            [Some(2), Some(0), Some(1)]
+        );
+    }
+}
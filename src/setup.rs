@@ -0,0 +1,310 @@
+//! The instance, device and swapchain setup every windowed demo starts with, and the image
+//! headless runs draw to instead of a swapchain.
+
+use std::sync::Arc;
+
+use vulkano::device::physical::PhysicalDevice;
+use vulkano::device::{
+    Device, DeviceCreateInfo, DeviceExtensions, Features, Queue, QueueCreateInfo,
+};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{AttachmentImage, ImageUsage, ImageViewAbstract, SwapchainImage};
+use vulkano::instance::{Instance, InstanceCreateInfo, InstanceExtensions};
+use vulkano::memory::allocator::StandardMemoryAllocator;
+use vulkano::shader::{EntryPoint, ShaderModule};
+use vulkano::swapchain::{Surface, Swapchain, SwapchainCreateInfo};
+use vulkano::VulkanLibrary;
+use winit::window::Window;
+
+use crate::debug;
+use crate::error::{Context, Error, Result};
+use crate::gpu::{self, GpuSelector};
+use crate::present::{self, DynamicRange, PresentSettings, SurfaceFormat, SurfaceSupport};
+
+/// Creates an instance with the extensions needed to draw to a window, the HDR color spaces
+/// when they're supported, and validation when it was asked for.
+pub fn create_instance() -> Result<Arc<Instance>> {
+    let library = VulkanLibrary::new()?;
+    let required_extensions = vulkano_win::required_extensions(&library);
+
+    let create_info = debug::instance_create_info(
+        &library,
+        InstanceCreateInfo {
+            enabled_extensions: InstanceExtensions {
+                ext_swapchain_colorspace: library.supported_extensions().ext_swapchain_colorspace,
+                ..required_extensions
+            },
+            enumerate_portability: true,
+            ..Default::default()
+        },
+    );
+
+    Ok(Instance::new(library, create_info)?)
+}
+
+/// Picks the physical device with a queue that can draw, compute and present to `surface`, with
+/// [`gpu::select`]. Returns it with the index of that queue family. Without a surface nothing is
+/// presented, and any queue that can draw and compute will do.
+///
+/// Compute work falls back to that queue when there's no dedicated compute family, so it has to
+/// support both. Vulkan guarantees such a family on any device that can draw.
+pub fn select_physical_device(
+    instance: &Arc<Instance>,
+    selector: Option<&GpuSelector>,
+    surface: Option<&Surface>,
+    device_extensions: &DeviceExtensions,
+) -> Result<(Arc<PhysicalDevice>, u32)> {
+    gpu::select(
+        instance,
+        selector,
+        device_extensions,
+        &Features::empty(),
+        |p| {
+            p.queue_family_properties()
+                .iter()
+                .enumerate()
+                .position(|(i, q)| {
+                    q.queue_flags.graphics
+                        && q.queue_flags.compute
+                        && surface.map_or(true, |surface| {
+                            p.surface_support(i as u32, surface).unwrap_or(false)
+                        })
+                })
+                .map(|i| i as u32)
+        },
+    )
+}
+
+/// The queues of a device. Work without a dedicated queue family goes to the graphics queue.
+pub struct Queues {
+    pub graphics: Arc<Queue>,
+    /// From a family without graphics or compute, usually the DMA engine.
+    pub transfer: Arc<Queue>,
+    /// From a family with compute but no graphics, which runs beside the graphics work.
+    pub compute: Arc<Queue>,
+}
+
+impl Queues {
+    /// The queue families of the queues, each once, to share resources between them.
+    pub fn families(&self) -> Vec<u32> {
+        let mut families = vec![self.graphics.queue_family_index()];
+        for queue in [&self.transfer, &self.compute] {
+            if !families.contains(&queue.queue_family_index()) {
+                families.push(queue.queue_family_index());
+            }
+        }
+        families
+    }
+}
+
+/// The first queue family that can transfer but has neither graphics nor compute.
+pub fn dedicated_transfer_family(physical_device: &PhysicalDevice) -> Option<u32> {
+    physical_device
+        .queue_family_properties()
+        .iter()
+        .position(|q| q.queue_flags.transfer && !q.queue_flags.graphics && !q.queue_flags.compute)
+        .map(|i| i as u32)
+}
+
+/// The first queue family with compute but no graphics.
+pub fn dedicated_compute_family(physical_device: &PhysicalDevice) -> Option<u32> {
+    physical_device
+        .queue_family_properties()
+        .iter()
+        .position(|q| q.queue_flags.compute && !q.queue_flags.graphics)
+        .map(|i| i as u32)
+}
+
+/// Creates a device with a queue from `queue_family_index`, and one from the dedicated transfer
+/// and compute families when the device has them. Pipeline statistics queries are enabled when
+/// the device supports them, for the profiler.
+pub fn create_device(
+    physical_device: Arc<PhysicalDevice>,
+    queue_family_index: u32,
+    device_extensions: DeviceExtensions,
+) -> Result<(Arc<Device>, Queues)> {
+    let enabled_features = Features {
+        pipeline_statistics_query: physical_device
+            .supported_features()
+            .pipeline_statistics_query,
+        ..Features::empty()
+    };
+
+    let transfer_family = dedicated_transfer_family(&physical_device);
+    let compute_family = dedicated_compute_family(&physical_device);
+    log::info!(
+        "queue families: graphics {}, transfer {:?}, compute {:?}",
+        queue_family_index,
+        transfer_family,
+        compute_family
+    );
+
+    // The dedicated families can't be the graphics family, nor each other.
+    let queue_create_infos = [Some(queue_family_index), transfer_family, compute_family]
+        .into_iter()
+        .flatten()
+        .map(|queue_family_index| QueueCreateInfo {
+            queue_family_index,
+            ..Default::default()
+        })
+        .collect();
+
+    let (device, mut queues) = Device::new(
+        physical_device,
+        DeviceCreateInfo {
+            enabled_extensions: device_extensions,
+            enabled_features,
+            queue_create_infos,
+            ..Default::default()
+        },
+    )?;
+    let mut next_queue = || {
+        queues
+            .next()
+            .ok_or(Error::Unsupported("the device has no queue"))
+    };
+
+    let graphics = next_queue()?;
+    let transfer = match transfer_family {
+        Some(_) => next_queue()?,
+        None => graphics.clone(),
+    };
+    let compute = match compute_family {
+        Some(_) => next_queue()?,
+        None => graphics.clone(),
+    };
+
+    Ok((
+        device,
+        Queues {
+            graphics,
+            transfer,
+            compute,
+        },
+    ))
+}
+
+/// Picks the swapchain format for `surface` with [`present::select_surface_format`].
+pub fn select_surface_format(
+    device: &Device,
+    surface: &Surface,
+    dynamic_range: DynamicRange,
+) -> Result<SurfaceFormat> {
+    let formats = device
+        .physical_device()
+        .surface_formats(surface, Default::default())
+        .context("can't get surface formats")?;
+    let surface_format = present::select_surface_format(&formats, dynamic_range)
+        .ok_or(Error::Unsupported("no supported surface format"))?;
+    log::info!(
+        "surface format {:?} in {:?}",
+        surface_format.format,
+        surface_format.color_space
+    );
+
+    Ok(surface_format)
+}
+
+/// Creates a swapchain the size of the window in `surface_format`, with the present mode and
+/// image count of `present` as far as the surface supports them.
+pub fn create_swapchain(
+    device: &Arc<Device>,
+    surface: &Arc<Surface>,
+    image_usage: ImageUsage,
+    surface_format: SurfaceFormat,
+    present: &PresentSettings,
+) -> Result<(Arc<Swapchain>, Vec<Arc<SwapchainImage>>)> {
+    let surface_capabilities = device
+        .physical_device()
+        .surface_capabilities(surface, Default::default())
+        .context("can't get surface capabilities")?;
+    let support = SurfaceSupport::query(device.physical_device(), surface)?;
+
+    Ok(Swapchain::new(
+        device.clone(),
+        surface.clone(),
+        SwapchainCreateInfo {
+            min_image_count: present.image_count(&support),
+            image_format: Some(surface_format.format),
+            image_color_space: surface_format.color_space,
+            image_extent: window(surface).inner_size().into(),
+            image_usage,
+            present_mode: present.present_mode(&support),
+            composite_alpha: surface_capabilities
+                .supported_composite_alpha
+                .iter()
+                .next()
+                .ok_or(Error::Unsupported("no supported composite alpha"))?,
+            ..Default::default()
+        },
+    )?)
+}
+
+/// A view of each swapchain image, as the offscreen image is drawn to through its view too.
+pub fn swapchain_views(
+    images: Vec<Arc<SwapchainImage>>,
+) -> Result<Vec<Arc<dyn ImageViewAbstract>>> {
+    images
+        .into_iter()
+        .map(|image| {
+            let view = ImageView::new_default(image).context("can't create image view")?;
+            Ok(view as Arc<dyn ImageViewAbstract>)
+        })
+        .collect()
+}
+
+/// The size of the image headless runs draw to, when `--resolution` isn't given.
+pub const OFFSCREEN_RESOLUTION: [u32; 2] = [1024, 768];
+
+/// The format of the image headless runs draw to. Every device can render to it, and
+/// `--output` can save it.
+pub const OFFSCREEN_FORMAT: Format = Format::B8G8R8A8_SRGB;
+
+/// Creates the image a headless run draws to instead of a swapchain image. It can be copied
+/// from, for `--output`.
+pub fn create_offscreen_image(
+    memory_allocator: &StandardMemoryAllocator,
+    dimensions: [u32; 2],
+) -> Result<Arc<ImageView<AttachmentImage>>> {
+    let image = AttachmentImage::with_usage(
+        memory_allocator,
+        dimensions,
+        OFFSCREEN_FORMAT,
+        ImageUsage {
+            color_attachment: true,
+            transfer_src: true,
+            ..ImageUsage::empty()
+        },
+    )
+    .context("can't create offscreen image")?;
+    debug::set_image_name(&*image, "offscreen image");
+    ImageView::new_default(image).context("can't create image view")
+}
+
+/// The entry point of `module` called `name`.
+pub fn entry_point<'a>(module: &'a ShaderModule, name: &'static str) -> Result<EntryPoint<'a>> {
+    module
+        .entry_point(name)
+        .ok_or(Error::MissingEntryPoint(name))
+}
+
+/// The window a surface created with `build_vk_surface` draws to.
+pub fn window(surface: &Surface) -> &Window {
+    surface
+        .object()
+        .expect("can't create surface object")
+        .downcast_ref::<Window>()
+        .expect("can't downcast surface object")
+}
+
+/// Creates a new surface for the window `surface` draws to, to replace it once it was lost.
+pub fn recreate_surface(surface: &Surface, instance: &Arc<Instance>) -> Result<Arc<Surface>> {
+    let window = surface
+        .object()
+        .expect("can't create surface object")
+        .clone()
+        .downcast::<Window>()
+        .expect("can't downcast surface object");
+    vulkano_win::create_surface_from_winit(window, instance.clone()).context("can't create surface")
+}
@@ -0,0 +1,327 @@
+//! Runtime GLSL loading, so shaders can be iterated on without rebuilding the demos.
+//!
+//! The demos keep their `vulkano_shaders::shader!` modules as the default. A [`ShaderWatcher`]
+//! is an opt-in replacement that compiles GLSL files from disk with shaderc and tells the frame
+//! loop when they changed, so the affected pipeline can be rebuilt while the demo is running.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use shaderc::{CompileOptions, Compiler, ResolvedInclude, ShaderKind};
+use vulkano::device::Device;
+use vulkano::pipeline::graphics::GraphicsPipelineCreationError;
+use vulkano::shader::{ShaderCreationError, ShaderModule};
+
+use crate::reflect::ReflectError;
+
+/// Environment variable pointing to the directory the demos load their GLSL files from.
+pub const SHADER_DIR_ENV: &str = "VULKAN_RUST_SHADER_DIR";
+
+#[derive(Debug)]
+pub enum ShaderError {
+    /// shaderc couldn't create the named part of itself.
+    Shaderc(&'static str),
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Compile {
+        path: PathBuf,
+        error: shaderc::Error,
+    },
+    Module {
+        path: PathBuf,
+        error: ShaderCreationError,
+    },
+    MissingEntryPoint {
+        path: PathBuf,
+        name: &'static str,
+    },
+    Reflect {
+        path: PathBuf,
+        error: ReflectError,
+    },
+    Pipeline(GraphicsPipelineCreationError),
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Shaderc(what) => write!(f, "can't create shaderc {}", what),
+            Self::Io { path, error } => write!(f, "can't read {}: {}", path.display(), error),
+            // shaderc already prefixes every diagnostic with the file name and line.
+            Self::Compile { error, .. } => write!(f, "{}", error),
+            Self::Module { path, error } => {
+                write!(
+                    f,
+                    "can't create shader module {}: {}",
+                    path.display(),
+                    error
+                )
+            }
+            Self::MissingEntryPoint { path, name } => {
+                write!(f, "{} has no entry point named `{}`", path.display(), name)
+            }
+            Self::Reflect { path, error } => write!(f, "{}: {}", path.display(), error),
+            Self::Pipeline(error) => write!(f, "can't create graphics pipeline: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+struct WatchedFile {
+    path: PathBuf,
+    kind: ShaderKind,
+    modified: Option<SystemTime>,
+}
+
+/// Compiles a set of GLSL files and polls their modification time.
+pub struct ShaderWatcher {
+    compiler: Compiler,
+    files: Vec<WatchedFile>,
+    polled: bool,
+}
+
+impl ShaderWatcher {
+    pub fn new(
+        files: impl IntoIterator<Item = (PathBuf, ShaderKind)>,
+    ) -> Result<Self, ShaderError> {
+        Ok(Self {
+            compiler: Compiler::new().ok_or(ShaderError::Shaderc("compiler"))?,
+            files: files
+                .into_iter()
+                .map(|(path, kind)| WatchedFile {
+                    path,
+                    kind,
+                    modified: None,
+                })
+                .collect(),
+            polled: false,
+        })
+    }
+
+    /// Returns true when any file changed since the last call. The first call always returns
+    /// true, so the initial load goes through the same path as a reload.
+    pub fn poll(&mut self) -> bool {
+        let mut changed = !self.polled;
+        self.polled = true;
+        for file in &mut self.files {
+            // A file that is being rewritten may briefly not exist, keep the last known time.
+            let Ok(modified) = file.path.metadata().and_then(|m| m.modified()) else {
+                continue;
+            };
+            if file.modified != Some(modified) {
+                file.modified = Some(modified);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Makes the next [`Self::poll`] return true, for when the modules have to be loaded again
+    /// on a new device.
+    pub fn reset(&mut self) {
+        self.polled = false;
+    }
+
+    /// Compiles every file with `defines` and creates its shader module, in the order given to
+    /// [`Self::new`].
+    pub fn load(
+        &self,
+        device: &Arc<Device>,
+        defines: &[(&str, Option<&str>)],
+    ) -> Result<Vec<Arc<ShaderModule>>, ShaderError> {
+        self.files
+            .iter()
+            .map(|file| {
+                let words = compile(&self.compiler, &file.path, file.kind, defines)?;
+                load_module(device, &file.path, &words)
+            })
+            .collect()
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|file| file.path.as_path())
+    }
+}
+
+/// Compiles the GLSL file at `path` to SPIR-V, with each of `defines` defined as its value, or
+/// empty when `None`, like `define:` in `vulkano_shaders::shader!`.
+pub fn compile(
+    compiler: &Compiler,
+    path: &Path,
+    kind: ShaderKind,
+    defines: &[(&str, Option<&str>)],
+) -> Result<Vec<u32>, ShaderError> {
+    let source = std::fs::read_to_string(path).map_err(|error| ShaderError::Io {
+        path: path.to_owned(),
+        error,
+    })?;
+
+    let mut options = CompileOptions::new().ok_or(ShaderError::Shaderc("compile options"))?;
+    options.set_generate_debug_info();
+    for &(name, value) in defines {
+        options.add_macro_definition(name, value);
+    }
+    // Included files are looked up next to the file including them. They aren't watched, an
+    // edit shows up once a watched file changes.
+    options.set_include_callback(|name, _, requester, _| {
+        let path = Path::new(requester)
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join(name);
+        let content = std::fs::read_to_string(&path)
+            .map_err(|error| format!("can't read {}: {}", path.display(), error))?;
+        Ok(ResolvedInclude {
+            resolved_name: path.display().to_string(),
+            content,
+        })
+    });
+
+    let artifact = compiler
+        .compile_into_spirv(
+            &source,
+            kind,
+            &path.display().to_string(),
+            "main",
+            Some(&options),
+        )
+        .map_err(|error| ShaderError::Compile {
+            path: path.to_owned(),
+            error,
+        })?;
+
+    if artifact.get_num_warnings() > 0 {
+        log::warn!("{}", artifact.get_warning_messages());
+    }
+
+    Ok(artifact.as_binary().to_vec())
+}
+
+fn load_module(
+    device: &Arc<Device>,
+    path: &Path,
+    words: &[u32],
+) -> Result<Arc<ShaderModule>, ShaderError> {
+    // Safety: the SPIR-V comes straight out of shaderc, which only emits valid modules.
+    unsafe { ShaderModule::from_words(device.clone(), words) }.map_err(|error| {
+        ShaderError::Module {
+            path: path.to_owned(),
+            error,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::time::Duration;
+
+    use super::*;
+
+    const SHADER: &str = "#version 450
+        layout(location = 0) out vec4 f_color;
+        void main() { f_color = vec4(1.0); }";
+
+    /// An empty directory of its own for each test.
+    fn shader_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vulkan_rust-shader-{}", test));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Writes `source` to `path` and moves its modification time forward by `seconds`, as
+    /// saving it again later would, even on file systems with coarse timestamps.
+    fn write(path: &Path, source: &str, seconds: u64) {
+        std::fs::write(path, source).unwrap();
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(seconds))
+            .unwrap();
+    }
+
+    fn watcher(path: &Path) -> ShaderWatcher {
+        ShaderWatcher::new([(path.to_owned(), ShaderKind::Fragment)]).unwrap()
+    }
+
+    #[test]
+    fn only_changed_files_are_reported() {
+        let path = shader_dir("poll").join("shader.frag");
+        write(&path, SHADER, 0);
+        let mut watcher = watcher(&path);
+
+        assert!(watcher.poll(), "the first poll loads the shaders");
+        assert!(!watcher.poll());
+
+        write(&path, SHADER, 10);
+        assert!(watcher.poll());
+        assert!(!watcher.poll());
+
+        // Editors often delete and recreate the file when saving.
+        std::fs::remove_file(&path).unwrap();
+        assert!(!watcher.poll());
+        write(&path, SHADER, 20);
+        assert!(watcher.poll());
+
+        watcher.reset();
+        assert!(watcher.poll());
+    }
+
+    #[test]
+    fn changed_files_are_recompiled() {
+        let path = shader_dir("recompile").join("shader.frag");
+        write(&path, SHADER, 0);
+        let mut watcher = watcher(&path);
+        assert!(watcher.poll());
+        let before = compile(&watcher.compiler, &path, ShaderKind::Fragment, &[]).unwrap();
+
+        write(&path, &SHADER.replace("vec4(1.0)", "vec4(0.5)"), 10);
+        assert!(watcher.poll());
+        let after = compile(&watcher.compiler, &path, ShaderKind::Fragment, &[]).unwrap();
+        assert_ne!(before, after);
+    }
+
+    #[test]
+    fn broken_shaders_report_the_file_and_line() {
+        let path = shader_dir("broken").join("shader.frag");
+        write(&path, &SHADER.replace("vec4(1.0);", "vec4(1.0)"), 0);
+        let watcher = watcher(&path);
+
+        let error = compile(&watcher.compiler, &path, ShaderKind::Fragment, &[]).unwrap_err();
+        assert!(matches!(error, ShaderError::Compile { .. }), "{:?}", error);
+        let message = error.to_string();
+        assert!(message.contains("shader.frag:3"), "{}", message);
+    }
+
+    #[test]
+    fn defines_and_includes_are_applied() {
+        let dir = shader_dir("includes");
+        std::fs::write(dir.join("color.glsl"), "const vec4 COLOR = vec4(1.0);").unwrap();
+        let path = dir.join("shader.frag");
+        let source = "#version 450
+            #include \"color.glsl\"
+            layout(location = 0) out vec4 f_color;
+            void main() {
+            #ifdef RED
+                f_color = COLOR;
+            #endif
+            }";
+        write(&path, source, 0);
+        let watcher = watcher(&path);
+
+        let build = |defines: &[(&str, Option<&str>)]| {
+            compile(&watcher.compiler, &path, ShaderKind::Fragment, defines)
+        };
+        assert_ne!(build(&[]).unwrap(), build(&[("RED", None)]).unwrap());
+
+        std::fs::remove_file(dir.join("color.glsl")).unwrap();
+        let message = build(&[]).unwrap_err().to_string();
+        assert!(message.contains("color.glsl"), "{}", message);
+    }
+}
@@ -0,0 +1,454 @@
+//! Per-frame counters of the work a frame records, and the pipeline statistics the GPU reports
+//! for it.
+//!
+//! The counters are collected by [`CountingBuilder`], which records through to the wrapped
+//! builder and counts as it goes. Commands recorded on the builder directly aren't counted.
+
+use std::ops::AddAssign;
+use std::sync::Arc;
+
+use vulkano::buffer::{BufferContents, TypedBufferAccess};
+use vulkano::command_buffer::allocator::CommandBufferAllocator;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CopyBufferInfo, CopyError, PipelineExecutionError,
+};
+use vulkano::descriptor_set::DescriptorSetsCollection;
+use vulkano::pipeline::graphics::input_assembly::{Index, PrimitiveTopology};
+use vulkano::pipeline::graphics::vertex_input::VertexBuffersCollection;
+use vulkano::pipeline::graphics::viewport::Viewport;
+use vulkano::pipeline::{GraphicsPipeline, PartialStateMode, PipelineBindPoint, PipelineLayout};
+use vulkano::query::QueryPipelineStatisticFlags;
+
+/// What a frame recorded, as counted on the CPU.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameCounters {
+    pub draw_calls: u64,
+    pub vertices: u64,
+    pub triangles: u64,
+    pub descriptor_set_binds: u64,
+    pub pipeline_binds: u64,
+    /// Push constants and buffer copies, plus whatever the demo writes to mapped memory.
+    pub bytes_uploaded: u64,
+}
+
+impl AddAssign for FrameCounters {
+    fn add_assign(&mut self, other: Self) {
+        self.draw_calls += other.draw_calls;
+        self.vertices += other.vertices;
+        self.triangles += other.triangles;
+        self.descriptor_set_binds += other.descriptor_set_binds;
+        self.pipeline_binds += other.pipeline_binds;
+        self.bytes_uploaded += other.bytes_uploaded;
+    }
+}
+
+impl FrameCounters {
+    /// The CSV header matching [`Self::csv_row`].
+    pub const CSV_HEADER: &'static str =
+        "draw_calls,vertices,triangles,descriptor_set_binds,pipeline_binds,bytes_uploaded";
+
+    pub fn csv_row(&self) -> String {
+        format!(
+            "{},{},{},{},{},{}",
+            self.draw_calls,
+            self.vertices,
+            self.triangles,
+            self.descriptor_set_binds,
+            self.pipeline_binds,
+            self.bytes_uploaded
+        )
+    }
+
+    /// Each counter with its name, for display.
+    pub fn rows(&self) -> [(&'static str, u64); 6] {
+        [
+            ("draw calls", self.draw_calls),
+            ("vertices", self.vertices),
+            ("triangles", self.triangles),
+            ("descriptor set binds", self.descriptor_set_binds),
+            ("pipeline binds", self.pipeline_binds),
+            ("bytes uploaded", self.bytes_uploaded),
+        ]
+    }
+}
+
+/// What the GPU reports for a frame's pipeline statistics queries.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PipelineStatistics {
+    pub input_assembly_vertices: u64,
+    pub input_assembly_primitives: u64,
+    pub vertex_shader_invocations: u64,
+    pub clipping_invocations: u64,
+    pub clipping_primitives: u64,
+    pub fragment_shader_invocations: u64,
+}
+
+impl AddAssign for PipelineStatistics {
+    fn add_assign(&mut self, other: Self) {
+        self.input_assembly_vertices += other.input_assembly_vertices;
+        self.input_assembly_primitives += other.input_assembly_primitives;
+        self.vertex_shader_invocations += other.vertex_shader_invocations;
+        self.clipping_invocations += other.clipping_invocations;
+        self.clipping_primitives += other.clipping_primitives;
+        self.fragment_shader_invocations += other.fragment_shader_invocations;
+    }
+}
+
+impl PipelineStatistics {
+    /// The statistics queried, in the order the GPU writes them, which is the order of the
+    /// fields.
+    pub const FLAGS: QueryPipelineStatisticFlags = QueryPipelineStatisticFlags {
+        input_assembly_vertices: true,
+        input_assembly_primitives: true,
+        vertex_shader_invocations: true,
+        clipping_invocations: true,
+        clipping_primitives: true,
+        fragment_shader_invocations: true,
+        ..QueryPipelineStatisticFlags::empty()
+    };
+
+    /// How many values one query writes.
+    pub const LEN: usize = 6;
+
+    /// Reads the values of one query.
+    pub fn from_results(results: &[u64]) -> Self {
+        Self {
+            input_assembly_vertices: results[0],
+            input_assembly_primitives: results[1],
+            vertex_shader_invocations: results[2],
+            clipping_invocations: results[3],
+            clipping_primitives: results[4],
+            fragment_shader_invocations: results[5],
+        }
+    }
+
+    /// The CSV header matching [`Self::csv_row`].
+    pub const CSV_HEADER: &'static str = "input_assembly_vertices,input_assembly_primitives,\
+        vertex_shader_invocations,clipping_invocations,clipping_primitives,\
+        fragment_shader_invocations";
+
+    pub fn csv_row(&self) -> String {
+        format!(
+            "{},{},{},{},{},{}",
+            self.input_assembly_vertices,
+            self.input_assembly_primitives,
+            self.vertex_shader_invocations,
+            self.clipping_invocations,
+            self.clipping_primitives,
+            self.fragment_shader_invocations
+        )
+    }
+
+    /// Each statistic with its name, for display.
+    pub fn rows(&self) -> [(&'static str, u64); 6] {
+        [
+            ("input assembly vertices", self.input_assembly_vertices),
+            ("input assembly primitives", self.input_assembly_primitives),
+            ("vertex shader invocations", self.vertex_shader_invocations),
+            ("clipping invocations", self.clipping_invocations),
+            ("clipping primitives", self.clipping_primitives),
+            (
+                "fragment shader invocations",
+                self.fragment_shader_invocations,
+            ),
+        ]
+    }
+}
+
+/// Records into a command buffer builder and adds what it records to a [`FrameCounters`].
+/// Commands it doesn't wrap can be recorded through [`Self::inner`], uncounted.
+pub struct CountingBuilder<'a, L, A: CommandBufferAllocator> {
+    builder: &'a mut AutoCommandBufferBuilder<L, A>,
+    counters: &'a mut FrameCounters,
+    /// The topology of the bound pipeline, when it's fixed.
+    topology: Option<PrimitiveTopology>,
+}
+
+impl<'a, L, A: CommandBufferAllocator> CountingBuilder<'a, L, A> {
+    pub fn new(
+        builder: &'a mut AutoCommandBufferBuilder<L, A>,
+        counters: &'a mut FrameCounters,
+    ) -> Self {
+        Self {
+            builder,
+            counters,
+            topology: None,
+        }
+    }
+
+    pub fn inner(&mut self) -> &mut AutoCommandBufferBuilder<L, A> {
+        self.builder
+    }
+
+    pub fn bind_pipeline_graphics(&mut self, pipeline: Arc<GraphicsPipeline>) -> &mut Self {
+        self.topology = match pipeline.input_assembly_state().topology {
+            PartialStateMode::Fixed(topology) => Some(topology),
+            PartialStateMode::Dynamic(_) => None,
+        };
+        self.counters.pipeline_binds += 1;
+        self.builder.bind_pipeline_graphics(pipeline);
+        self
+    }
+
+    pub fn bind_descriptor_sets(
+        &mut self,
+        pipeline_bind_point: PipelineBindPoint,
+        pipeline_layout: Arc<PipelineLayout>,
+        first_set: u32,
+        descriptor_sets: impl DescriptorSetsCollection,
+    ) -> &mut Self {
+        let descriptor_sets = descriptor_sets.into_vec();
+        self.counters.descriptor_set_binds += descriptor_sets.len() as u64;
+        self.builder.bind_descriptor_sets(
+            pipeline_bind_point,
+            pipeline_layout,
+            first_set,
+            descriptor_sets,
+        );
+        self
+    }
+
+    pub fn bind_vertex_buffers(
+        &mut self,
+        first_binding: u32,
+        vertex_buffers: impl VertexBuffersCollection,
+    ) -> &mut Self {
+        self.builder
+            .bind_vertex_buffers(first_binding, vertex_buffers);
+        self
+    }
+
+    pub fn bind_index_buffer<Ib, I>(&mut self, index_buffer: Arc<Ib>) -> &mut Self
+    where
+        Ib: TypedBufferAccess<Content = [I]> + 'static,
+        I: Index + 'static,
+    {
+        self.builder.bind_index_buffer(index_buffer);
+        self
+    }
+
+    pub fn set_viewport(
+        &mut self,
+        first_viewport: u32,
+        viewports: impl IntoIterator<Item = Viewport>,
+    ) -> &mut Self {
+        self.builder.set_viewport(first_viewport, viewports);
+        self
+    }
+
+    pub fn push_constants<Pc: BufferContents>(
+        &mut self,
+        pipeline_layout: Arc<PipelineLayout>,
+        offset: u32,
+        push_constants: Pc,
+    ) -> &mut Self {
+        self.counters.bytes_uploaded += std::mem::size_of_val(&push_constants) as u64;
+        self.builder
+            .push_constants(pipeline_layout, offset, push_constants);
+        self
+    }
+
+    pub fn copy_buffer(
+        &mut self,
+        copy_buffer_info: CopyBufferInfo,
+    ) -> Result<&mut Self, CopyError> {
+        let bytes: u64 = copy_buffer_info.regions.iter().map(|r| r.size).sum();
+        self.builder.copy_buffer(copy_buffer_info)?;
+        self.counters.bytes_uploaded += bytes;
+        Ok(self)
+    }
+
+    pub fn draw(
+        &mut self,
+        vertex_count: u32,
+        instance_count: u32,
+        first_vertex: u32,
+        first_instance: u32,
+    ) -> Result<&mut Self, PipelineExecutionError> {
+        self.builder
+            .draw(vertex_count, instance_count, first_vertex, first_instance)?;
+        self.count_draw(vertex_count, instance_count);
+        Ok(self)
+    }
+
+    pub fn draw_indexed(
+        &mut self,
+        index_count: u32,
+        instance_count: u32,
+        first_index: u32,
+        vertex_offset: i32,
+        first_instance: u32,
+    ) -> Result<&mut Self, PipelineExecutionError> {
+        self.builder.draw_indexed(
+            index_count,
+            instance_count,
+            first_index,
+            vertex_offset,
+            first_instance,
+        )?;
+        self.count_draw(index_count, instance_count);
+        Ok(self)
+    }
+
+    fn count_draw(&mut self, vertex_count: u32, instance_count: u32) {
+        let vertices = vertex_count as u64 * instance_count as u64;
+        let triangles = match self.topology {
+            Some(PrimitiveTopology::TriangleList) => vertex_count as u64 / 3,
+            Some(PrimitiveTopology::TriangleStrip | PrimitiveTopology::TriangleFan) => {
+                vertex_count.saturating_sub(2) as u64
+            }
+            _ => 0,
+        };
+
+        self.counters.draw_calls += 1;
+        self.counters.vertices += vertices;
+        self.counters.triangles += triangles * instance_count as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every statistic in the order of its bit, which is the order a query writes the enabled
+    /// ones in, with whether [`PipelineStatistics::FLAGS`] enables it.
+    fn flags_in_bit_order() -> [(&'static str, bool); 13] {
+        let flags = PipelineStatistics::FLAGS;
+        [
+            ("input_assembly_vertices", flags.input_assembly_vertices),
+            ("input_assembly_primitives", flags.input_assembly_primitives),
+            ("vertex_shader_invocations", flags.vertex_shader_invocations),
+            (
+                "geometry_shader_invocations",
+                flags.geometry_shader_invocations,
+            ),
+            (
+                "geometry_shader_primitives",
+                flags.geometry_shader_primitives,
+            ),
+            ("clipping_invocations", flags.clipping_invocations),
+            ("clipping_primitives", flags.clipping_primitives),
+            (
+                "fragment_shader_invocations",
+                flags.fragment_shader_invocations,
+            ),
+            (
+                "tessellation_control_shader_patches",
+                flags.tessellation_control_shader_patches,
+            ),
+            (
+                "tessellation_evaluation_shader_invocations",
+                flags.tessellation_evaluation_shader_invocations,
+            ),
+            (
+                "compute_shader_invocations",
+                flags.compute_shader_invocations,
+            ),
+            ("task_shader_invocations", flags.task_shader_invocations),
+            ("mesh_shader_invocations", flags.mesh_shader_invocations),
+        ]
+    }
+
+    fn statistic(statistics: &PipelineStatistics, name: &str) -> u64 {
+        match name {
+            "input_assembly_vertices" => statistics.input_assembly_vertices,
+            "input_assembly_primitives" => statistics.input_assembly_primitives,
+            "vertex_shader_invocations" => statistics.vertex_shader_invocations,
+            "clipping_invocations" => statistics.clipping_invocations,
+            "clipping_primitives" => statistics.clipping_primitives,
+            "fragment_shader_invocations" => statistics.fragment_shader_invocations,
+            _ => panic!("{} isn't kept", name),
+        }
+    }
+
+    fn statistics() -> PipelineStatistics {
+        PipelineStatistics::from_results(&[1, 2, 3, 4, 5, 6])
+    }
+
+    #[test]
+    fn results_are_read_in_the_order_of_the_flags() {
+        let enabled: Vec<_> = flags_in_bit_order()
+            .into_iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(enabled.len(), PipelineStatistics::LEN);
+
+        let results: Vec<u64> = (0..PipelineStatistics::LEN as u64)
+            .map(|i| 100 + i)
+            .collect();
+        let statistics = PipelineStatistics::from_results(&results);
+        for (i, name) in enabled.into_iter().enumerate() {
+            assert_eq!(statistic(&statistics, name), results[i], "{}", name);
+        }
+    }
+
+    #[test]
+    fn csv_rows_match_their_headers() {
+        let statistics = statistics();
+        let header: Vec<_> = PipelineStatistics::CSV_HEADER.split(',').collect();
+        let row: Vec<u64> = statistics
+            .csv_row()
+            .split(',')
+            .map(|value| value.parse().unwrap())
+            .collect();
+        assert_eq!(header.len(), row.len());
+        for (name, value) in header.into_iter().zip(row) {
+            assert_eq!(statistic(&statistics, name), value, "{}", name);
+        }
+
+        let counters = FrameCounters {
+            draw_calls: 1,
+            vertices: 2,
+            triangles: 3,
+            descriptor_set_binds: 4,
+            pipeline_binds: 5,
+            bytes_uploaded: 6,
+        };
+        assert_eq!(
+            FrameCounters::CSV_HEADER.split(',').count(),
+            counters.rows().len()
+        );
+        assert_eq!(counters.csv_row(), "1,2,3,4,5,6");
+    }
+
+    #[test]
+    fn counters_add_up_field_by_field() {
+        let mut counters = FrameCounters {
+            draw_calls: 1,
+            vertices: 2,
+            triangles: 3,
+            descriptor_set_binds: 4,
+            pipeline_binds: 5,
+            bytes_uploaded: 6,
+        };
+        counters += FrameCounters {
+            draw_calls: 10,
+            vertices: 20,
+            triangles: 30,
+            descriptor_set_binds: 40,
+            pipeline_binds: 50,
+            bytes_uploaded: 60,
+        };
+        assert_eq!(
+            counters,
+            FrameCounters {
+                draw_calls: 11,
+                vertices: 22,
+                triangles: 33,
+                descriptor_set_binds: 44,
+                pipeline_binds: 55,
+                bytes_uploaded: 66,
+            }
+        );
+        counters += FrameCounters::default();
+        assert_eq!(counters.csv_row(), "11,22,33,44,55,66");
+
+        let mut statistics = statistics();
+        statistics += PipelineStatistics::from_results(&[10, 20, 30, 40, 50, 60]);
+        assert_eq!(
+            statistics,
+            PipelineStatistics::from_results(&[11, 22, 33, 44, 55, 66])
+        );
+    }
+}
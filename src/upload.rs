@@ -0,0 +1,170 @@
+//! Uploads to device-local memory, staged through host-visible buffers.
+//!
+//! Every upload is recorded into one command buffer, so a demo's geometry and textures go to the
+//! GPU in a single submission. With the queues of [`crate::setup::create_device`], buffers are
+//! copied on the transfer queue instead and shared with every queue family, so they need no
+//! ownership transfer. Images are still uploaded on the graphics queue, their layout transitions
+//! can't happen on a transfer-only queue.
+
+use std::sync::Arc;
+
+use vulkano::buffer::{
+    BufferAccess, BufferContents, BufferUsage, CpuAccessibleBuffer, DeviceLocalBuffer,
+    TypedBufferAccess,
+};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferInfo, PrimaryAutoCommandBuffer,
+    PrimaryCommandBufferAbstract,
+};
+use vulkano::device::Queue;
+use vulkano::memory::allocator::MemoryAllocator;
+use vulkano::sync::GpuFuture;
+
+use crate::error::{Context, Result};
+use crate::setup::Queues;
+
+pub struct Uploader {
+    queue: Arc<Queue>,
+    builder: AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    /// Where the buffer copies go, when there is a dedicated transfer queue.
+    transfer: Option<(
+        Arc<Queue>,
+        AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    )>,
+    /// The queue families the uploaded buffers are used from.
+    queue_family_indices: Vec<u32>,
+    staged_bytes: u64,
+}
+
+impl Uploader {
+    /// Records uploads to be submitted to `queue`.
+    pub fn new(
+        command_buffer_allocator: &StandardCommandBufferAllocator,
+        queue: Arc<Queue>,
+    ) -> Result<Self> {
+        let builder = AutoCommandBufferBuilder::primary(
+            command_buffer_allocator,
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .context("can't create upload command buffer builder")?;
+
+        Ok(Self {
+            queue_family_indices: vec![queue.queue_family_index()],
+            queue,
+            builder,
+            transfer: None,
+            staged_bytes: 0,
+        })
+    }
+
+    /// Records uploads for `queues`, copying buffers on the transfer queue. The buffers can be
+    /// used from any of the queues.
+    pub fn for_queues(
+        command_buffer_allocator: &StandardCommandBufferAllocator,
+        queues: &Queues,
+    ) -> Result<Self> {
+        let mut uploader = Self::new(command_buffer_allocator, queues.graphics.clone())?;
+        uploader.queue_family_indices = queues.families();
+
+        if queues.transfer.queue_family_index() != queues.graphics.queue_family_index() {
+            let builder = AutoCommandBufferBuilder::primary(
+                command_buffer_allocator,
+                queues.transfer.queue_family_index(),
+                CommandBufferUsage::OneTimeSubmit,
+            )
+            .context("can't create transfer command buffer builder")?;
+            uploader.transfer = Some((queues.transfer.clone(), builder));
+        }
+
+        Ok(uploader)
+    }
+
+    /// The command buffer on the graphics queue, for uploads vulkano records itself like
+    /// `ImmutableImage::from_iter`.
+    pub fn builder(&mut self) -> &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer> {
+        &mut self.builder
+    }
+
+    /// Creates a device-local buffer holding `data`. `usage` is what the buffer is used for
+    /// afterwards, the transfer usage is added here.
+    pub fn buffer_from_iter<T, I>(
+        &mut self,
+        allocator: &(impl MemoryAllocator + ?Sized),
+        usage: BufferUsage,
+        data: I,
+    ) -> Result<Arc<DeviceLocalBuffer<[T]>>>
+    where
+        [T]: BufferContents,
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let staging = CpuAccessibleBuffer::from_iter(
+            allocator,
+            BufferUsage {
+                transfer_src: true,
+                ..BufferUsage::empty()
+            },
+            false,
+            data,
+        )
+        .context("can't create staging buffer")?;
+
+        let buffer = DeviceLocalBuffer::<[T]>::array(
+            allocator,
+            staging.len(),
+            BufferUsage {
+                transfer_dst: true,
+                ..usage
+            },
+            self.queue_family_indices.iter().copied(),
+        )
+        .context("can't create device local buffer")?;
+
+        let builder = match &mut self.transfer {
+            Some((_, builder)) => builder,
+            None => &mut self.builder,
+        };
+        self.staged_bytes += staging.size();
+        builder
+            .copy_buffer(CopyBufferInfo::buffers(staging, buffer.clone()))
+            .context("can't copy staging buffer")?;
+
+        Ok(buffer)
+    }
+
+    /// Submits every upload recorded so far. The returned future has to be waited for, or
+    /// chained before the first use of what was uploaded.
+    pub fn submit(self) -> Result<Box<dyn GpuFuture>> {
+        log::debug!("uploading {} staged bytes", self.staged_bytes);
+
+        let graphics = self
+            .builder
+            .build()
+            .context("can't build upload command buffer")?
+            .execute(self.queue)
+            .context("can't execute upload command buffer")?;
+
+        let Some((queue, builder)) = self.transfer else {
+            return Ok(graphics.boxed());
+        };
+
+        // The semaphore makes whatever is chained after this wait for the copies.
+        let transfer = builder
+            .build()
+            .context("can't build transfer command buffer")?
+            .execute(queue)
+            .context("can't execute transfer command buffer")?
+            .then_signal_semaphore_and_flush()
+            .context("can't flush transfer command buffer")?;
+
+        Ok(transfer.join(graphics).boxed())
+    }
+
+    /// Submits every upload recorded so far and waits until they're done.
+    pub fn submit_and_wait(self) -> Result<()> {
+        self.submit()?.then_signal_fence_and_flush()?.wait(None)?;
+        Ok(())
+    }
+}